@@ -0,0 +1,417 @@
+use std::{collections::BTreeMap, fs, path::Path};
+
+use anyhow::{anyhow, bail, Result};
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::{
+    abi::{canonical_signature, check_arguments, decode, encode, parse_tokens, ParamType, Token},
+    parser::decode_hex,
+    signatures::{selector, topic},
+    util::to_bytes32,
+    Uint256,
+};
+
+#[derive(Debug, Clone, Deserialize)]
+struct JsonParam {
+    #[serde(default)]
+    name: String,
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    components: Vec<JsonParam>,
+    #[serde(default)]
+    indexed: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct JsonItem {
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    inputs: Vec<JsonParam>,
+    #[serde(default)]
+    outputs: Vec<JsonParam>,
+    #[serde(default, rename = "stateMutability")]
+    state_mutability: String,
+    #[serde(default)]
+    anonymous: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Param {
+    pub name: String,
+    pub kind: ParamType,
+    pub indexed: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Function {
+    pub name: String,
+    pub inputs: Vec<Param>,
+    pub outputs: Vec<Param>,
+    pub state_mutability: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Event {
+    pub name: String,
+    pub inputs: Vec<Param>,
+    pub anonymous: bool,
+}
+
+// custom error, encoded like a function call
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Error {
+    pub name: String,
+    pub inputs: Vec<Param>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Abi {
+    pub functions: Vec<Function>,
+    pub events: Vec<Event>,
+    pub errors: Vec<Error>,
+}
+
+// compiler output with both the ABI and the code
+#[derive(Debug, Clone, Default)]
+pub struct Artifact {
+    pub abi: Abi,
+    // creation code, empty for abstract contracts and interfaces
+    pub bytecode: Vec<u8>,
+    pub deployed_bytecode: Vec<u8>,
+    // compressed solc source maps, Foundry only
+    pub source_map: Option<String>,
+    pub deployed_source_map: Option<String>,
+    // source file paths by source id
+    pub sources: BTreeMap<usize, String>,
+}
+
+fn types(params: &[Param]) -> Vec<ParamType> {
+    params.iter().map(|p| p.kind.clone()).collect()
+}
+
+impl JsonParam {
+    // tuples are written out as `(t1,t2)` followed by any array suffix
+    fn canonical(&self) -> String {
+        match self.kind.strip_prefix("tuple") {
+            Some(suffix) => {
+                let components: Vec<_> = self.components.iter().map(|c| c.canonical()).collect();
+                format!("({}){}", components.join(","), suffix)
+            }
+            None => self.kind.clone(),
+        }
+    }
+
+    fn to_param(&self) -> Result<Param> {
+        Ok(Param {
+            name: self.name.clone(),
+            kind: ParamType::parse(&self.canonical())?,
+            indexed: self.indexed,
+        })
+    }
+}
+
+fn to_params(params: &[JsonParam]) -> Result<Vec<Param>> {
+    params.iter().map(|p| p.to_param()).collect()
+}
+
+impl Function {
+    pub fn signature(&self) -> String {
+        canonical_signature(&self.name, &types(&self.inputs))
+    }
+
+    pub fn selector(&self) -> u32 {
+        selector(&self.signature())
+    }
+
+    pub fn encode_input(&self, tokens: &[Token]) -> Result<Vec<u8>> {
+        check_arguments(&types(&self.inputs), tokens)?;
+        Ok([self.selector().to_be_bytes().to_vec(), encode(tokens)].concat())
+    }
+
+    // encode calldata from arguments written as on the command line
+    pub fn encode_input_str(&self, values: &[&str]) -> Result<Vec<u8>> {
+        self.encode_input(&parse_tokens(&types(&self.inputs), values)?)
+    }
+
+    pub fn decode_output(&self, data: &[u8]) -> Result<Vec<Token>> {
+        decode(&types(&self.outputs), data)
+    }
+}
+
+impl Event {
+    pub fn signature(&self) -> String {
+        canonical_signature(&self.name, &types(&self.inputs))
+    }
+
+    pub fn topic(&self) -> Uint256 {
+        Uint256::from_bytes_be(&topic(&self.signature()))
+    }
+
+    // decode the parameters of a log, in declaration order
+    pub fn decode_log(&self, topics: &[Uint256], data: &[u8]) -> Result<Vec<Token>> {
+        let mut topics = topics.iter();
+        if !self.anonymous && topics.next() != Some(&self.topic()) {
+            bail!("Topic mismatch for {}", self.name);
+        }
+        let data_types: Vec<_> = self
+            .inputs
+            .iter()
+            .filter(|p| !p.indexed)
+            .map(|p| p.kind.clone())
+            .collect();
+        let mut data_tokens = decode(&data_types, data)?.into_iter();
+
+        let mut tokens = vec![];
+        for param in &self.inputs {
+            let token = if param.indexed {
+                let word = topics
+                    .next()
+                    .ok_or(anyhow!("Missing topic for {}", param.name))?;
+                // indexed dynamic values are only present as their hash
+                if param.kind.is_dynamic() || matches!(param.kind, ParamType::Tuple(_)) {
+                    Token::FixedBytes(to_bytes32(word).to_vec())
+                } else {
+                    decode(std::slice::from_ref(&param.kind), &to_bytes32(word))?.remove(0)
+                }
+            } else {
+                data_tokens.next().unwrap()
+            };
+            tokens.push(token);
+        }
+        Ok(tokens)
+    }
+}
+
+impl Error {
+    pub fn signature(&self) -> String {
+        canonical_signature(&self.name, &types(&self.inputs))
+    }
+
+    pub fn selector(&self) -> u32 {
+        selector(&self.signature())
+    }
+}
+
+impl Abi {
+    pub fn from_json(json: &str) -> Result<Self> {
+        let value: Value = serde_json::from_str(json)?;
+        Self::from_value(value)
+    }
+
+    fn from_value(value: Value) -> Result<Self> {
+        let items: Vec<JsonItem> = serde_json::from_value(value)?;
+        let mut abi = Abi::default();
+        for item in items {
+            match item.kind.as_str() {
+                "function" => abi.functions.push(Function {
+                    name: item.name,
+                    inputs: to_params(&item.inputs)?,
+                    outputs: to_params(&item.outputs)?,
+                    state_mutability: item.state_mutability,
+                }),
+                "event" => abi.events.push(Event {
+                    name: item.name,
+                    inputs: to_params(&item.inputs)?,
+                    anonymous: item.anonymous,
+                }),
+                "error" => abi.errors.push(Error {
+                    name: item.name,
+                    inputs: to_params(&item.inputs)?,
+                }),
+                // constructor, fallback and receive have no selector
+                _ => {}
+            }
+        }
+        Ok(abi)
+    }
+
+    // look up by name, or by full signature when overloaded
+    pub fn function(&self, name: &str) -> Result<&Function> {
+        let mut matches = self
+            .functions
+            .iter()
+            .filter(|f| f.name == name || f.signature() == name);
+        let function = matches.next().ok_or(anyhow!("No function {}", name))?;
+        if matches.next().is_some() {
+            bail!("{} is overloaded, use the full signature", name);
+        }
+        Ok(function)
+    }
+
+    pub fn function_by_selector(&self, selector: u32) -> Option<&Function> {
+        self.functions.iter().find(|f| f.selector() == selector)
+    }
+
+    pub fn event_by_topic(&self, topic: &Uint256) -> Option<&Event> {
+        self.events
+            .iter()
+            .find(|e| !e.anonymous && e.topic() == *topic)
+    }
+
+    pub fn error_by_selector(&self, selector: u32) -> Option<&Error> {
+        self.errors.iter().find(|e| e.selector() == selector)
+    }
+}
+
+// unlinked library addresses, `__$<34 hex>$__` or `__<name>__` before solc 0.5, take the
+// 40 characters of the address and are zero-filled
+fn zero_placeholders(hex: &str) -> String {
+    let hex = hex.strip_prefix("0x").unwrap_or(hex);
+    // anything else is left for `decode_hex` to reject
+    if !hex.is_ascii() {
+        return hex.to_string();
+    }
+    let mut filled = String::with_capacity(hex.len());
+    let mut rest = hex;
+    while let Some(start) = rest.find("__") {
+        let end = (start + 40).min(rest.len());
+        filled.push_str(&rest[..start]);
+        filled.push_str(&"0".repeat(end - start));
+        rest = &rest[end..];
+    }
+    filled.push_str(rest);
+    filled
+}
+
+// `"0x.."` as used by Hardhat or `{"object": "0x.."}` as used by Foundry
+fn bytecode_field(value: Option<&Value>) -> Result<Vec<u8>> {
+    let hex = match value {
+        Some(Value::String(s)) => s.as_str(),
+        Some(Value::Object(o)) => o.get("object").and_then(|v| v.as_str()).unwrap_or(""),
+        _ => "",
+    };
+    Ok(decode_hex(&zero_placeholders(hex))?)
+}
+
+fn source_map_field(value: Option<&Value>) -> Option<String> {
+    Some(value?.get("sourceMap")?.as_str()?.to_string())
+}
+
+impl Artifact {
+    // a Foundry or Hardhat artifact, or a bare ABI array
+    pub fn from_json(json: &str) -> Result<Self> {
+        let mut value: Value = serde_json::from_str(json)?;
+        if value.is_array() {
+            return Ok(Artifact {
+                abi: Abi::from_value(value)?,
+                ..Default::default()
+            });
+        }
+        let abi = value
+            .get_mut("abi")
+            .map(Value::take)
+            .ok_or(anyhow!("Artifact has no abi"))?;
+        let mut sources = BTreeMap::new();
+        let id = value.get("id").and_then(|v| v.as_u64());
+        let path = value.pointer("/ast/absolutePath").and_then(|v| v.as_str());
+        if let (Some(id), Some(path)) = (id, path) {
+            sources.insert(id as usize, path.to_string());
+        }
+        Ok(Artifact {
+            abi: Abi::from_value(abi)?,
+            bytecode: bytecode_field(value.get("bytecode"))?,
+            deployed_bytecode: bytecode_field(value.get("deployedBytecode"))?,
+            source_map: source_map_field(value.get("bytecode")),
+            deployed_source_map: source_map_field(value.get("deployedBytecode")),
+            sources,
+        })
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        Self::from_json(&fs::read_to_string(path)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Artifact;
+    use crate::{abi::Token, Uint256};
+
+    const FOUNDRY: &str = r#"{
+        "abi": [
+            {"type": "constructor", "inputs": [], "stateMutability": "nonpayable"},
+            {"type": "function", "name": "add", "stateMutability": "pure",
+             "inputs": [{"name": "a", "type": "uint256"}, {"name": "b", "type": "uint256"}],
+             "outputs": [{"name": "", "type": "uint256"}]},
+            {"type": "event", "name": "Added", "anonymous": false,
+             "inputs": [{"name": "who", "type": "address", "indexed": true},
+                        {"name": "sum", "type": "uint256", "indexed": false}]},
+            {"type": "error", "name": "Overflow", "inputs": []}
+        ],
+        "bytecode": {"object": "0x600f8060093d393df36000356020350160005260206000f3"},
+        "deployedBytecode": {"object": "0x6000356020350160005260206000f3"}
+    }"#;
+
+    #[test]
+    fn test_load_foundry_artifact() {
+        let artifact = Artifact::from_json(FOUNDRY).unwrap();
+        assert_eq!(artifact.bytecode.len(), 24);
+        assert_eq!(artifact.deployed_bytecode.len(), 15);
+
+        let add = artifact.abi.function("add").unwrap();
+        assert_eq!(add.selector(), 0x771602f7);
+        let calldata = add.encode_input_str(&["1", "2"]).unwrap();
+        assert_eq!(calldata.len(), 4 + 64);
+        assert_eq!(
+            add.decode_output(&calldata[36..]).unwrap(),
+            vec![Token::Uint(2u32.into())]
+        );
+        assert!(add
+            .encode_input(&[Token::Bytes(vec![1]), Token::Uint(2u32.into())])
+            .is_err());
+        assert_eq!(artifact.abi.errors[0].signature(), "Overflow()");
+    }
+
+    #[test]
+    fn test_decode_log() {
+        let artifact = Artifact::from_json(FOUNDRY).unwrap();
+        let added = &artifact.abi.events[0];
+        assert_eq!(added.signature(), "Added(address,uint256)");
+        let who = Uint256::from(0xbeefu32);
+        let data = crate::util::to_bytes32(&3u32.into());
+        let tokens = added.decode_log(&[added.topic(), who], &data).unwrap();
+        let mut address = [0u8; 20];
+        address[18..].copy_from_slice(&[0xbe, 0xef]);
+        assert_eq!(
+            tokens,
+            vec![Token::Address(address), Token::Uint(3u32.into())]
+        );
+        assert_eq!(
+            artifact.abi.event_by_topic(&added.topic()).unwrap().name,
+            "Added"
+        );
+    }
+
+    #[test]
+    fn test_load_hardhat_artifact() {
+        let artifact = Artifact::from_json(
+            r#"{"contractName": "Foo", "abi": [], "bytecode": "0x6000", "deployedBytecode": "0x"}"#,
+        )
+        .unwrap();
+        assert_eq!(artifact.bytecode, vec![0x60, 0x00]);
+        assert!(artifact.deployed_bytecode.is_empty());
+    }
+
+    #[test]
+    fn test_unlinked_library() {
+        // PUSH20 <library> EXTCODESIZE, with the library left unlinked
+        let artifact = Artifact::from_json(
+            r#"{
+                "abi": [{"type": "function", "name": "f", "inputs": [], "outputs": [],
+                         "stateMutability": "view"}],
+                "bytecode": {"object": "0x73__$0123456789abcdef0123456789abcdef01$__3b"},
+                "deployedBytecode": {"object": "0x73__Lib___________________________________3b"}
+            }"#,
+        )
+        .unwrap();
+        let expected = [vec![0x73], vec![0; 20], vec![0x3b]].concat();
+        assert_eq!(artifact.bytecode, expected);
+        assert_eq!(artifact.deployed_bytecode, expected);
+        assert_eq!(artifact.abi.function("f").unwrap().signature(), "f()");
+    }
+}
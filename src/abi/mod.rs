@@ -0,0 +1,700 @@
+pub mod json;
+
+use std::fmt;
+
+use anyhow::{anyhow, bail, Result};
+use num_bigint::BigInt;
+use num_traits::{Num, Zero};
+
+use crate::{
+    signatures::selector,
+    util::{from_signed, to_bytes32, to_signed},
+    Address, Uint256,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParamType {
+    Uint(usize),
+    Int(usize),
+    Address,
+    Bool,
+    FixedBytes(usize),
+    // external function pointer, an address and a selector encoded like bytes24
+    Function,
+    Bytes,
+    String,
+    Array(Box<ParamType>),
+    FixedArray(Box<ParamType>, usize),
+    Tuple(Vec<ParamType>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Token {
+    Uint(Uint256),
+    Int(BigInt),
+    Address(Address),
+    Bool(bool),
+    FixedBytes(Vec<u8>),
+    Bytes(Vec<u8>),
+    String(String),
+    Array(Vec<Token>),
+    FixedArray(Vec<Token>),
+    Tuple(Vec<Token>),
+}
+
+// decoded REVERT payload
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RevertReason {
+    // Error(string)
+    Error(String),
+    // Panic(uint256)
+    Panic(Uint256),
+    // empty payload or a custom error
+    Unknown(Vec<u8>),
+}
+
+const ERROR_SELECTOR: u32 = 0x08c379a0;
+const PANIC_SELECTOR: u32 = 0x4e487b71;
+
+// split on commas which are not nested in brackets or quotes
+fn split_top_level(input: &str) -> Vec<&str> {
+    let mut result = vec![];
+    let mut depth = 0i32;
+    let mut quoted = false;
+    let mut start = 0;
+    for (i, c) in input.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            '(' | '[' if !quoted => depth += 1,
+            ')' | ']' if !quoted => depth -= 1,
+            ',' if !quoted && depth == 0 => {
+                result.push(input[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    let last = input[start..].trim();
+    if !last.is_empty() || !result.is_empty() {
+        result.push(last);
+    }
+    result
+}
+
+impl ParamType {
+    pub fn parse(input: &str) -> Result<Self> {
+        let input = input.trim();
+        // array suffixes bind last, `uint256[2][]` is a dynamic array of uint256[2]
+        if let Some(rest) = input.strip_suffix(']') {
+            let open = rest.rfind('[').ok_or(anyhow!("Invalid type: {}", input))?;
+            let inner = Box::new(Self::parse(&rest[..open])?);
+            let size = &rest[open + 1..];
+            return if size.is_empty() {
+                Ok(ParamType::Array(inner))
+            } else {
+                Ok(ParamType::FixedArray(inner, size.parse()?))
+            };
+        }
+        if let Some(inner) = input.strip_prefix('(').and_then(|s| s.strip_suffix(')')) {
+            return Ok(ParamType::Tuple(parse_types(inner)?));
+        }
+        if let Some(inner) = input
+            .strip_prefix("tuple(")
+            .and_then(|s| s.strip_suffix(')'))
+        {
+            return Ok(ParamType::Tuple(parse_types(inner)?));
+        }
+
+        let bits = |s: &str| -> Result<usize> {
+            let bits = if s.is_empty() { 256 } else { s.parse()? };
+            if bits == 0 || bits > 256 || bits % 8 != 0 {
+                bail!("Invalid type: {}", input);
+            }
+            Ok(bits)
+        };
+        let kind = match input {
+            "address" => ParamType::Address,
+            "bool" => ParamType::Bool,
+            "bytes" => ParamType::Bytes,
+            "string" => ParamType::String,
+            "function" => ParamType::Function,
+            s if s.starts_with("uint") => ParamType::Uint(bits(&s[4..])?),
+            s if s.starts_with("int") => ParamType::Int(bits(&s[3..])?),
+            s if s.starts_with("bytes") => {
+                let size: usize = s[5..].parse()?;
+                if size == 0 || size > 32 {
+                    bail!("Invalid type: {}", input);
+                }
+                ParamType::FixedBytes(size)
+            }
+            _ => bail!("Invalid type: {}", input),
+        };
+        Ok(kind)
+    }
+
+    pub fn is_dynamic(&self) -> bool {
+        match self {
+            ParamType::Bytes | ParamType::String | ParamType::Array(_) => true,
+            ParamType::FixedArray(inner, _) => inner.is_dynamic(),
+            ParamType::Tuple(types) => types.iter().any(|t| t.is_dynamic()),
+            _ => false,
+        }
+    }
+
+    // size of the head slot(s) taken in the enclosing tuple
+    fn head_size(&self) -> usize {
+        if self.is_dynamic() {
+            return 32;
+        }
+        match self {
+            ParamType::FixedArray(inner, n) => inner.head_size() * n,
+            ParamType::Tuple(types) => types.iter().map(|t| t.head_size()).sum(),
+            _ => 32,
+        }
+    }
+}
+
+impl fmt::Display for ParamType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParamType::Uint(bits) => write!(f, "uint{}", bits),
+            ParamType::Int(bits) => write!(f, "int{}", bits),
+            ParamType::Address => write!(f, "address"),
+            ParamType::Bool => write!(f, "bool"),
+            ParamType::FixedBytes(size) => write!(f, "bytes{}", size),
+            ParamType::Function => write!(f, "function"),
+            ParamType::Bytes => write!(f, "bytes"),
+            ParamType::String => write!(f, "string"),
+            ParamType::Array(inner) => write!(f, "{}[]", inner),
+            ParamType::FixedArray(inner, n) => write!(f, "{}[{}]", inner, n),
+            ParamType::Tuple(types) => {
+                let types: Vec<_> = types.iter().map(|t| t.to_string()).collect();
+                write!(f, "({})", types.join(","))
+            }
+        }
+    }
+}
+
+// comma separated list of types, optionally wrapped in parentheses
+pub fn parse_types(input: &str) -> Result<Vec<ParamType>> {
+    let input = input.trim();
+    let input = match input.strip_prefix('(').and_then(|s| s.strip_suffix(')')) {
+        Some(inner) if split_top_level(input).len() == 1 => inner,
+        _ => input,
+    };
+    split_top_level(input)
+        .into_iter()
+        .map(ParamType::parse)
+        .collect()
+}
+
+// `name(type,...)` into the name and parameter types
+pub fn parse_signature(signature: &str) -> Result<(String, Vec<ParamType>)> {
+    let signature = signature.trim();
+    let open = signature
+        .find('(')
+        .ok_or(anyhow!("Invalid signature: {}", signature))?;
+    if !signature.ends_with(')') {
+        bail!("Invalid signature: {}", signature);
+    }
+    let name = signature[..open].trim().to_string();
+    let types = parse_types(&signature[open + 1..signature.len() - 1])?;
+    Ok((name, types))
+}
+
+// canonical signature, as hashed for the selector
+pub fn canonical_signature(name: &str, types: &[ParamType]) -> String {
+    let types: Vec<_> = types.iter().map(|t| t.to_string()).collect();
+    format!("{}({})", name, types.join(","))
+}
+
+fn parse_uint(input: &str) -> Result<Uint256> {
+    let value = match input.strip_prefix("0x") {
+        Some(hex) => Uint256::from_str_radix(hex, 16)?,
+        None => Uint256::from_str_radix(input, 10)?,
+    };
+    Ok(value)
+}
+
+fn parse_hex_bytes(input: &str) -> Result<Vec<u8>> {
+    Ok(hex::decode(input.strip_prefix("0x").unwrap_or(input))?)
+}
+
+impl Token {
+    // parse a value written the way the CLI takes it, e.g. `[1,2]` or `(0x..,"text")`
+    pub fn parse(kind: &ParamType, input: &str) -> Result<Self> {
+        let input = input.trim();
+        let token = match kind {
+            ParamType::Uint(bits) => {
+                let value = parse_uint(input)?;
+                if value.bits() > *bits as u64 {
+                    bail!("{} does not fit in uint{}", input, bits);
+                }
+                Token::Uint(value)
+            }
+            ParamType::Int(bits) => {
+                let value = match input.strip_prefix('-') {
+                    Some(abs) => -BigInt::from(parse_uint(abs)?),
+                    None => BigInt::from(parse_uint(input)?),
+                };
+                let limit = BigInt::from(1) << (*bits - 1);
+                if value >= limit || value < -limit {
+                    bail!("{} does not fit in int{}", input, bits);
+                }
+                Token::Int(value)
+            }
+            ParamType::Address => {
+                let bytes = parse_hex_bytes(input)?;
+                let address: Address = bytes
+                    .try_into()
+                    .map_err(|_| anyhow!("Invalid address: {}", input))?;
+                Token::Address(address)
+            }
+            ParamType::Bool => match input {
+                "true" => Token::Bool(true),
+                "false" => Token::Bool(false),
+                _ => bail!("Invalid bool: {}", input),
+            },
+            ParamType::FixedBytes(size) => {
+                let mut bytes = parse_hex_bytes(input)?;
+                if bytes.len() > *size {
+                    bail!("{} does not fit in bytes{}", input, size);
+                }
+                bytes.resize(*size, 0);
+                Token::FixedBytes(bytes)
+            }
+            ParamType::Function => Token::parse(&ParamType::FixedBytes(24), input)?,
+            ParamType::Bytes => Token::Bytes(parse_hex_bytes(input)?),
+            ParamType::String => {
+                let unquoted = input
+                    .strip_prefix('"')
+                    .and_then(|s| s.strip_suffix('"'))
+                    .unwrap_or(input);
+                Token::String(unquoted.to_string())
+            }
+            ParamType::Array(inner) | ParamType::FixedArray(inner, _) => {
+                let items = input
+                    .strip_prefix('[')
+                    .and_then(|s| s.strip_suffix(']'))
+                    .ok_or(anyhow!("Invalid array: {}", input))?;
+                let tokens = split_top_level(items)
+                    .into_iter()
+                    .map(|item| Token::parse(inner, item))
+                    .collect::<Result<Vec<_>>>()?;
+                match kind {
+                    ParamType::FixedArray(_, n) if tokens.len() != *n => {
+                        bail!("Expected {} items: {}", n, input)
+                    }
+                    ParamType::FixedArray(..) => Token::FixedArray(tokens),
+                    _ => Token::Array(tokens),
+                }
+            }
+            ParamType::Tuple(types) => {
+                let items = input
+                    .strip_prefix('(')
+                    .and_then(|s| s.strip_suffix(')'))
+                    .ok_or(anyhow!("Invalid tuple: {}", input))?;
+                Token::Tuple(parse_tokens(types, &split_top_level(items))?)
+            }
+        };
+        Ok(token)
+    }
+
+    // whether the token is a value of `kind`
+    pub fn type_check(&self, kind: &ParamType) -> bool {
+        match (kind, self) {
+            (ParamType::Uint(bits), Token::Uint(value)) => value.bits() <= *bits as u64,
+            (ParamType::Int(bits), Token::Int(value)) => {
+                let limit = BigInt::from(1) << (*bits - 1);
+                *value < limit && *value >= -limit
+            }
+            (ParamType::Address, Token::Address(_))
+            | (ParamType::Bool, Token::Bool(_))
+            | (ParamType::Bytes, Token::Bytes(_))
+            | (ParamType::String, Token::String(_)) => true,
+            (ParamType::FixedBytes(size), Token::FixedBytes(bytes)) => bytes.len() == *size,
+            (ParamType::Function, Token::FixedBytes(bytes)) => bytes.len() == 24,
+            (ParamType::Array(inner), Token::Array(tokens)) => {
+                tokens.iter().all(|t| t.type_check(inner))
+            }
+            (ParamType::FixedArray(inner, n), Token::FixedArray(tokens)) => {
+                tokens.len() == *n && tokens.iter().all(|t| t.type_check(inner))
+            }
+            (ParamType::Tuple(types), Token::Tuple(tokens)) => {
+                types.len() == tokens.len()
+                    && tokens.iter().zip(types).all(|(t, k)| t.type_check(k))
+            }
+            _ => false,
+        }
+    }
+
+    fn is_dynamic(&self) -> bool {
+        match self {
+            Token::Bytes(_) | Token::String(_) | Token::Array(_) => true,
+            Token::FixedArray(tokens) | Token::Tuple(tokens) => {
+                tokens.iter().any(|t| t.is_dynamic())
+            }
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let join = |tokens: &[Token]| {
+            tokens
+                .iter()
+                .map(|t| t.to_string())
+                .collect::<Vec<_>>()
+                .join(",")
+        };
+        match self {
+            Token::Uint(v) => write!(f, "{}", v),
+            Token::Int(v) => write!(f, "{}", v),
+            Token::Address(a) => write!(f, "0x{}", hex::encode(a)),
+            Token::Bool(b) => write!(f, "{}", b),
+            Token::FixedBytes(b) | Token::Bytes(b) => write!(f, "0x{}", hex::encode(b)),
+            Token::String(s) => write!(f, "{:?}", s),
+            Token::Array(tokens) | Token::FixedArray(tokens) => write!(f, "[{}]", join(tokens)),
+            Token::Tuple(tokens) => write!(f, "({})", join(tokens)),
+        }
+    }
+}
+
+pub fn parse_tokens(types: &[ParamType], values: &[&str]) -> Result<Vec<Token>> {
+    if types.len() != values.len() {
+        bail!("Expected {} values, got {}", types.len(), values.len());
+    }
+    types
+        .iter()
+        .zip(values)
+        .map(|(kind, value)| Token::parse(kind, value))
+        .collect()
+}
+
+fn pad_right(bytes: &[u8]) -> Vec<u8> {
+    let mut padded = bytes.to_vec();
+    padded.resize(bytes.len().div_ceil(32) * 32, 0);
+    padded
+}
+
+fn encode_token(token: &Token) -> Vec<u8> {
+    match token {
+        Token::Uint(v) => to_bytes32(v).to_vec(),
+        Token::Int(v) => to_bytes32(&from_signed(v)).to_vec(),
+        Token::Address(a) => {
+            let mut word = vec![0u8; 12];
+            word.extend_from_slice(a);
+            word
+        }
+        Token::Bool(b) => to_bytes32(&(*b as u32).into()).to_vec(),
+        Token::FixedBytes(b) => pad_right(b),
+        Token::Bytes(b) => [to_bytes32(&b.len().into()).to_vec(), pad_right(b)].concat(),
+        Token::String(s) => {
+            let b = s.as_bytes();
+            [to_bytes32(&b.len().into()).to_vec(), pad_right(b)].concat()
+        }
+        Token::Array(tokens) => {
+            [to_bytes32(&tokens.len().into()).to_vec(), encode(tokens)].concat()
+        }
+        Token::FixedArray(tokens) | Token::Tuple(tokens) => encode(tokens),
+    }
+}
+
+// encode `tokens` as a tuple
+pub fn encode(tokens: &[Token]) -> Vec<u8> {
+    let head_size: usize = tokens
+        .iter()
+        .map(|t| {
+            if t.is_dynamic() {
+                32
+            } else {
+                encode_token(t).len()
+            }
+        })
+        .sum();
+
+    let mut head = vec![];
+    let mut tail = vec![];
+    for token in tokens {
+        let encoded = encode_token(token);
+        if token.is_dynamic() {
+            head.extend_from_slice(&to_bytes32(&(head_size + tail.len()).into()));
+            tail.extend(encoded);
+        } else {
+            head.extend(encoded);
+        }
+    }
+    [head, tail].concat()
+}
+
+// arguments of a call taking `types`
+pub fn check_arguments(types: &[ParamType], tokens: &[Token]) -> Result<()> {
+    if types.len() != tokens.len() {
+        bail!("Expected {} arguments, got {}", types.len(), tokens.len());
+    }
+    for (i, (kind, token)) in types.iter().zip(tokens).enumerate() {
+        if !token.type_check(kind) {
+            bail!("Argument {} is not a {}: {}", i, kind, token);
+        }
+    }
+    Ok(())
+}
+
+pub fn encode_function_call(signature: &str, tokens: &[Token]) -> Result<Vec<u8>> {
+    let (name, types) = parse_signature(signature)?;
+    check_arguments(&types, tokens)?;
+    let selector = selector(&canonical_signature(&name, &types));
+    Ok([selector.to_be_bytes().to_vec(), encode(tokens)].concat())
+}
+
+fn read_word(data: &[u8], offset: usize) -> Result<&[u8]> {
+    offset
+        .checked_add(32)
+        .and_then(|end| data.get(offset..end))
+        .ok_or(anyhow!("Data too short at offset {}", offset))
+}
+
+fn read_usize(data: &[u8], offset: usize) -> Result<usize> {
+    let word = Uint256::from_bytes_be(read_word(data, offset)?);
+    usize::try_from(word).map_err(|_| anyhow!("Offset out of range at {}", offset))
+}
+
+fn read_bytes(data: &[u8], offset: usize) -> Result<Vec<u8>> {
+    let len = read_usize(data, offset)?;
+    let start = offset + 32;
+    start
+        .checked_add(len)
+        .and_then(|end| data.get(start..end))
+        .map(|b| b.to_vec())
+        .ok_or(anyhow!(
+            "Data too short for {} bytes at offset {}",
+            len,
+            start
+        ))
+}
+
+// decode a single value whose head is at `offset` of the tuple starting at `base`
+fn decode_param(kind: &ParamType, data: &[u8], base: usize, offset: usize) -> Result<Token> {
+    if kind.is_dynamic() {
+        let location = base
+            .checked_add(read_usize(data, base + offset)?)
+            .ok_or(anyhow!("Offset out of range"))?;
+        return decode_at(kind, data, location);
+    }
+    decode_at(kind, data, base + offset)
+}
+
+// decode a value whose encoding starts at `location`
+fn decode_at(kind: &ParamType, data: &[u8], location: usize) -> Result<Token> {
+    let token = match kind {
+        ParamType::Uint(bits) => {
+            let value = Uint256::from_bytes_be(read_word(data, location)?);
+            if value.bits() > *bits as u64 {
+                bail!("Dirty uint{} at offset {}", bits, location);
+            }
+            Token::Uint(value)
+        }
+        ParamType::Int(bits) => {
+            let value = to_signed(&Uint256::from_bytes_be(read_word(data, location)?));
+            let limit = BigInt::from(1) << (*bits - 1);
+            if value >= limit || value < -limit {
+                bail!("Dirty int{} at offset {}", bits, location);
+            }
+            Token::Int(value)
+        }
+        ParamType::Address => {
+            let word = read_word(data, location)?;
+            if word[..12].iter().any(|b| *b != 0) {
+                bail!("Dirty address at offset {}", location);
+            }
+            Token::Address(word[12..].try_into().unwrap())
+        }
+        ParamType::Bool => {
+            let value = Uint256::from_bytes_be(read_word(data, location)?);
+            if value > 1u32.into() {
+                bail!("Dirty bool at offset {}", location);
+            }
+            Token::Bool(!value.is_zero())
+        }
+        ParamType::FixedBytes(size) => {
+            let word = read_word(data, location)?;
+            if word[*size..].iter().any(|b| *b != 0) {
+                bail!("Dirty bytes{} at offset {}", size, location);
+            }
+            Token::FixedBytes(word[..*size].to_vec())
+        }
+        ParamType::Function => {
+            let word = read_word(data, location)?;
+            if word[24..].iter().any(|b| *b != 0) {
+                bail!("Dirty function at offset {}", location);
+            }
+            Token::FixedBytes(word[..24].to_vec())
+        }
+        ParamType::Bytes => Token::Bytes(read_bytes(data, location)?),
+        ParamType::String => Token::String(String::from_utf8(read_bytes(data, location)?)?),
+        ParamType::Array(inner) => {
+            let len = read_usize(data, location)?;
+            // every item takes at least one word, so this also bounds the allocation
+            if len > data.len() / 32 {
+                bail!("Array length {} out of range at offset {}", len, location);
+            }
+            let types = vec![(**inner).clone(); len];
+            Token::Array(decode_tuple(&types, data, location + 32)?)
+        }
+        ParamType::FixedArray(inner, n) => {
+            let types = vec![(**inner).clone(); *n];
+            Token::FixedArray(decode_tuple(&types, data, location)?)
+        }
+        ParamType::Tuple(types) => Token::Tuple(decode_tuple(types, data, location)?),
+    };
+    Ok(token)
+}
+
+fn decode_tuple(types: &[ParamType], data: &[u8], base: usize) -> Result<Vec<Token>> {
+    let mut offset = 0;
+    let mut tokens = vec![];
+    for kind in types {
+        tokens.push(decode_param(kind, data, base, offset)?);
+        offset += kind.head_size();
+    }
+    Ok(tokens)
+}
+
+// decode return data or function arguments encoded as a tuple of `types`
+pub fn decode(types: &[ParamType], data: &[u8]) -> Result<Vec<Token>> {
+    decode_tuple(types, data, 0)
+}
+
+// decode calldata for `signature`, checking the selector
+pub fn decode_function_call(signature: &str, calldata: &[u8]) -> Result<Vec<Token>> {
+    let (name, types) = parse_signature(signature)?;
+    let expected = selector(&canonical_signature(&name, &types));
+    let actual = calldata
+        .get(..4)
+        .map(|s| u32::from_be_bytes(s.try_into().unwrap()))
+        .ok_or(anyhow!("Calldata too short"))?;
+    if actual != expected {
+        bail!("Selector mismatch: 0x{:08x} != 0x{:08x}", actual, expected);
+    }
+    decode(&types, &calldata[4..])
+}
+
+pub fn decode_revert(data: &[u8]) -> RevertReason {
+    let selector = data
+        .get(..4)
+        .map(|s| u32::from_be_bytes(s.try_into().unwrap()));
+    let reason = match selector {
+        Some(ERROR_SELECTOR) => decode(&[ParamType::String], &data[4..])
+            .ok()
+            .and_then(|mut t| match t.pop() {
+                Some(Token::String(s)) => Some(RevertReason::Error(s)),
+                _ => None,
+            }),
+        Some(PANIC_SELECTOR) => {
+            decode(&[ParamType::Uint(256)], &data[4..])
+                .ok()
+                .and_then(|mut t| match t.pop() {
+                    Some(Token::Uint(code)) => Some(RevertReason::Panic(code)),
+                    _ => None,
+                })
+        }
+        _ => None,
+    };
+    reason.unwrap_or_else(|| RevertReason::Unknown(data.to_vec()))
+}
+
+impl fmt::Display for RevertReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RevertReason::Error(reason) => write!(f, "Error({:?})", reason),
+            RevertReason::Panic(code) => write!(f, "Panic(0x{:x})", code),
+            RevertReason::Unknown(data) => write!(f, "0x{}", hex::encode(data)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        canonical_signature, decode, decode_revert, encode, encode_function_call, parse_signature,
+        parse_tokens, parse_types, ParamType, RevertReason, Token,
+    };
+
+    #[test]
+    fn test_encode_function_call() {
+        let (_, types) = parse_signature("add(uint256,uint256)").unwrap();
+        let (_, callback) = parse_signature("call(function,bytes24)").unwrap();
+        assert_eq!(
+            canonical_signature("call", &callback),
+            "call(function,bytes24)"
+        );
+        let tokens = parse_tokens(&types, &["100", "0x64"]).unwrap();
+        let calldata = encode_function_call("add(uint256,uint256)", &tokens).unwrap();
+        assert_eq!(
+            hex::encode(calldata),
+            concat!(
+                "771602f7",
+                "0000000000000000000000000000000000000000000000000000000000000064",
+                "0000000000000000000000000000000000000000000000000000000000000064"
+            )
+        );
+    }
+
+    #[test]
+    fn test_dynamic_round_trip() {
+        let types = parse_types("(uint256[],string,(bool,bytes)[2],int8)").unwrap();
+        assert_eq!(types[2].to_string(), "(bool,bytes)[2]");
+        let tokens = parse_tokens(
+            &types,
+            &[
+                "[1,2,3]",
+                "\"hello, world\"",
+                "[(true,0x1234),(false,0x)]",
+                "-5",
+            ],
+        )
+        .unwrap();
+        let encoded = encode(&tokens);
+        assert_eq!(decode(&types, &encoded).unwrap(), tokens);
+        // head: 3 offsets and the int8, tails follow
+        assert_eq!(encoded[31], 0x80);
+        assert_eq!(encoded[127], 0xfb);
+    }
+
+    #[test]
+    fn test_decode_errors() {
+        assert!(decode(&[ParamType::Bytes], &[0u8; 16]).is_err());
+        let dirty = [0xffu8; 32];
+        assert!(decode(&[ParamType::Address], &dirty).is_err());
+        assert!(decode(&[ParamType::FixedBytes(4)], &dirty).is_err());
+        assert!(decode(&[ParamType::Function], &dirty).is_err());
+        assert_eq!(
+            decode(&[ParamType::FixedBytes(32)], &dirty).unwrap(),
+            vec![Token::FixedBytes(dirty.to_vec())]
+        );
+        assert_eq!(
+            decode(&[ParamType::Int(256)], &dirty).unwrap(),
+            vec![Token::Int((-1).into())]
+        );
+    }
+
+    #[test]
+    fn test_decode_revert() {
+        let data = hex::decode(concat!(
+            "08c379a0",
+            "0000000000000000000000000000000000000000000000000000000000000020",
+            "0000000000000000000000000000000000000000000000000000000000000004",
+            "6e6f706500000000000000000000000000000000000000000000000000000000"
+        ))
+        .unwrap();
+        assert_eq!(decode_revert(&data), RevertReason::Error("nope".into()));
+        let data = hex::decode(concat!(
+            "4e487b71",
+            "0000000000000000000000000000000000000000000000000000000000000011"
+        ))
+        .unwrap();
+        assert_eq!(decode_revert(&data).to_string(), "Panic(0x11)");
+        assert_eq!(decode_revert(&[]), RevertReason::Unknown(vec![]));
+    }
+}
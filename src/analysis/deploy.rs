@@ -0,0 +1,118 @@
+use std::ops::Range;
+
+use crate::{analysis::stack::ConstStack, block::Block, opcode::OpCode};
+
+// layout of init code which deploys an embedded runtime with CODECOPY + RETURN
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Deployment {
+    pub init_code: Range<usize>,
+    pub runtime: Range<usize>,
+    // anything appended after the runtime, usually ABI encoded constructor arguments
+    pub constructor_args: Range<usize>,
+    pub codecopy_position: usize,
+    pub return_position: usize,
+}
+
+impl Deployment {
+    pub fn init_code<'a>(&self, code: &'a [u8]) -> &'a [u8] {
+        &code[self.init_code.clone()]
+    }
+
+    pub fn runtime_code<'a>(&self, code: &'a [u8]) -> &'a [u8] {
+        &code[self.runtime.clone()]
+    }
+
+    pub fn constructor_args<'a>(&self, code: &'a [u8]) -> &'a [u8] {
+        &code[self.constructor_args.clone()]
+    }
+}
+
+// find the deploy prologue: CODECOPY(dest, offset, size) followed by RETURN(dest, size)
+pub fn find_deployment(blocks: &[Block], code_len: usize) -> Option<Deployment> {
+    let mut stack = ConstStack::new();
+    // (dest, offset, size, position) of the last constant CODECOPY
+    let mut codecopy = None;
+
+    for block in blocks {
+        match &block.opcode {
+            // values on entry to a jump target are unknown
+            OpCode::JUMPDEST => {
+                stack.clear();
+                codecopy = None;
+            }
+            OpCode::CODECOPY => {
+                let args = (stack.peek(0), stack.peek(1), stack.peek(2));
+                codecopy = match args {
+                    (Some(dest), Some(offset), Some(size)) => {
+                        match (usize::try_from(offset), usize::try_from(size)) {
+                            (Ok(offset), Ok(size)) => {
+                                Some((dest.clone(), offset, size, block.position))
+                            }
+                            _ => None,
+                        }
+                    }
+                    _ => None,
+                };
+            }
+            OpCode::RETURN => {
+                if let (Some((dest, offset, size, codecopy_position)), Some(ret), Some(ret_size)) =
+                    (&codecopy, stack.peek(0), stack.peek(1))
+                {
+                    let end = offset.saturating_add(*size);
+                    let valid = *dest == *ret
+                        && *ret_size == (*size).into()
+                        && *offset > block.position
+                        && end <= code_len;
+                    if valid {
+                        return Some(Deployment {
+                            init_code: 0..*offset,
+                            runtime: *offset..end,
+                            constructor_args: end..code_len,
+                            codecopy_position: *codecopy_position,
+                            return_position: block.position,
+                        });
+                    }
+                }
+            }
+            _ => {}
+        }
+        stack.apply(&block.opcode);
+        if block.opcode.is_terminator() {
+            stack.clear();
+            codecopy = None;
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::find_deployment;
+    use crate::parser::{decode_hex, parse_bytes};
+
+    #[test]
+    fn test_find_deployment() {
+        // huff: PUSH1 0x0f DUP1 PUSH1 0x09 RETURNDATASIZE CODECOPY RETURNDATASIZE RETURN
+        let code = decode_hex("600f8060093d393df36000356020350160005260206000f3").unwrap();
+        let deployment = find_deployment(&parse_bytes(&code), code.len()).unwrap();
+        assert_eq!(deployment.init_code, 0..9);
+        assert_eq!(deployment.runtime, 9..24);
+        assert!(deployment.constructor_args.is_empty());
+
+        // solc: PUSH1 0x0f DUP1 PUSH1 0x0a PUSH0 CODECOPY PUSH0 RETURN INVALID, with an argument tail
+        let code = decode_hex(&format!(
+            "600f80600a5f395ff3fe{}{}",
+            "6000356020350160005260206000f3",
+            "00000000000000000000000000000000000000000000000000000000000000ff"
+        ))
+        .unwrap();
+        let deployment = find_deployment(&parse_bytes(&code), code.len()).unwrap();
+        assert_eq!(deployment.runtime, 10..25);
+        assert_eq!(deployment.constructor_args(&code)[31], 0xff);
+
+        // a runtime alone has no prologue
+        let code = decode_hex("6000356020350160005260206000f3").unwrap();
+        assert_eq!(find_deployment(&parse_bytes(&code), code.len()), None);
+    }
+}
@@ -0,0 +1,351 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::{block::Block, opcode::OpCode, signatures::SignatureDb, Uint256};
+
+// how the selector is taken from the first calldata word
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SelectorExtraction {
+    // CALLDATALOAD(0) >> 0xe0
+    Shr,
+    // CALLDATALOAD(0) / 2**224 & 0xffffffff, before Constantinople
+    Div,
+    Unknown,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DispatcherLayout {
+    Linear,
+    // solc splits large dispatchers on GT/LT against a pivot selector
+    BinarySearch,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub selector: u32,
+    // JUMPDEST of the function body
+    pub destination: usize,
+    // EQ comparing against the selector
+    pub check_position: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dispatcher {
+    pub extraction: SelectorExtraction,
+    pub layout: DispatcherLayout,
+    pub entries: Vec<Entry>,
+}
+
+impl Dispatcher {
+    pub fn entry(&self, selector: u32) -> Option<&Entry> {
+        self.entries.iter().find(|e| e.selector == selector)
+    }
+
+    // labels for the entry points, keyed by position
+    pub fn labels(&self) -> BTreeMap<usize, String> {
+        self.entries
+            .iter()
+            .map(|e| (e.destination, format!("selector_0x{:08x}", e.selector)))
+            .collect()
+    }
+
+    // like `labels`, using the text signature when `db` knows the selector
+    pub fn named_labels(&self, db: &SignatureDb) -> BTreeMap<usize, String> {
+        let mut labels = self.labels();
+        for e in &self.entries {
+            if let Some(name) = db.function(e.selector) {
+                labels.insert(e.destination, name.to_string());
+            }
+        }
+        labels
+    }
+}
+
+// index of the JUMPDEST at `position`
+fn jumpdest_index(blocks: &[Block], position: &Value) -> Option<usize> {
+    let Value::Const(position) = position else {
+        return None;
+    };
+    let position = usize::try_from(position).ok()?;
+    blocks
+        .binary_search_by_key(&position, |b| b.position)
+        .ok()
+        .filter(|&i| blocks[i].opcode == OpCode::JUMPDEST)
+}
+
+// what the dispatcher walk knows about a stack slot
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Value {
+    Other,
+    Const(Uint256),
+    // CALLDATALOAD(0)
+    Word,
+    Selector,
+    // EQ, GT or LT of the selector against a constant
+    Compare {
+        op: OpCode,
+        selector: u32,
+        position: usize,
+    },
+}
+
+fn pop(stack: &mut Vec<Value>) -> Value {
+    stack.pop().unwrap_or(Value::Other)
+}
+
+// the selector compared against a constant
+fn compare(op: &OpCode, a: Value, b: Value, position: usize) -> Value {
+    match (a, b) {
+        (Value::Selector, Value::Const(v)) | (Value::Const(v), Value::Selector) => {
+            match u32::try_from(&v) {
+                Ok(selector) => Value::Compare {
+                    op: op.clone(),
+                    selector,
+                    position,
+                },
+                Err(_) => Value::Other,
+            }
+        }
+        _ => Value::Other,
+    }
+}
+
+// abstract effect of a non-jump instruction
+fn apply(stack: &mut Vec<Value>, block: &Block, extraction: &mut Option<SelectorExtraction>) {
+    let op = &block.opcode;
+    if let Some(v) = op.push_value() {
+        stack.push(Value::Const(v));
+        return;
+    }
+    let value = match op {
+        OpCode::DUPN(n) => {
+            let n = *n as usize;
+            match stack.len().checked_sub(n) {
+                Some(i) => stack[i].clone(),
+                None => Value::Other,
+            }
+        }
+        OpCode::SWAPN(n) => {
+            let n = *n as usize;
+            while stack.len() <= n {
+                stack.insert(0, Value::Other);
+            }
+            let top = stack.len() - 1;
+            stack.swap(top, top - n);
+            return;
+        }
+        OpCode::CALLDATALOAD => match pop(stack) {
+            Value::Const(v) if v == Uint256::default() => Value::Word,
+            _ => Value::Other,
+        },
+        OpCode::SHR => match (pop(stack), pop(stack)) {
+            (Value::Const(shift), Value::Word) if shift == Uint256::from(0xe0u32) => {
+                *extraction = Some(SelectorExtraction::Shr);
+                Value::Selector
+            }
+            _ => Value::Other,
+        },
+        OpCode::DIV => match (pop(stack), pop(stack)) {
+            (Value::Word, Value::Const(d)) if d == Uint256::from(1u32) << 224 => {
+                *extraction = Some(SelectorExtraction::Div);
+                Value::Selector
+            }
+            _ => Value::Other,
+        },
+        // the 0xffffffff mask after DIV keeps the selector
+        OpCode::AND => match (pop(stack), pop(stack)) {
+            (Value::Selector, Value::Const(m)) | (Value::Const(m), Value::Selector)
+                if m == Uint256::from(u32::MAX) =>
+            {
+                Value::Selector
+            }
+            _ => Value::Other,
+        },
+        OpCode::EQ | OpCode::GT | OpCode::LT => {
+            let (a, b) = (pop(stack), pop(stack));
+            compare(op, a, b, block.position)
+        }
+        _ => {
+            let (inputs, outputs) = op.stack_io();
+            for _ in 0..inputs {
+                pop(stack);
+            }
+            stack.resize(stack.len() + outputs, Value::Other);
+            return;
+        }
+    };
+    stack.push(value);
+}
+
+// follow the selector from the block at `start` through the comparisons on it
+fn walk(blocks: &[Block], start: usize) -> Option<Dispatcher> {
+    let mut entries: Vec<Entry> = vec![];
+    let mut layout = DispatcherLayout::Linear;
+    let mut extraction = None;
+    let mut visited = BTreeSet::new();
+    let mut work = vec![(start, vec![])];
+
+    while let Some((mut i, mut stack)) = work.pop() {
+        while let Some(block) = blocks.get(i) {
+            if !visited.insert(i) {
+                break;
+            }
+            match &block.opcode {
+                OpCode::JUMPI => {
+                    let destination = jumpdest_index(blocks, &pop(&mut stack));
+                    match (pop(&mut stack), destination) {
+                        (
+                            Value::Compare {
+                                op: OpCode::EQ,
+                                selector,
+                                position,
+                            },
+                            Some(d),
+                        ) if entries.iter().all(|e| e.selector != selector) => {
+                            // the function body, not followed
+                            entries.push(Entry {
+                                selector,
+                                destination: blocks[d].position,
+                                check_position: position,
+                            });
+                        }
+                        (
+                            Value::Compare {
+                                op: OpCode::GT | OpCode::LT,
+                                ..
+                            },
+                            Some(d),
+                        ) => {
+                            layout = DispatcherLayout::BinarySearch;
+                            work.push((d, stack.clone()));
+                        }
+                        _ => {}
+                    }
+                }
+                // dispatcher blocks may be chained by jumps that keep the selector
+                OpCode::JUMP => match jumpdest_index(blocks, &pop(&mut stack)) {
+                    Some(d) if stack.contains(&Value::Selector) => {
+                        i = d;
+                        continue;
+                    }
+                    _ => break,
+                },
+                op if op.is_terminator() => break,
+                _ => apply(&mut stack, block, &mut extraction),
+            }
+            i += 1;
+        }
+    }
+
+    if entries.is_empty() {
+        return None;
+    }
+    Some(Dispatcher {
+        extraction: extraction.unwrap_or(SelectorExtraction::Unknown),
+        layout,
+        entries,
+    })
+}
+
+// first instruction of the basic block containing `i`
+fn block_start(blocks: &[Block], mut i: usize) -> usize {
+    while i > 0 && blocks[i].opcode != OpCode::JUMPDEST {
+        let previous = &blocks[i - 1].opcode;
+        if *previous == OpCode::JUMPI || previous.is_terminator() {
+            break;
+        }
+        i -= 1;
+    }
+    i
+}
+
+// recover the selector to entry point table of a solc or Huff style dispatcher
+pub fn find_dispatcher(blocks: &[Block]) -> Option<Dispatcher> {
+    // start from a block loading the first calldata word
+    (1..blocks.len())
+        .filter(|&i| {
+            blocks[i].opcode == OpCode::CALLDATALOAD
+                && blocks[i - 1].opcode.push_value() == Some(Uint256::default())
+        })
+        .find_map(|i| walk(blocks, block_start(blocks, i)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{find_dispatcher, DispatcherLayout, Entry, SelectorExtraction};
+    use crate::parser::parse;
+
+    #[test]
+    fn test_linear_dispatcher() {
+        // PUSH0 CALLDATALOAD PUSH1 0xe0 SHR
+        // DUP1 PUSH4 0x0f52d66e EQ PUSH1 0x1c JUMPI
+        // DUP1 PUSH4 0xaabbccdd EQ PUSH1 0x1e JUMPI
+        // PUSH0 DUP1 REVERT JUMPDEST STOP JUMPDEST STOP
+        let parsed = parse(concat!(
+            "5f3560e01c",
+            "80630f52d66e14601c57",
+            "8063aabbccdd14601e57",
+            "5f80fd5b005b00"
+        ))
+        .unwrap();
+        let dispatcher = find_dispatcher(&parsed).unwrap();
+        assert_eq!(dispatcher.extraction, SelectorExtraction::Shr);
+        assert_eq!(dispatcher.layout, DispatcherLayout::Linear);
+        assert_eq!(
+            dispatcher.entries,
+            vec![
+                Entry {
+                    selector: 0x0f52d66e,
+                    destination: 0x1c,
+                    check_position: 11
+                },
+                Entry {
+                    selector: 0xaabbccdd,
+                    destination: 0x1e,
+                    check_position: 21
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn test_binary_search_dispatcher() {
+        // PUSH0 CALLDATALOAD PUSH1 0xe0 SHR
+        // DUP1 PUSH4 0x50000000 GT PUSH1 0x19 JUMPI
+        // DUP1 PUSH4 0x0f52d66e EQ PUSH1 0x25 JUMPI
+        // JUMPDEST DUP1 PUSH4 0xaabbccdd EQ PUSH1 0x27 JUMPI STOP
+        // JUMPDEST STOP JUMPDEST STOP
+        let parsed = parse(concat!(
+            "5f3560e01c",
+            "80635000000011601957",
+            "80630f52d66e14602557",
+            "5b8063aabbccdd1460275700",
+            "5b005b00"
+        ))
+        .unwrap();
+        let dispatcher = find_dispatcher(&parsed).unwrap();
+        assert_eq!(dispatcher.layout, DispatcherLayout::BinarySearch);
+        let selectors: Vec<_> = dispatcher.entries.iter().map(|e| e.selector).collect();
+        assert_eq!(selectors, vec![0x0f52d66e, 0xaabbccdd]);
+    }
+
+    #[test]
+    fn test_body_comparisons() {
+        // PUSH0 CALLDATALOAD PUSH1 0xe0 SHR
+        // DUP1 PUSH4 0xaabbccdd EQ PUSH1 0x12 JUMPI PUSH0 DUP1 REVERT
+        // JUMPDEST PUSH1 4 CALLDATALOAD DUP1 PUSH4 0x12345678 EQ PUSH1 0x28 JUMPI
+        // DUP1 PUSH1 0x10 LT PUSH1 0x2a JUMPI STOP JUMPDEST STOP JUMPDEST STOP
+        let parsed = parse(concat!(
+            "5f3560e01c",
+            "8063aabbccdd14601257",
+            "5f80fd",
+            "5b60043580631234567814602857",
+            "80601010602a5700",
+            "5b005b00"
+        ))
+        .unwrap();
+        let dispatcher = find_dispatcher(&parsed).unwrap();
+        assert_eq!(dispatcher.layout, DispatcherLayout::Linear);
+        let selectors: Vec<_> = dispatcher.entries.iter().map(|e| e.selector).collect();
+        assert_eq!(selectors, vec![0xaabbccdd]);
+    }
+}
@@ -0,0 +1,481 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    ops::Range,
+};
+
+use crate::{block::Block, emulator::arithmetic, formatter::mnemonic, opcode::OpCode, Uint256};
+
+// contexts explored before giving up on the rest of the graph
+const MAX_NODES: usize = 10_000;
+
+const STACK_LIMIT: usize = 1024;
+
+// value read by a statement of the three-address code
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Operand {
+    Const(Uint256),
+    // stack item at the block's entry, counted from the top
+    Param(usize),
+    // result of an earlier statement of the same block
+    Temp(usize),
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Operand::Const(v) => write!(f, "0x{:x}", v),
+            Operand::Param(i) => write!(f, "s{}", i),
+            Operand::Temp(i) => write!(f, "t{}", i),
+        }
+    }
+}
+
+// an instruction other than stack shuffling and jumps, with its operands top of the stack first
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Statement {
+    pub position: usize,
+    pub opcode: OpCode,
+    pub args: Vec<Operand>,
+    // temporary assigned once, which makes a block SSA with its params as phis
+    pub result: Option<usize>,
+}
+
+impl fmt::Display for Statement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(result) = self.result {
+            write!(f, "t{} = ", result)?;
+        }
+        let args: Vec<_> = self.args.iter().map(|a| a.to_string()).collect();
+        write!(
+            f,
+            "{}({})",
+            mnemonic(&self.opcode).to_lowercase(),
+            args.join(", ")
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Exit {
+    Jump(Operand),
+    // JUMPI, falling through to the next block when `condition` is zero
+    Branch { target: Operand, condition: Operand },
+    // into the JUMPDEST starting the next block, or off the end of the code
+    Fallthrough,
+    // the last statement stops execution
+    Halt,
+}
+
+// straight-line code between jump destinations and jumps
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BasicBlock {
+    pub position: usize,
+    // indexes of the instructions
+    pub range: Range<usize>,
+    // entry items popped, and read at most
+    pub inputs: usize,
+    pub depth: usize,
+    pub statements: Vec<Statement>,
+    // pushed in place of the popped inputs, bottom first
+    pub outputs: Vec<Operand>,
+    pub exit: Exit,
+}
+
+impl fmt::Display for BasicBlock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "loc_{:x}:", self.position)?;
+        for s in &self.statements {
+            writeln!(f, "    {}", s)?;
+        }
+        let outputs: Vec<_> = self.outputs.iter().map(|o| o.to_string()).collect();
+        writeln!(
+            f,
+            "    pops {}, pushes [{}]",
+            self.inputs,
+            outputs.join(", ")
+        )?;
+        match &self.exit {
+            Exit::Jump(target) => writeln!(f, "    jump({})", target),
+            Exit::Branch { target, condition } => {
+                writeln!(f, "    jumpi({}, {})", target, condition)
+            }
+            Exit::Fallthrough | Exit::Halt => Ok(()),
+        }
+    }
+}
+
+// stack of a block being lifted, entry items become params as they are reached
+#[derive(Default)]
+struct Lifter {
+    stack: Vec<Operand>,
+    inputs: usize,
+    depth: usize,
+    temps: usize,
+    statements: Vec<Statement>,
+}
+
+impl Lifter {
+    fn pop(&mut self) -> Operand {
+        self.stack.pop().unwrap_or_else(|| {
+            self.inputs += 1;
+            self.depth = self.depth.max(self.inputs);
+            Operand::Param(self.inputs - 1)
+        })
+    }
+
+    fn peek(&mut self, n: usize) -> Operand {
+        match self.stack.len().checked_sub(n + 1) {
+            Some(i) => self.stack[i].clone(),
+            None => {
+                let param = self.inputs + n - self.stack.len();
+                self.depth = self.depth.max(param + 1);
+                Operand::Param(param)
+            }
+        }
+    }
+
+    fn swap(&mut self, n: usize) {
+        while self.stack.len() < n + 1 {
+            self.stack.insert(0, Operand::Param(self.inputs));
+            self.inputs += 1;
+        }
+        self.depth = self.depth.max(self.inputs);
+        let len = self.stack.len();
+        self.stack.swap(len - 1, len - 1 - n);
+    }
+
+    // `Some` once the block ends
+    fn apply(&mut self, b: &Block) -> Option<Exit> {
+        match &b.opcode {
+            OpCode::JUMPDEST => {}
+            OpCode::POP => {
+                self.pop();
+            }
+            OpCode::DUPN(n) => {
+                let item = self.peek(*n as usize - 1);
+                self.stack.push(item);
+            }
+            OpCode::SWAPN(n) => self.swap(*n as usize),
+            OpCode::PC => self.stack.push(Operand::Const(b.position.into())),
+            OpCode::JUMP => return Some(Exit::Jump(self.pop())),
+            OpCode::JUMPI => {
+                let target = self.pop();
+                let condition = self.pop();
+                return Some(Exit::Branch { target, condition });
+            }
+            op => {
+                if let Some(value) = op.push_value() {
+                    self.stack.push(Operand::Const(value));
+                    return None;
+                }
+                let (inputs, outputs) = op.stack_io();
+                let args = (0..inputs).map(|_| self.pop()).collect();
+                let result = (outputs == 1).then(|| {
+                    self.temps += 1;
+                    self.temps - 1
+                });
+                self.statements.push(Statement {
+                    position: b.position,
+                    opcode: op.clone(),
+                    args,
+                    result,
+                });
+                self.stack.extend(result.map(Operand::Temp));
+                if op.is_terminator() {
+                    return Some(Exit::Halt);
+                }
+            }
+        }
+        None
+    }
+}
+
+fn lift_block(blocks: &[Block], range: Range<usize>) -> BasicBlock {
+    let mut lifter = Lifter::default();
+    let mut exit = Exit::Fallthrough;
+    for b in &blocks[range.clone()] {
+        if let Some(e) = lifter.apply(b) {
+            exit = e;
+        }
+    }
+    BasicBlock {
+        position: blocks[range.start].position,
+        range,
+        inputs: lifter.inputs,
+        depth: lifter.depth,
+        statements: lifter.statements,
+        outputs: lifter.stack,
+        exit,
+    }
+}
+
+// three-address code of each basic block
+pub fn lift(blocks: &[Block]) -> Vec<BasicBlock> {
+    let mut result = vec![];
+    let mut start = 0;
+    for (i, b) in blocks.iter().enumerate() {
+        let ends = b.opcode.is_terminator()
+            || b.opcode == OpCode::JUMPI
+            || blocks
+                .get(i + 1)
+                .is_some_and(|b| b.opcode == OpCode::JUMPDEST);
+        if ends {
+            result.push(lift_block(blocks, start..i + 1));
+            start = i + 1;
+        }
+    }
+    if start < blocks.len() {
+        result.push(lift_block(blocks, start..blocks.len()));
+    }
+    result
+}
+
+// a basic block entered with a given stack
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Node {
+    pub block: usize,
+    // bottom first, only constants which are jump destinations are kept, like return
+    // addresses of internal functions
+    pub context: Vec<Option<Uint256>>,
+    // node reached by the JUMP or taken JUMPI
+    pub jump: Option<usize>,
+    // node reached by falling through
+    pub next: Option<usize>,
+}
+
+// control flow graph resolving jumps by the constants on the stack, so code shared by
+// several callers gets a node per caller
+#[derive(Debug, Clone)]
+pub struct Cfg {
+    pub blocks: Vec<BasicBlock>,
+    pub nodes: Vec<Node>,
+}
+
+impl Cfg {
+    pub fn new(code: &[Block]) -> Self {
+        let blocks = lift(code);
+        let destinations: HashMap<_, _> = blocks
+            .iter()
+            .enumerate()
+            .filter(|(_, b)| code[b.range.start].opcode == OpCode::JUMPDEST)
+            .map(|(i, b)| (b.position, i))
+            .collect();
+        let mut cfg = Cfg {
+            blocks,
+            nodes: vec![],
+        };
+        if cfg.blocks.is_empty() {
+            return cfg;
+        }
+        let mut index = HashMap::new();
+        let mut node = |cfg: &mut Cfg, block: usize, context: Vec<Option<Uint256>>| {
+            let key = (block, context);
+            if let Some(i) = index.get(&key) {
+                return Some(*i);
+            }
+            if cfg.nodes.len() >= MAX_NODES {
+                return None;
+            }
+            let (block, context) = key.clone();
+            cfg.nodes.push(Node {
+                block,
+                context,
+                jump: None,
+                next: None,
+            });
+            index.insert(key, cfg.nodes.len() - 1);
+            Some(cfg.nodes.len() - 1)
+        };
+        node(&mut cfg, 0, vec![]);
+        let mut i = 0;
+        while i < cfg.nodes.len() {
+            let Node { block, context, .. } = cfg.nodes[i].clone();
+            if let Some((stack, target, taken)) = evaluate(&cfg.blocks[block], &context) {
+                let stack: Vec<_> = stack
+                    .into_iter()
+                    .map(|v| {
+                        v.filter(|v| position(v).is_some_and(|p| destinations.contains_key(&p)))
+                    })
+                    .collect();
+                let jump = target
+                    .and_then(|t| position(&t))
+                    .and_then(|t| destinations.get(&t));
+                if let (Some(jump), true) = (jump, taken != Some(false)) {
+                    cfg.nodes[i].jump = node(&mut cfg, *jump, stack.clone());
+                }
+                let falls = match cfg.blocks[block].exit {
+                    Exit::Branch { .. } => taken != Some(true),
+                    Exit::Fallthrough => true,
+                    _ => false,
+                };
+                if falls && block + 1 < cfg.blocks.len() {
+                    cfg.nodes[i].next = node(&mut cfg, block + 1, stack);
+                }
+            }
+            i += 1;
+        }
+        cfg
+    }
+
+    pub fn successors(&self, node: usize) -> impl Iterator<Item = usize> {
+        let n = &self.nodes[node];
+        n.jump.into_iter().chain(n.next)
+    }
+
+    // nodes reachable from `root`
+    pub fn reachable(&self, root: usize) -> HashSet<usize> {
+        let mut seen = HashSet::new();
+        let mut pending = vec![root];
+        while let Some(n) = pending.pop() {
+            if seen.insert(n) {
+                pending.extend(self.successors(n));
+            }
+        }
+        seen
+    }
+}
+
+fn position(value: &Uint256) -> Option<usize> {
+    usize::try_from(value).ok()
+}
+
+// stack after `block` entered with `context`, the jump target and whether a JUMPI is taken,
+// `None` when the stack under- or overflows
+#[allow(clippy::type_complexity)]
+fn evaluate(
+    block: &BasicBlock,
+    context: &[Option<Uint256>],
+) -> Option<(Vec<Option<Uint256>>, Option<Uint256>, Option<bool>)> {
+    if context.len() < block.depth {
+        return None;
+    }
+    let mut temps = vec![None; block.statements.len()];
+    let value = |temps: &[Option<Uint256>], operand: &Operand| match operand {
+        Operand::Const(v) => Some(v.clone()),
+        Operand::Param(i) => context[context.len() - 1 - i].clone(),
+        Operand::Temp(t) => temps[*t].clone(),
+    };
+    for s in &block.statements {
+        let args: Option<Vec<_>> = s.args.iter().map(|a| value(&temps, a)).collect();
+        if let Some(result) = s.result {
+            temps[result] = args.and_then(|args| arithmetic(&s.opcode, &args));
+        }
+    }
+    let mut stack = context[..context.len() - block.inputs].to_vec();
+    stack.extend(block.outputs.iter().map(|o| value(&temps, o)));
+    if stack.len() > STACK_LIMIT {
+        return None;
+    }
+    let (target, taken) = match &block.exit {
+        Exit::Jump(target) => (value(&temps, target), Some(true)),
+        Exit::Branch { target, condition } => (
+            value(&temps, target),
+            value(&temps, condition).map(|c| c != Uint256::default()),
+        ),
+        _ => (None, None),
+    };
+    Some((stack, target, taken))
+}
+
+// immediate dominator of each node reachable from `root`, the root dominating itself
+pub fn dominators(successors: &[Vec<usize>], root: usize) -> Vec<Option<usize>> {
+    // reverse postorder, from an iterative depth first search
+    let mut order = vec![];
+    let mut visited = vec![false; successors.len()];
+    let mut pending = vec![(root, 0)];
+    visited[root] = true;
+    while let Some((node, i)) = pending.pop() {
+        match successors[node].get(i) {
+            Some(&next) => {
+                pending.push((node, i + 1));
+                if !visited[next] {
+                    visited[next] = true;
+                    pending.push((next, 0));
+                }
+            }
+            None => order.push(node),
+        }
+    }
+    order.reverse();
+    let mut rank = vec![usize::MAX; successors.len()];
+    let mut predecessors = vec![vec![]; successors.len()];
+    for (r, &node) in order.iter().enumerate() {
+        rank[node] = r;
+        for &next in &successors[node] {
+            predecessors[next].push(node);
+        }
+    }
+
+    let mut idom = vec![None; successors.len()];
+    idom[root] = Some(root);
+    let intersect = |idom: &[Option<usize>], mut a: usize, mut b: usize| {
+        while a != b {
+            while rank[a] > rank[b] {
+                a = idom[a].unwrap();
+            }
+            while rank[b] > rank[a] {
+                b = idom[b].unwrap();
+            }
+        }
+        a
+    };
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for &node in order.iter().skip(1) {
+            let mut processed = predecessors[node].iter().filter(|p| idom[**p].is_some());
+            let Some(&first) = processed.next() else {
+                continue;
+            };
+            let new = processed.fold(first, |a, &p| intersect(&idom, a, p));
+            if idom[node] != Some(new) {
+                idom[node] = Some(new);
+                changed = true;
+            }
+        }
+    }
+    idom
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{dominators, lift, Cfg};
+    use crate::parser::parse;
+
+    #[test]
+    fn test_lift() {
+        // PUSH1 4 CALLDATALOAD DUP2 SSTORE JUMP, JUMPDEST STOP
+        let blocks = parse("6004358155565b00").unwrap();
+        let lifted = lift(&blocks);
+        assert_eq!(lifted.len(), 2);
+        assert_eq!(
+            lifted[0].to_string(),
+            "loc_0:\n    t0 = calldataload(0x4)\n    sstore(s0, t0)\n    pops 1, pushes []\n    jump(s0)\n"
+        );
+        assert_eq!((lifted[0].inputs, lifted[0].depth), (1, 1));
+    }
+
+    #[test]
+    fn test_cfg() {
+        // an internal function at 0xd called from 0x0 and 0x5, returning to 0x5 and 0xb
+        // PUSH1 5 PUSH1 0xd JUMP, JUMPDEST PUSH1 0xb PUSH1 0xd JUMP, JUMPDEST STOP, JUMPDEST JUMP
+        let blocks = parse("6005600d565b600b600d565b005b56").unwrap();
+        let cfg = Cfg::new(&blocks);
+        let positions: Vec<_> = cfg
+            .nodes
+            .iter()
+            .map(|n| cfg.blocks[n.block].position)
+            .collect();
+        assert_eq!(positions, [0x0, 0xd, 0x5, 0xd, 0xb]);
+        assert_eq!(cfg.nodes[3].jump, Some(4));
+
+        let successors: Vec<Vec<_>> = (0..cfg.nodes.len())
+            .map(|n| cfg.successors(n).collect())
+            .collect();
+        assert_eq!(
+            dominators(&successors, 0),
+            [Some(0), Some(0), Some(1), Some(2), Some(3)]
+        );
+    }
+}
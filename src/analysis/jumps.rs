@@ -0,0 +1,77 @@
+use std::collections::BTreeMap;
+
+use crate::{analysis::stack::ConstStack, block::Block, opcode::OpCode};
+
+// index of the instruction at `position`
+pub fn block_index(blocks: &[Block], position: usize) -> Option<usize> {
+    blocks.binary_search_by_key(&position, |b| b.position).ok()
+}
+
+// destination of each JUMP and JUMPI pushed within its basic block, keyed by index
+pub fn jump_targets(blocks: &[Block]) -> BTreeMap<usize, usize> {
+    let mut stack = ConstStack::new();
+    let mut targets = BTreeMap::new();
+    for (i, b) in blocks.iter().enumerate() {
+        if b.opcode == OpCode::JUMPDEST {
+            stack.clear();
+        }
+        if matches!(b.opcode, OpCode::JUMP | OpCode::JUMPI) {
+            if let Some(target) = stack.peek(0).and_then(|v| usize::try_from(v).ok()) {
+                targets.insert(i, target);
+            }
+        }
+        stack.apply(&b.opcode);
+        if b.opcode.is_terminator() || b.opcode == OpCode::JUMPI {
+            stack.clear();
+        }
+    }
+    targets
+}
+
+// instructions reachable from the start, a jump with an unknown target may reach any JUMPDEST
+pub fn reachable(blocks: &[Block]) -> Vec<bool> {
+    let targets = jump_targets(blocks);
+    let is_jumpdest = |i: &usize| blocks[*i].opcode == OpCode::JUMPDEST;
+    let mut reached = vec![false; blocks.len()];
+    let mut pending = vec![0];
+    while let Some(i) = pending.pop() {
+        if i >= blocks.len() || reached[i] {
+            continue;
+        }
+        reached[i] = true;
+        let op = &blocks[i].opcode;
+        if matches!(op, OpCode::JUMP | OpCode::JUMPI) {
+            match targets.get(&i) {
+                Some(target) => pending.extend(block_index(blocks, *target).filter(is_jumpdest)),
+                None => pending.extend((0..blocks.len()).filter(is_jumpdest)),
+            }
+        }
+        if !op.is_terminator() {
+            pending.push(i + 1);
+        }
+    }
+    reached
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{jump_targets, reachable};
+    use crate::parser::parse;
+
+    #[test]
+    fn test_reachable() {
+        // PUSH1 6 JUMP, PUSH1 0 STOP, JUMPDEST PUSH1 0 PUSH1 0x0d JUMPI STOP, JUMPDEST STOP
+        let blocks = parse("6006566000005b6000600d57005b00").unwrap();
+        let targets = jump_targets(&blocks);
+        assert_eq!(targets.into_iter().collect::<Vec<_>>(), [(1, 6), (7, 13)]);
+        let reached = reachable(&blocks);
+        assert_eq!(
+            reached,
+            [true, true, false, false, true, true, true, true, true, true, true]
+        );
+
+        // CALLDATALOAD JUMP STOP JUMPDEST STOP, the target is unknown so the JUMPDEST is reachable
+        let blocks = parse("3556005b00").unwrap();
+        assert_eq!(reachable(&blocks), [true, true, false, true, true]);
+    }
+}
@@ -0,0 +1,5 @@
+pub mod deploy;
+pub mod dispatcher;
+pub mod ir;
+pub mod jumps;
+pub mod stack;
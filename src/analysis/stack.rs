@@ -0,0 +1,85 @@
+use crate::{emulator::arithmetic, opcode::OpCode, Uint256};
+
+// stack of values known at analysis time, `None` when unknown
+#[derive(Debug, Clone, Default)]
+pub struct ConstStack {
+    pub values: Vec<Option<Uint256>>,
+}
+
+impl ConstStack {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // value at `position` from the top, unknown below what has been tracked
+    pub fn peek(&self, position: usize) -> Option<&Uint256> {
+        self.values
+            .len()
+            .checked_sub(position + 1)
+            .and_then(|i| self.values[i].as_ref())
+    }
+
+    pub fn pop(&mut self) -> Option<Uint256> {
+        self.values.pop().flatten()
+    }
+
+    pub fn push(&mut self, value: Option<Uint256>) {
+        self.values.push(value);
+    }
+
+    pub fn clear(&mut self) {
+        self.values.clear();
+    }
+
+    // simulate `op`; RETURNDATASIZE is taken to be zero as no call has been made
+    pub fn apply(&mut self, op: &OpCode) {
+        match op {
+            OpCode::DUPN(n) => {
+                let value = self.peek(*n as usize - 1).cloned();
+                self.push(value);
+            }
+            OpCode::SWAPN(n) => {
+                let n = *n as usize;
+                while self.values.len() < n + 1 {
+                    self.values.insert(0, None);
+                }
+                let len = self.values.len();
+                self.values.swap(len - 1, len - 1 - n);
+            }
+            OpCode::RETURNDATASIZE => self.push(Some(Uint256::default())),
+            op => {
+                if let Some(value) = op.push_value() {
+                    self.push(Some(value));
+                    return;
+                }
+                let (inputs, outputs) = op.stack_io();
+                let args: Vec<_> = (0..inputs).map(|_| self.pop()).collect();
+                let args: Option<Vec<_>> = args.into_iter().collect();
+                let result = args.and_then(|args| arithmetic(op, &args));
+                for _ in 0..outputs {
+                    self.push(result.clone());
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ConstStack;
+    use crate::{opcode::OpCode, parser::parse};
+
+    #[test]
+    fn test_fold() {
+        let mut stack = ConstStack::new();
+        // CALLDATASIZE PUSH1 1 PUSH2 0x0100 SHL PUSH1 2 PUSH1 3 MUL ADD
+        for block in parse("3660016101001b600260030201").unwrap() {
+            stack.apply(&block.opcode);
+        }
+        // a shift by 256 gives zero, so the sum is known
+        assert_eq!(stack.values, vec![None, Some(6u32.into())]);
+        // an unknown operand is still popped
+        stack.apply(&OpCode::ADD);
+        assert_eq!(stack.values, vec![None]);
+    }
+}
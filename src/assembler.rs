@@ -0,0 +1,154 @@
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Result};
+
+use crate::{
+    formatter::mnemonic, opcode::OpCode, parser::parse_bytes_with_spec, spec::SpecId, Uint256,
+};
+
+// byte of every defined opcode by name, PUSH1-32 included
+fn opcodes() -> HashMap<String, u8> {
+    (0..=255u8)
+        .filter_map(|op| {
+            let opcode = parse_bytes_with_spec(&[op], SpecId::LATEST)
+                .remove(0)
+                .opcode;
+            match opcode {
+                OpCode::INVALID(_) => None,
+                opcode => Some((mnemonic(&opcode), op)),
+            }
+        })
+        .collect()
+}
+
+fn parse_hex(operand: &str) -> Result<Vec<u8>> {
+    let digits = operand
+        .strip_prefix("0x")
+        .ok_or(anyhow!("Expected a hex operand, got {}", operand))?;
+    Ok(hex::decode(digits)?)
+}
+
+// one formatter instruction line without its position and annotation
+fn assemble_instruction(line: &str, opcodes: &HashMap<String, u8>) -> Result<Vec<u8>> {
+    let mut parts = line.split_whitespace();
+    let name = parts.next().unwrap_or_default();
+    let operand = parts.next();
+    let truncated = parts.next() == Some("(truncated)");
+
+    if let Some(op) = name
+        .strip_prefix("INVALID(")
+        .and_then(|s| s.strip_suffix(')'))
+    {
+        let op = op.strip_prefix("0x").unwrap_or(op);
+        return u8::from_str_radix(op, 16)
+            .map(|op| vec![op])
+            .map_err(|_| anyhow!("Invalid opcode {}", name));
+    }
+    let op = *opcodes
+        .get(name)
+        .ok_or(anyhow!("Unknown instruction {}", name))?;
+    let opcode = match (op, operand) {
+        (0x60..=0x7f, Some(operand)) if truncated => {
+            OpCode::TRUNCATED_PUSHN(op - 0x5f, parse_hex(operand)?)
+        }
+        (0x60..=0x7f, Some(operand)) => {
+            let n = op - 0x5f;
+            let value = Uint256::parse_bytes(operand.trim_start_matches("0x").as_bytes(), 16)
+                .ok_or(anyhow!("Invalid PUSH value {}", operand))?;
+            if value.bits() > n as u64 * 8 {
+                return Err(anyhow!("{} does not fit in PUSH{}", operand, n));
+            }
+            OpCode::PUSHN(n, value)
+        }
+        (0x60..=0x7f, None) => return Err(anyhow!("Missing value of {}", name)),
+        (_, Some(operand)) => return Err(anyhow!("Unexpected operand {}", operand)),
+        (op, None) => return Ok(vec![op]),
+    };
+    Ok(opcode.to_bytes())
+}
+
+// bytecode of plain `format` output; labels, comments, raw bytes and annotations are skipped
+pub fn assemble(text: &str) -> Result<Vec<u8>> {
+    let opcodes = opcodes();
+    let mut code = vec![];
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with("//") || line.ends_with(':') {
+            continue;
+        }
+        // the position prefix is optional
+        let line = match line.split_once(": ") {
+            Some((position, rest)) if usize::from_str_radix(position, 16).is_ok() => rest,
+            _ => line,
+        };
+        let line = line.split_once("//").map_or(line, |(line, _)| line);
+        // the raw bytes column
+        let line = match line.split_once(char::is_whitespace) {
+            Some((bytes, rest))
+                if bytes.chars().all(|c| c.is_ascii_hexdigit())
+                    && rest.split_whitespace().next().is_some_and(|op| {
+                        opcodes.contains_key(op) || op.starts_with("INVALID(")
+                    }) =>
+            {
+                rest
+            }
+            _ => line,
+        };
+        let bytes =
+            assemble_instruction(line, &opcodes).map_err(|e| anyhow!("Line {}: {}", i + 1, e))?;
+        code.extend(bytes);
+    }
+    Ok(code)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use proptest::prelude::*;
+
+    use super::assemble;
+    use crate::{
+        formatter::{format, format_with_options, FormatOptions},
+        parser::parse_bytes,
+        signatures::SignatureDb,
+    };
+
+    #[test]
+    fn test_assemble() {
+        let text = "start:\n// comment\n00000000: PUSH2\t0x102\nCALLER\nLOG1\nINVALID(0xc)\nPUSH4\t0xaabb\t(truncated)\n";
+        assert_eq!(
+            assemble(text).unwrap(),
+            [0x61, 0x01, 0x02, 0x33, 0xa1, 0x0c, 0x63, 0xaa, 0xbb]
+        );
+        assert!(assemble("PUSH1\t0x100").is_err());
+        assert!(assemble("FOO").is_err());
+    }
+
+    proptest! {
+        #[test]
+        fn test_format_roundtrip(code in prop::collection::vec(any::<u8>(), 0..512)) {
+            let blocks = parse_bytes(&code);
+            prop_assert_eq!(assemble(&format(&blocks)).unwrap(), code.clone());
+
+            // labels, comments and signature annotations are skipped
+            let marks: BTreeMap<usize, String> = blocks
+                .iter()
+                .step_by(3)
+                .map(|b| (b.position, format!("l{}", b.position)))
+                .collect();
+            let db = SignatureDb::bundled();
+            let options = FormatOptions::default();
+            let text = format_with_options(&blocks, &marks, Some(&db), &marks, &options);
+            prop_assert_eq!(assemble(&text).unwrap(), code.clone());
+
+            let options = FormatOptions {
+                bytes: true,
+                stack_effect: true,
+                ..Default::default()
+            };
+            let text = format_with_options(&blocks, &marks, None, &marks, &options);
+            prop_assert_eq!(assemble(&text).unwrap(), code);
+        }
+    }
+}
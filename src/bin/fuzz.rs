@@ -0,0 +1,138 @@
+use std::{
+    panic,
+    path::PathBuf,
+    process::ExitCode,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{Context, Result};
+use clap::Parser;
+
+use evm_utils::{
+    fuzz::{self, Case, Fixture, Reference, Rng, DEFAULT_REFERENCE},
+    spec::SpecId,
+    statetest::find_fixtures,
+    trace::Trace,
+};
+
+#[derive(Debug, Parser)]
+#[command(about = "Differential fuzzing against a reference EVM's EIP-3155 traces")]
+struct Args {
+    /// Replay these golden fixtures, or directories of them, instead of fuzzing
+    #[arg(long)]
+    golden: Vec<PathBuf>,
+    /// Reference command, {code}, {input}, {gas} and {fork} are replaced by the case
+    #[arg(long, default_value = DEFAULT_REFERENCE)]
+    reference: String,
+    /// Number of generated cases
+    #[arg(long, default_value_t = 1000)]
+    runs: u64,
+    /// Seed of the first case, the current time by default
+    #[arg(long)]
+    seed: Option<u64>,
+    /// Hard fork the cases are generated and executed for
+    #[arg(long, default_value_t = SpecId::default())]
+    spec: SpecId,
+    /// Directory shrunk divergences are saved to
+    #[arg(long, default_value = "fuzz/regressions")]
+    out: PathBuf,
+}
+
+// a panic is a divergence too
+fn run_evemu(case: &Case) -> Option<Trace> {
+    panic::catch_unwind(|| fuzz::run(case)).ok()
+}
+
+fn diverges(case: &Case, reference: &Reference) -> Option<String> {
+    let Ok(expected) = reference.run(case) else {
+        return None;
+    };
+    match run_evemu(case) {
+        Some(trace) => trace.divergence(&expected),
+        None => Some("panic".into()),
+    }
+}
+
+fn replay(paths: &[PathBuf]) -> Result<ExitCode> {
+    let (mut passed, mut failed) = (0, 0);
+    for path in paths {
+        for file in find_fixtures(path)? {
+            let divergence = match Fixture::load(&file) {
+                Ok(fixture) => match run_evemu(&fixture.case) {
+                    Some(trace) => trace.divergence(&fixture.expected),
+                    None => Some("panic".into()),
+                },
+                Err(e) => Some(format!("{:#}", e)),
+            };
+            match divergence {
+                Some(reason) => {
+                    println!("FAIL {}: {}", file.display(), reason);
+                    failed += 1;
+                }
+                None => passed += 1,
+            }
+        }
+    }
+    println!("{} passed, {} failed", passed, failed);
+    Ok(if failed == 0 {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    })
+}
+
+fn main() -> Result<ExitCode> {
+    let args = Args::parse();
+    // panics are reported as divergences, not printed while shrinking
+    panic::set_hook(Box::new(|_| {}));
+
+    if !args.golden.is_empty() {
+        return replay(&args.golden);
+    }
+
+    let reference = Reference::parse(&args.reference)?;
+    let seed = args.seed.unwrap_or_else(|| {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs())
+    });
+    println!("seed {}", seed);
+    // fail early when the reference cannot be run at all
+    let empty = Case {
+        spec: args.spec,
+        code: vec![0x00],
+        calldata: vec![],
+        gas: 100_000,
+    };
+    reference.run(&empty).context("Reference EVM not usable")?;
+
+    let mut divergences = 0;
+    for run in 0..args.runs {
+        let mut rng = Rng::new(seed.wrapping_add(run));
+        let case = Case::generate(&mut rng, args.spec);
+        let Some(reason) = diverges(&case, &reference) else {
+            continue;
+        };
+        let case = fuzz::shrink(&case, |c| diverges(c, &reference).is_some());
+        let fixture = Fixture {
+            divergence: diverges(&case, &reference),
+            expected: reference.run(&case)?,
+            case,
+        };
+        let path = fixture.save(&args.out)?;
+        println!(
+            "FAIL seed {}: {} (shrunk to 0x{}, saved to {})",
+            seed.wrapping_add(run),
+            reason,
+            hex::encode(&fixture.case.code),
+            path.display()
+        );
+        divergences += 1;
+    }
+    println!("{} runs, {} divergences", args.runs, divergences);
+    Ok(if divergences == 0 {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    })
+}
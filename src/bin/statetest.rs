@@ -0,0 +1,64 @@
+use std::{fs, path::PathBuf, process::ExitCode};
+
+use anyhow::{Context, Result};
+use clap::Parser;
+
+use evm_utils::{
+    spec::SpecId,
+    statetest::{find_fixtures, is_blockchain_test, CaseStatus, StateTest},
+};
+
+#[derive(Debug, Parser)]
+#[command(about = "Run ethereum/tests GeneralStateTests fixtures, skipping BlockchainTests")]
+struct Args {
+    /// Fixture files or directories searched for them
+    #[arg(required = true)]
+    paths: Vec<PathBuf>,
+    /// Only run the post entries of this fork
+    #[arg(long)]
+    fork: Option<SpecId>,
+    /// Print failures and the summary only
+    #[arg(long, short)]
+    quiet: bool,
+}
+
+fn main() -> Result<ExitCode> {
+    let args = Args::parse();
+
+    let (mut passed, mut failed, mut skipped) = (0, 0, 0);
+    for path in &args.paths {
+        for file in find_fixtures(path)? {
+            let json = fs::read_to_string(&file)
+                .with_context(|| format!("Failed to read {}", file.display()))?;
+            if is_blockchain_test(&json) {
+                println!("SKIP {}: blockchain tests not supported", file.display());
+                skipped += 1;
+                continue;
+            }
+            let tests = match StateTest::from_json(&json) {
+                Ok(tests) => tests,
+                Err(e) => {
+                    println!("FAIL {}: {:#}", file.display(), e);
+                    failed += 1;
+                    continue;
+                }
+            };
+            for result in tests.iter().flat_map(|t| t.run(args.fork)) {
+                match result.status {
+                    CaseStatus::Passed => passed += 1,
+                    CaseStatus::Failed(_) => failed += 1,
+                    CaseStatus::Skipped(_) => skipped += 1,
+                }
+                if !args.quiet || matches!(result.status, CaseStatus::Failed(_)) {
+                    println!("{}", result);
+                }
+            }
+        }
+    }
+    println!("{} passed, {} failed, {} skipped", passed, failed, skipped);
+    Ok(if failed == 0 {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    })
+}
@@ -0,0 +1,429 @@
+use std::collections::HashMap;
+
+use crate::{
+    abi::{
+        decode, decode_revert, encode, encode_function_call, parse_signature, RevertReason, Token,
+    },
+    emulator::{FrameResult, Halt, Message},
+    env::Env,
+    signatures::selector,
+    state::{Log, State},
+    Address, Uint256,
+};
+
+// address(uint160(uint256(keccak256("hevm cheat code"))))
+pub const CHEATCODE_ADDRESS: Address = [
+    0x71, 0x09, 0x70, 0x9e, 0xcf, 0xa9, 0x1a, 0x80, 0x62, 0x6f, 0xf3, 0x98, 0x9d, 0x68, 0xf6, 0x7f,
+    0x5b, 0x1d, 0xd1, 0x2d,
+];
+
+const SIGNATURES: &[&str] = &[
+    "warp(uint256)",
+    "roll(uint256)",
+    "prank(address)",
+    "prank(address,address)",
+    "deal(address,uint256)",
+    "store(address,bytes32,bytes32)",
+    "load(address,bytes32)",
+    "expectRevert()",
+    "expectRevert(bytes)",
+    "expectRevert(bytes4)",
+    "expectEmit()",
+    "expectEmit(address)",
+    "expectEmit(bool,bool,bool,bool)",
+    "expectEmit(bool,bool,bool,bool,address)",
+    "label(address,string)",
+];
+
+#[derive(Debug, Clone)]
+struct Prank {
+    caller: Address,
+    origin: Option<Address>,
+    // depth of the frame whose next call is pranked
+    depth: usize,
+}
+
+#[derive(Debug, Clone)]
+struct ExpectedRevert {
+    // any revert matches when None
+    data: Option<Vec<u8>>,
+    depth: usize,
+}
+
+#[derive(Debug, Clone)]
+struct ExpectedEmit {
+    // topic 1 to 3 and data
+    checks: [bool; 4],
+    emitter: Option<Address>,
+    depth: usize,
+    // the next log of the test contract, not emitted itself
+    log: Option<Log>,
+    found: bool,
+}
+
+impl ExpectedEmit {
+    fn matches(&self, log: &Log) -> bool {
+        let Some(expected) = &self.log else {
+            return false;
+        };
+        if self.emitter.is_some_and(|e| e != log.address)
+            || expected.topics.len() != log.topics.len()
+            || expected.topics.first() != log.topics.first()
+        {
+            return false;
+        }
+        let topics = expected.topics.iter().zip(&log.topics).skip(1);
+        topics
+            .zip(self.checks)
+            .all(|((a, b), check)| !check || a == b)
+            && (!self.checks[3] || expected.data == log.data)
+    }
+}
+
+// Foundry-style cheatcodes, called through `CHEATCODE_ADDRESS`
+#[derive(Debug, Clone, Default)]
+pub struct Cheatcodes {
+    pub labels: HashMap<Address, String>,
+    prank: Option<Prank>,
+    expected_revert: Option<ExpectedRevert>,
+    expected_emits: Vec<ExpectedEmit>,
+}
+
+fn revert_with(message: &str, gas_left: u64) -> FrameResult {
+    FrameResult {
+        halt: Halt::Revert,
+        gas_left,
+        output: encode_function_call("Error(string)", &[Token::String(message.into())])
+            .unwrap_or_default(),
+        created_address: None,
+    }
+}
+
+fn to_u64(value: &Uint256) -> u64 {
+    value.try_into().unwrap_or(u64::MAX)
+}
+
+fn to_uint(bytes: &[u8]) -> Uint256 {
+    Uint256::from_bytes_be(bytes)
+}
+
+impl Cheatcodes {
+    pub fn label(&self, address: &Address) -> Option<&str> {
+        self.labels.get(address).map(|s| s.as_str())
+    }
+
+    // caller and origin override for the next call made at `depth`
+    pub fn take_prank(&mut self, depth: usize) -> Option<(Address, Option<Address>)> {
+        let prank = self.prank.take_if(|p| p.depth == depth)?;
+        Some((prank.caller, prank.origin))
+    }
+
+    // returns true if the log is an expectation set up by `expectEmit` and must not be emitted
+    pub fn record_log(&mut self, depth: usize, log: &Log) -> bool {
+        if let Some(expected) = self
+            .expected_emits
+            .iter_mut()
+            .find(|e| e.depth == depth && e.log.is_none())
+        {
+            expected.log = Some(log.clone());
+            return true;
+        }
+        if let Some(expected) = self
+            .expected_emits
+            .iter_mut()
+            .find(|e| !e.found && e.matches(log))
+        {
+            expected.found = true;
+        }
+        false
+    }
+
+    // apply pending expectations to the result of a call made at `depth`
+    pub fn check_call(&mut self, depth: usize, mut result: FrameResult) -> FrameResult {
+        if let Some(expected) = self.expected_revert.take_if(|e| e.depth == depth) {
+            if result.is_success() {
+                return revert_with("call did not revert as expected", result.gas_left);
+            }
+            let matches = match &expected.data {
+                None => true,
+                Some(data) if data.len() == 4 => result.output.starts_with(data),
+                Some(data) => {
+                    result.output == *data
+                        || matches!(decode_revert(&result.output),
+                            RevertReason::Error(s) if s.as_bytes() == data.as_slice())
+                }
+            };
+            if !matches {
+                return revert_with("revert data mismatch", result.gas_left);
+            }
+            result.halt = Halt::Stop;
+            result.output.clear();
+            return result;
+        }
+
+        let (checked, pending): (Vec<_>, Vec<_>) = std::mem::take(&mut self.expected_emits)
+            .into_iter()
+            .partition(|e| e.depth == depth && e.log.is_some());
+        self.expected_emits = pending;
+        if checked.iter().any(|e| !e.found) && result.is_success() {
+            return revert_with("log != expected log", result.gas_left);
+        }
+        result
+    }
+
+    fn apply(
+        &mut self,
+        state: &mut State,
+        env: &mut Env,
+        depth: usize,
+        signature: &str,
+        args: &[Token],
+    ) -> Result<Vec<Token>, String> {
+        let expect_emit = |checks, emitter| ExpectedEmit {
+            checks,
+            emitter,
+            depth,
+            log: None,
+            found: false,
+        };
+        match (signature, args) {
+            ("warp(uint256)", [Token::Uint(t)]) => env.block.timestamp = to_u64(t),
+            ("roll(uint256)", [Token::Uint(n)]) => env.block.number = to_u64(n),
+            ("prank(address)", [Token::Address(caller)]) => {
+                self.prank = Some(Prank {
+                    caller: *caller,
+                    origin: None,
+                    depth,
+                })
+            }
+            ("prank(address,address)", [Token::Address(caller), Token::Address(origin)]) => {
+                self.prank = Some(Prank {
+                    caller: *caller,
+                    origin: Some(*origin),
+                    depth,
+                })
+            }
+            ("deal(address,uint256)", [Token::Address(address), Token::Uint(balance)]) => {
+                state.set_balance(*address, balance.clone())
+            }
+            (
+                "store(address,bytes32,bytes32)",
+                [Token::Address(address), Token::FixedBytes(key), Token::FixedBytes(value)],
+            ) => state.sstore(*address, to_uint(key), to_uint(value)),
+            ("load(address,bytes32)", [Token::Address(address), Token::FixedBytes(key)]) => {
+                let value = state.sload(address, &to_uint(key));
+                return Ok(vec![Token::Uint(value)]);
+            }
+            ("expectRevert()", []) => {
+                self.expected_revert = Some(ExpectedRevert { data: None, depth })
+            }
+            ("expectRevert(bytes)", [Token::Bytes(data)])
+            | ("expectRevert(bytes4)", [Token::FixedBytes(data)]) => {
+                self.expected_revert = Some(ExpectedRevert {
+                    data: Some(data.clone()),
+                    depth,
+                })
+            }
+            ("expectEmit()", []) => self.expected_emits.push(expect_emit([true; 4], None)),
+            ("expectEmit(address)", [Token::Address(emitter)]) => self
+                .expected_emits
+                .push(expect_emit([true; 4], Some(*emitter))),
+            (
+                "expectEmit(bool,bool,bool,bool)",
+                [Token::Bool(a), Token::Bool(b), Token::Bool(c), Token::Bool(d)],
+            ) => self
+                .expected_emits
+                .push(expect_emit([*a, *b, *c, *d], None)),
+            (
+                "expectEmit(bool,bool,bool,bool,address)",
+                [Token::Bool(a), Token::Bool(b), Token::Bool(c), Token::Bool(d), Token::Address(emitter)],
+            ) => self
+                .expected_emits
+                .push(expect_emit([*a, *b, *c, *d], Some(*emitter))),
+            ("label(address,string)", [Token::Address(address), Token::String(label)]) => {
+                self.labels.insert(*address, label.clone());
+            }
+            _ => return Err(format!("Invalid arguments for {}", signature)),
+        }
+        Ok(vec![])
+    }
+
+    // handle a call to `CHEATCODE_ADDRESS`, cheatcodes cost no gas
+    pub fn call(&mut self, state: &mut State, env: &mut Env, message: &Message) -> FrameResult {
+        let actual = message
+            .input
+            .get(..4)
+            .map(|s| u32::from_be_bytes(s.try_into().unwrap()));
+        let Some(signature) = SIGNATURES.iter().find(|s| Some(selector(s)) == actual) else {
+            return revert_with("Unknown cheatcode", message.gas);
+        };
+        let args = parse_signature(signature)
+            .and_then(|(_, types)| decode(&types, &message.input[4..]))
+            .map_err(|e| e.to_string());
+        // the frame that made the call
+        let depth = message.depth.saturating_sub(1);
+        match args.and_then(|args| self.apply(state, env, depth, signature, &args)) {
+            Ok(tokens) => FrameResult {
+                halt: if tokens.is_empty() {
+                    Halt::Stop
+                } else {
+                    Halt::Return
+                },
+                gas_left: message.gas,
+                output: encode(&tokens),
+                created_address: None,
+            },
+            Err(e) => revert_with(&e, message.gas),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CHEATCODE_ADDRESS;
+    use crate::{
+        abi::{encode_function_call, Token},
+        emulator::{execute_call, CallKind, FrameResult, Halt, Message},
+        env::Env,
+        signatures::selector,
+        spec::SpecId,
+        state::State,
+        util::{address_to_uint, keccak256},
+        Address, Uint256,
+    };
+
+    const TEST: Address = [0xaa; 20];
+    const TARGET: Address = [0xbb; 20];
+
+    // bytecode calling the cheatcode with one word argument, if any
+    fn cheat_code(signature: &str, arg: Option<&Address>) -> String {
+        let mut code = format!("63{:08x}60e01b5f52", selector(signature));
+        if let Some(arg) = arg {
+            code += &format!("73{}600452", hex::encode(arg));
+        }
+        let size = 4 + 32 * usize::from(arg.is_some());
+        code + &format!(
+            "5f5f60{:02x}5f5f73{}5af150",
+            size,
+            hex::encode(CHEATCODE_ADDRESS)
+        )
+    }
+
+    // run `test` as the test contract at depth 0, with `target` deployed
+    fn run_test(test: &str, target: &str) -> State {
+        let mut state = State::default();
+        state.enable_cheatcodes();
+        state.set_code(TEST, hex::decode(test).unwrap());
+        state.set_code(TARGET, hex::decode(target).unwrap());
+        let message = Message {
+            kind: CallKind::Call,
+            caller: [1; 20],
+            address: TEST,
+            code_address: TEST,
+            value: Uint256::default(),
+            input: vec![],
+            gas: 1_000_000,
+            is_static: false,
+            depth: 0,
+        };
+        let result = execute_call(&mut state, &mut Env::default(), SpecId::Cancun, &message);
+        assert!(result.is_success());
+        state
+    }
+
+    fn cheat(state: &mut State, env: &mut Env, signature: &str, args: &[Token]) -> FrameResult {
+        let message = Message {
+            kind: CallKind::Call,
+            caller: [1; 20],
+            address: CHEATCODE_ADDRESS,
+            code_address: CHEATCODE_ADDRESS,
+            value: Uint256::default(),
+            input: encode_function_call(signature, args).unwrap(),
+            gas: 1000,
+            is_static: false,
+            depth: 1,
+        };
+        execute_call(state, env, SpecId::Cancun, &message)
+    }
+
+    #[test]
+    fn test_cheatcodes() {
+        assert_eq!(CHEATCODE_ADDRESS, keccak256(b"hevm cheat code")[12..]);
+
+        let mut state = State::default();
+        let mut env = Env::default();
+        state.enable_cheatcodes();
+        let target = Token::Address([2; 20]);
+        cheat(
+            &mut state,
+            &mut env,
+            "warp(uint256)",
+            &[Token::Uint(100u32.into())],
+        );
+        assert_eq!(env.block.timestamp, 100);
+        cheat(
+            &mut state,
+            &mut env,
+            "deal(address,uint256)",
+            &[target.clone(), Token::Uint(5u32.into())],
+        );
+        assert_eq!(state.balance(&[2; 20]), 5u32.into());
+
+        let slot = Token::FixedBytes(vec![0; 32]);
+        let value = Token::FixedBytes([vec![0; 31], vec![7]].concat());
+        cheat(
+            &mut state,
+            &mut env,
+            "store(address,bytes32,bytes32)",
+            &[target.clone(), slot.clone(), value],
+        );
+        let result = cheat(
+            &mut state,
+            &mut env,
+            "load(address,bytes32)",
+            &[target, slot],
+        );
+        assert_eq!(result.halt, Halt::Return);
+        assert_eq!(result.output, [vec![0; 31], vec![7]].concat());
+
+        // the next call at depth 0 must revert, and then counts as a success
+        cheat(&mut state, &mut env, "expectRevert()", &[]);
+        let cheatcodes = state.cheatcodes.as_mut().unwrap();
+        let reverted = FrameResult {
+            halt: Halt::Revert,
+            gas_left: 0,
+            output: vec![],
+            created_address: None,
+        };
+        assert!(cheatcodes.check_call(0, reverted.clone()).is_success());
+        assert!(!cheatcodes.check_call(0, reverted).is_success());
+    }
+
+    #[test]
+    fn test_prank() {
+        // prank, then DELEGATECALL and CALL a target storing CALLER at slot 0
+        let call = format!("5f5f5f5f5f73{}5af150", hex::encode(TARGET));
+        let delegatecall = format!("5f5f5f5f73{}5af450", hex::encode(TARGET));
+        let test = cheat_code("prank(address)", Some(&[0xcc; 20])) + &delegatecall + &call + "00";
+        let state = run_test(&test, "335f5500");
+        // the delegated frame keeps the original caller and leaves the prank to the call
+        assert_eq!(state.sload(&TEST, &0u32.into()), address_to_uint(&[1; 20]));
+        assert_eq!(
+            state.sload(&TARGET, &0u32.into()),
+            address_to_uint(&[0xcc; 20])
+        );
+    }
+
+    #[test]
+    fn test_expect_emit() {
+        // expectEmit, the expected LOG1 0x77, then CALL the target and store success at slot 0
+        let call = format!("5f5f5f5f5f73{}5af15f5500", hex::encode(TARGET));
+        let test = cheat_code("expectEmit()", None) + "60775f5fa1" + &call;
+        for (topic, success) in [("77", 1u32), ("78", 0)] {
+            let state = run_test(&test, &format!("60{}5f5fa100", topic));
+            assert_eq!(state.sload(&TEST, &0u32.into()), success.into());
+            // the expectation itself is not emitted
+            assert!(state.logs.iter().all(|log| log.address == TARGET));
+        }
+    }
+}
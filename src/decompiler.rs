@@ -0,0 +1,1442 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt::Write as _,
+    rc::Rc,
+};
+
+use num_traits::{One, Zero};
+
+use crate::{
+    analysis::{
+        dispatcher::find_dispatcher,
+        ir::{dominators, BasicBlock, Cfg, Exit, Operand},
+    },
+    block::Block,
+    expr::{self, constant, op},
+    formatter::mnemonic,
+    opcode::OpCode,
+    signatures::SignatureDb,
+    Uint256,
+};
+
+// nodes walked per function before the rest is left out
+const MAX_STEPS: usize = 20_000;
+
+// solc's scratch space and free memory pointer, writes there are bookkeeping
+const RESERVED_MEMORY: usize = 0x60;
+
+// what an expression reads, or an instruction writes
+const STORAGE: u8 = 1;
+const TRANSIENT: u8 = 2;
+const MEMORY: u8 = 4;
+const WORLD: u8 = 8;
+
+type Expr = expr::Expr<Leaf>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Leaf {
+    // local variable, from a join of branches, a loop or a value read before a write
+    Var(usize),
+    // stack item of a function entered with an unknown stack, from the bottom
+    Stack(usize),
+    // KECCAK256 of memory holding these words
+    Hash(Vec<Rc<Expr>>),
+}
+
+fn negate(e: &Rc<Expr>) -> Rc<Expr> {
+    match &**e {
+        Expr::Op(OpCode::ISZERO, args) if args[0].is_boolean() => args[0].clone(),
+        _ => op(OpCode::ISZERO, vec![e.clone()]),
+    }
+}
+
+fn offset(e: &Expr) -> Option<usize> {
+    e.constant().and_then(|v| usize::try_from(v).ok())
+}
+
+fn reads(e: &Rc<Expr>) -> u8 {
+    fn walk(e: &Rc<Expr>, seen: &mut HashSet<*const Expr>) -> u8 {
+        if !seen.insert(Rc::as_ptr(e)) {
+            return 0;
+        }
+        match &**e {
+            Expr::Op(opcode, args) => {
+                let own = match opcode {
+                    OpCode::SLOAD => STORAGE,
+                    OpCode::TLOAD => TRANSIENT,
+                    OpCode::MLOAD | OpCode::SHA3 => MEMORY,
+                    OpCode::BALANCE
+                    | OpCode::SELFBALANCE
+                    | OpCode::EXTCODESIZE
+                    | OpCode::EXTCODEHASH
+                    | OpCode::RETURNDATASIZE => WORLD,
+                    _ => 0,
+                };
+                args.iter().fold(own, |acc, a| acc | walk(a, seen))
+            }
+            Expr::Leaf(Leaf::Hash(words)) => words.iter().fold(0, |acc, w| acc | walk(w, seen)),
+            _ => 0,
+        }
+    }
+    walk(e, &mut HashSet::new())
+}
+
+fn writes(opcode: &OpCode) -> u8 {
+    use OpCode::*;
+
+    match opcode {
+        SSTORE => STORAGE,
+        TSTORE => TRANSIENT,
+        MSTORE | MSTORE8 | CALLDATACOPY | CODECOPY | RETURNDATACOPY | EXTCODECOPY | MCOPY => MEMORY,
+        STATICCALL => MEMORY | WORLD,
+        CALL | CALLCODE | DELEGATECALL | CREATE | CREATE2 => STORAGE | TRANSIENT | MEMORY | WORLD,
+        _ => 0,
+    }
+}
+
+fn references(e: &Rc<Expr>, var: usize) -> bool {
+    match &**e {
+        Expr::Leaf(Leaf::Var(v)) => *v == var,
+        Expr::Op(_, args) | Expr::Leaf(Leaf::Hash(args)) => args.iter().any(|a| references(a, var)),
+        _ => false,
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+struct State {
+    // bottom first
+    stack: Vec<Rc<Expr>>,
+    // words written at constant offsets, `false` once a later write overlapped their tail
+    memory: BTreeMap<usize, (Rc<Expr>, bool)>,
+}
+
+impl State {
+    // drop what a write of `size` bytes at `offset` overwrites
+    fn clobber(&mut self, offset: &Expr, size: &Expr) {
+        let (Some(start), Some(size)) = (self::offset(offset), self::offset(size)) else {
+            self.memory.clear();
+            return;
+        };
+        let Some(end) = start.checked_add(size).filter(|_| size > 0) else {
+            if size > 0 {
+                self.memory.clear();
+            }
+            return;
+        };
+        self.memory.retain(|k, _| !(start..end).contains(k));
+        for (_, (_, whole)) in self.memory.range_mut(start.saturating_sub(31)..start) {
+            *whole = false;
+        }
+    }
+
+    fn store(&mut self, offset: &Expr, value: Rc<Expr>) {
+        self.clobber(offset, &Expr::Const(32u32.into()));
+        if let Some(offset) = self::offset(offset) {
+            self.memory.insert(offset, (value, true));
+        }
+    }
+
+    // words written over `offset..offset + size` in order, when they cover it
+    fn input(&self, offset: &Expr, size: &Expr) -> Option<Vec<Rc<Expr>>> {
+        let start = self::offset(offset)?;
+        let size = self::offset(size)?;
+        if size == 0 {
+            return Some(vec![]);
+        }
+        let words: Vec<_> = self.memory.range(start..start.checked_add(size)?).collect();
+        let covered = words.first().is_some_and(|(k, _)| **k == start)
+            && words.windows(2).all(|w| *w[1].0 <= w[0].0 + 32)
+            && words.last().is_some_and(|(k, _)| **k + 32 >= start + size);
+        covered.then(|| words.into_iter().map(|(_, (v, _))| v.clone()).collect())
+    }
+
+    // the words when memory holds exactly whole words, like the key and slot of a mapping
+    fn words(&self, offset: &Expr, size: &Expr) -> Option<Vec<Rc<Expr>>> {
+        let start = self::offset(offset)?;
+        let size = self::offset(size)?;
+        if size == 0 || size % 32 != 0 {
+            return None;
+        }
+        (0..size / 32)
+            .map(|i| match self.memory.get(&(start + 32 * i)) {
+                Some((value, true)) => Some(value.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Stmt {
+    Assign(usize, Rc<Expr>),
+    // instruction run for its effect, `memory` holds the words it reads from memory when known
+    Effect {
+        opcode: OpCode,
+        args: Vec<Rc<Expr>>,
+        memory: Option<Vec<Rc<Expr>>>,
+        result: Option<usize>,
+    },
+    If(Rc<Expr>, Vec<Stmt>, Vec<Stmt>),
+    Require(Rc<Expr>),
+    // `None` loops until a break
+    While(Option<Rc<Expr>>, Vec<Stmt>),
+    Break,
+    Continue,
+    // a jump the structuring could not follow
+    Goto(Rc<Expr>),
+    // statements of a join, known once every branch reaching it has been walked
+    Phi(usize),
+    Comment(String),
+}
+
+fn is_halt(opcode: &OpCode) -> bool {
+    use OpCode::*;
+
+    matches!(opcode, STOP | RETURN | REVERT | INVALID(_) | SELFDESTRUCT)
+}
+
+// whether control never leaves the end of `stmts`
+fn terminal(stmts: &[Stmt]) -> bool {
+    match stmts.last() {
+        Some(Stmt::Effect { opcode, .. }) => is_halt(opcode),
+        Some(Stmt::Break | Stmt::Continue | Stmt::Goto(_) | Stmt::Comment(_)) => true,
+        Some(Stmt::If(_, then, otherwise)) => terminal(then) && terminal(otherwise),
+        _ => false,
+    }
+}
+
+fn size(stmts: &[Stmt]) -> usize {
+    stmts
+        .iter()
+        .map(|s| match s {
+            Stmt::If(_, then, otherwise) => 1 + size(then) + size(otherwise),
+            Stmt::While(_, body) => 1 + size(body),
+            _ => 1,
+        })
+        .sum()
+}
+
+// `if`, with branches which both end turned into a guard clause and a `require`
+fn conditional(condition: Rc<Expr>, then: Vec<Stmt>, otherwise: Vec<Stmt>) -> Vec<Stmt> {
+    if then.is_empty() && otherwise.is_empty() {
+        return vec![];
+    }
+    if then.is_empty() {
+        return conditional(negate(&condition), otherwise, then);
+    }
+    if !otherwise.is_empty() && terminal(&then) && terminal(&otherwise) {
+        let (condition, short, long) = if size(&then) <= size(&otherwise) {
+            (condition, then, otherwise)
+        } else {
+            (negate(&condition), otherwise, then)
+        };
+        let mut result = conditional(condition, short, vec![]);
+        result.extend(long);
+        return result;
+    }
+    match then.as_slice() {
+        [Stmt::Effect {
+            opcode: OpCode::REVERT,
+            memory: Some(data),
+            ..
+        }] if otherwise.is_empty() && data.is_empty() => vec![Stmt::Require(negate(&condition))],
+        _ => vec![Stmt::If(condition, then, otherwise)],
+    }
+}
+
+fn looping(condition: Option<Rc<Expr>>, mut body: Vec<Stmt>) -> Stmt {
+    if body.last() == Some(&Stmt::Continue) {
+        body.pop();
+    }
+    match body.first() {
+        Some(Stmt::If(c, then, otherwise))
+            if condition.is_none() && then == &[Stmt::Break] && otherwise.is_empty() =>
+        {
+            let condition = negate(c);
+            body.remove(0);
+            Stmt::While(Some(condition), body)
+        }
+        _ => Stmt::While(condition, body),
+    }
+}
+
+// where control goes after a block
+enum Target {
+    Node(usize),
+    // off the end of the code
+    Stop,
+    // a jump to a destination the graph does not know
+    Goto(Rc<Expr>),
+    // left out of the graph for its size
+    Unexplored,
+}
+
+enum Next {
+    Go(Target),
+    Branch(Rc<Expr>, Target, Target),
+    End,
+}
+
+// loops and join points of the code reachable from one entry
+struct Function {
+    // node where the two sides of each branch meet, `None` when they only meet by halting
+    ipdom: Vec<Option<usize>>,
+    // body of the natural loop at each header
+    loops: HashMap<usize, HashSet<usize>>,
+    // whether entries of the dispatcher become functions of their own
+    main: bool,
+}
+
+impl Function {
+    fn new(cfg: &Cfg, root: usize, main: bool) -> Self {
+        let reachable = cfg.reachable(root);
+        let n = cfg.nodes.len();
+        let successors: Vec<Vec<usize>> = (0..n)
+            .map(|i| match reachable.contains(&i) {
+                true => cfg.successors(i).collect(),
+                false => vec![],
+            })
+            .collect();
+        let idom = dominators(&successors, root);
+        let dominates = |a: usize, mut b: usize| loop {
+            if a == b {
+                return true;
+            }
+            match idom[b] {
+                Some(d) if d != b => b = d,
+                _ => return false,
+            }
+        };
+
+        let mut predecessors = vec![vec![]; n];
+        for (u, next) in successors.iter().enumerate() {
+            for &v in next {
+                predecessors[v].push(u);
+            }
+        }
+        let mut loops: HashMap<usize, HashSet<usize>> = HashMap::new();
+        for (u, next) in successors.iter().enumerate() {
+            for &header in next.iter().filter(|h| dominates(**h, u)) {
+                let body = loops
+                    .entry(header)
+                    .or_insert_with(|| HashSet::from([header]));
+                let mut pending = vec![u];
+                while let Some(x) = pending.pop() {
+                    if body.insert(x) {
+                        pending.extend(&predecessors[x]);
+                    }
+                }
+            }
+        }
+
+        // post-dominators are dominators of the reversed graph, from a node every halt reaches
+        let exit = n;
+        let mut reversed = vec![vec![]; n + 1];
+        for (u, next) in successors.iter().enumerate() {
+            if next.is_empty() && reachable.contains(&u) {
+                reversed[exit].push(u);
+            }
+            for &v in next {
+                reversed[v].push(u);
+            }
+        }
+        let ipdom = dominators(&reversed, exit)
+            .into_iter()
+            .take(n)
+            .map(|d| d.filter(|d| *d != exit))
+            .collect();
+        Function { ipdom, loops, main }
+    }
+}
+
+// a node where walks stop, and the states they reached it with
+#[derive(Default)]
+struct Join {
+    node: Option<usize>,
+    arrivals: Vec<(usize, State)>,
+}
+
+struct Loop {
+    header: usize,
+    body: HashSet<usize>,
+    exit: Option<usize>,
+    // whether the header has been walked, reaching it again continues the loop
+    entered: bool,
+    // stack items changing between iterations and their variables, `None` on the first pass
+    vars: Option<Vec<(usize, usize)>>,
+    continues: Vec<State>,
+    breaks: Vec<(usize, State)>,
+}
+
+// temporaries and params of `block` read after its statement `index`
+fn live_after(block: &BasicBlock, index: usize) -> (HashSet<usize>, HashSet<usize>) {
+    let exit = match &block.exit {
+        Exit::Jump(target) => vec![target],
+        Exit::Branch { target, condition } => vec![target, condition],
+        _ => vec![],
+    };
+    let mut temps = HashSet::new();
+    let mut params = HashSet::new();
+    let operands = block.statements[index + 1..]
+        .iter()
+        .flat_map(|s| &s.args)
+        .chain(&block.outputs)
+        .chain(exit);
+    for operand in operands {
+        match operand {
+            Operand::Temp(t) => temps.insert(*t),
+            Operand::Param(p) => params.insert(*p),
+            Operand::Const(_) => false,
+        };
+    }
+    (temps, params)
+}
+
+fn operand(operand: &Operand, state: &State, temps: &[Rc<Expr>]) -> Rc<Expr> {
+    match operand {
+        Operand::Const(v) => constant(v.clone()),
+        Operand::Param(i) => state.stack[state.stack.len() - 1 - i].clone(),
+        Operand::Temp(t) => temps[*t].clone(),
+    }
+}
+
+// structures the graph into statements, walking it with the stack and memory as expressions
+struct Decompiler<'a> {
+    cfg: &'a Cfg,
+    // selector of each function, by the position of its entry
+    entries: HashMap<usize, u32>,
+    // node and state of the first jump into each function
+    functions: HashMap<usize, (usize, State)>,
+    phis: Vec<Vec<Stmt>>,
+    loops: Vec<Loop>,
+    // nodes of the walk so far, revisiting one outside of a loop is a goto
+    path: Vec<usize>,
+    vars: usize,
+    steps: usize,
+    // nesting of first passes over loop bodies, whose output is thrown away
+    dry: usize,
+}
+
+impl Decompiler<'_> {
+    fn var(&mut self) -> usize {
+        self.vars += 1;
+        self.vars - 1
+    }
+
+    fn phi(&mut self) -> usize {
+        self.phis.push(vec![]);
+        self.phis.len() - 1
+    }
+
+    fn position(&self, node: usize) -> usize {
+        self.cfg.blocks[self.cfg.nodes[node].block].position
+    }
+
+    fn function(&mut self, root: usize, state: State, main: bool) -> Vec<Stmt> {
+        let f = Function::new(self.cfg, root, main);
+        self.steps = 0;
+        let body = self.walk(&f, root, state, &mut Join::default());
+        self.simplify(body)
+    }
+
+    // a variable in place of `e` when it reads what is about to be written
+    fn spill(
+        &mut self,
+        e: &mut Rc<Expr>,
+        writes: u8,
+        cache: &mut HashMap<*const Expr, Rc<Expr>>,
+        out: &mut Vec<Stmt>,
+    ) {
+        if reads(e) & writes == 0 {
+            return;
+        }
+        let var = cache.entry(Rc::as_ptr(e)).or_insert_with(|| {
+            let v = self.var();
+            out.push(Stmt::Assign(v, e.clone()));
+            Rc::new(Expr::Leaf(Leaf::Var(v)))
+        });
+        *e = var.clone();
+    }
+
+    // value of a statement, emitting it when it has effects
+    fn apply(
+        &mut self,
+        opcode: &OpCode,
+        args: Vec<Rc<Expr>>,
+        state: &mut State,
+        out: &mut Vec<Stmt>,
+    ) -> Option<Rc<Expr>> {
+        use OpCode::*;
+
+        let effect = |args, memory, result| Stmt::Effect {
+            opcode: opcode.clone(),
+            args,
+            memory,
+            result,
+        };
+        match opcode {
+            MLOAD => {
+                let value = match offset(&args[0]).and_then(|o| state.memory.get(&o)) {
+                    Some((value, true)) => value.clone(),
+                    _ => op(MLOAD, args),
+                };
+                Some(value)
+            }
+            SHA3 => Some(match state.words(&args[0], &args[1]) {
+                Some(words) => Rc::new(Expr::Leaf(Leaf::Hash(words))),
+                None => op(SHA3, args),
+            }),
+            MSTORE => {
+                state.store(&args[0], args[1].clone());
+                if offset(&args[0]).is_none_or(|o| o >= RESERVED_MEMORY) {
+                    out.push(effect(args, None, None));
+                }
+                None
+            }
+            MSTORE8 => {
+                state.clobber(&args[0], &Expr::Const(Uint256::one()));
+                out.push(effect(args, None, None));
+                None
+            }
+            CALLDATACOPY | CODECOPY | RETURNDATACOPY | MCOPY | EXTCODECOPY => {
+                let (destination, size) = if *opcode == EXTCODECOPY {
+                    (1, 3)
+                } else {
+                    (0, 2)
+                };
+                state.clobber(&args[destination], &args[size]);
+                out.push(effect(args, None, None));
+                None
+            }
+            CALL | CALLCODE | DELEGATECALL | STATICCALL | CREATE | CREATE2 => {
+                let (input, output) = match opcode {
+                    CALL | CALLCODE => (3, Some(5)),
+                    DELEGATECALL | STATICCALL => (2, Some(4)),
+                    _ => (1, None),
+                };
+                let memory = state.input(&args[input], &args[input + 1]);
+                if let Some(output) = output {
+                    state.clobber(&args[output], &args[output + 1]);
+                }
+                let v = self.var();
+                out.push(effect(args, memory, Some(v)));
+                Some(Rc::new(Expr::Leaf(Leaf::Var(v))))
+            }
+            RETURN | REVERT | LOGN(_) => {
+                let memory = state.input(&args[0], &args[1]);
+                out.push(effect(args, memory, None));
+                None
+            }
+            _ if opcode.stack_io().1 == 0 => {
+                out.push(effect(args, None, None));
+                None
+            }
+            _ => Some(op(opcode.clone(), args)),
+        }
+    }
+
+    fn target(&self, node: Option<usize>, block: usize, destination: Option<Rc<Expr>>) -> Target {
+        match (node, destination) {
+            (Some(node), _) => Target::Node(node),
+            (None, Some(destination)) => Target::Goto(destination),
+            (None, None) if block < self.cfg.blocks.len() => Target::Unexplored,
+            (None, None) => Target::Stop,
+        }
+    }
+
+    // run the block of `node` on `state`
+    fn execute(
+        &mut self,
+        f: &Function,
+        node: usize,
+        state: &mut State,
+        out: &mut Vec<Stmt>,
+    ) -> Next {
+        let cfg = self.cfg;
+        let n = &cfg.nodes[node];
+        let block = &cfg.blocks[n.block];
+        if state.stack.len() < block.depth {
+            out.push(Stmt::Comment("stack underflow".into()));
+            return Next::End;
+        }
+        let mut temps: Vec<Rc<Expr>> = vec![];
+        for (i, s) in block.statements.iter().enumerate() {
+            let args: Vec<_> = s.args.iter().map(|a| operand(a, state, &temps)).collect();
+            let writes = writes(&s.opcode);
+            if writes != 0 {
+                let (live_temps, live_params) = live_after(block, i);
+                let mut cache = HashMap::new();
+                let len = state.stack.len();
+                for (j, item) in state.stack.iter_mut().enumerate() {
+                    let param = len - 1 - j;
+                    if param >= block.inputs || live_params.contains(&param) {
+                        self.spill(item, writes, &mut cache, out);
+                    }
+                }
+                for (t, item) in temps.iter_mut().enumerate() {
+                    if live_temps.contains(&t) {
+                        self.spill(item, writes, &mut cache, out);
+                    }
+                }
+                for (item, _) in state.memory.values_mut() {
+                    self.spill(item, writes, &mut cache, out);
+                }
+            }
+            let value = self.apply(&s.opcode, args, state, out);
+            temps.extend(value.filter(|_| s.result.is_some()));
+        }
+
+        let outputs: Vec<_> = block
+            .outputs
+            .iter()
+            .map(|o| operand(o, state, &temps))
+            .collect();
+        let exit = match &block.exit {
+            Exit::Jump(target) => vec![operand(target, state, &temps)],
+            Exit::Branch { target, condition } => vec![
+                operand(target, state, &temps),
+                operand(condition, state, &temps),
+            ],
+            _ => vec![],
+        };
+        let len = state.stack.len();
+        state.stack.truncate(len - block.inputs);
+        state.stack.extend(outputs);
+
+        let next = n.block + 1;
+        match (&block.exit, exit.as_slice()) {
+            (Exit::Jump(_), [target]) => Next::Go(self.target(n.jump, next, Some(target.clone()))),
+            (Exit::Branch { .. }, [target, condition]) => {
+                let taken = self.target(n.jump, next, Some(target.clone()));
+                let fallthrough = self.target(n.next, next, None);
+                match condition.constant() {
+                    Some(c) if c.is_zero() => Next::Go(fallthrough),
+                    Some(_) => Next::Go(taken),
+                    None => match taken {
+                        // the dispatcher jumping to a function, which is printed on its own
+                        Target::Node(entry)
+                            if f.main && self.entries.contains_key(&self.position(entry)) =>
+                        {
+                            if self.dry == 0 {
+                                let position = self.position(entry);
+                                self.functions
+                                    .entry(position)
+                                    .or_insert_with(|| (entry, state.clone()));
+                            }
+                            Next::Go(fallthrough)
+                        }
+                        taken => Next::Branch(condition.clone(), taken, fallthrough),
+                    },
+                }
+            }
+            (Exit::Fallthrough, _) => Next::Go(self.target(n.next, next, None)),
+            _ => Next::End,
+        }
+    }
+
+    fn walk(
+        &mut self,
+        f: &Function,
+        mut node: usize,
+        mut state: State,
+        join: &mut Join,
+    ) -> Vec<Stmt> {
+        let mut out = vec![];
+        let depth = self.path.len();
+        loop {
+            if join.node == Some(node) {
+                let id = self.phi();
+                join.arrivals.push((id, state));
+                out.push(Stmt::Phi(id));
+                break;
+            }
+            let innermost = self.loops.last().map(|l| (l.header, l.exit, l.entered));
+            match innermost {
+                Some((header, _, true)) if header == node => {
+                    out.extend(self.continue_loop(state));
+                    break;
+                }
+                Some((_, Some(exit), _)) if exit == node => {
+                    let id = self.phi();
+                    self.loops.last_mut().unwrap().breaks.push((id, state));
+                    out.extend([Stmt::Phi(id), Stmt::Break]);
+                    break;
+                }
+                Some((header, _, false)) if header == node => {
+                    self.loops.last_mut().unwrap().entered = true;
+                }
+                _ => {
+                    let outer = self
+                        .loops
+                        .iter()
+                        .any(|l| l.header == node || l.exit == Some(node));
+                    if outer || self.path.contains(&node) {
+                        out.push(Stmt::Goto(constant(self.position(node))));
+                        break;
+                    }
+                    if f.loops.contains_key(&node) {
+                        out.extend(self.walk_loop(f, node, state, join));
+                        break;
+                    }
+                }
+            }
+            self.steps += 1;
+            if self.steps > MAX_STEPS {
+                out.push(Stmt::Comment("...".into()));
+                break;
+            }
+            self.path.push(node);
+            match self.execute(f, node, &mut state, &mut out) {
+                Next::Go(Target::Node(next)) => node = next,
+                Next::Go(target) => {
+                    out.extend(self.walk_target(f, target, state, join));
+                    break;
+                }
+                Next::Branch(condition, taken, fallthrough) => {
+                    out.extend(self.walk_branch(
+                        f,
+                        node,
+                        condition,
+                        taken,
+                        fallthrough,
+                        state,
+                        join,
+                    ));
+                    break;
+                }
+                Next::End => break,
+            }
+        }
+        self.path.truncate(depth);
+        out
+    }
+
+    fn walk_target(
+        &mut self,
+        f: &Function,
+        target: Target,
+        state: State,
+        join: &mut Join,
+    ) -> Vec<Stmt> {
+        match target {
+            Target::Node(node) => self.walk(f, node, state, join),
+            Target::Stop => vec![Stmt::Effect {
+                opcode: OpCode::STOP,
+                args: vec![],
+                memory: None,
+                result: None,
+            }],
+            Target::Goto(destination) => vec![Stmt::Goto(destination)],
+            Target::Unexplored => vec![Stmt::Comment("...".into())],
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn walk_branch(
+        &mut self,
+        f: &Function,
+        node: usize,
+        condition: Rc<Expr>,
+        taken: Target,
+        fallthrough: Target,
+        state: State,
+        join: &mut Join,
+    ) -> Vec<Stmt> {
+        // branches inside a loop meet inside it, or leave by break and continue
+        let merge = f.ipdom[node].filter(|m| match self.loops.last() {
+            Some(l) => l.body.contains(m) && *m != l.header,
+            None => true,
+        });
+        let mut inner = Join {
+            node: merge,
+            arrivals: vec![],
+        };
+        let then = self.walk_target(f, taken, state.clone(), &mut inner);
+        let otherwise = self.walk_target(f, fallthrough, state, &mut inner);
+        let mut out = vec![Stmt::If(condition, then, otherwise)];
+        if let (Some(merge), Some(state)) = (merge, self.merge(inner.arrivals)) {
+            out.extend(self.walk(f, merge, state, join));
+        }
+        out
+    }
+
+    // one state for all `arrivals`, assigning a variable where their stacks differ
+    fn merge(&mut self, arrivals: Vec<(usize, State)>) -> Option<State> {
+        let mut arrivals = arrivals.into_iter();
+        let (first, mut merged) = arrivals.next()?;
+        let rest: Vec<_> = arrivals.collect();
+        for slot in 0..merged.stack.len() {
+            if rest
+                .iter()
+                .all(|(_, s)| s.stack.get(slot) == Some(&merged.stack[slot]))
+            {
+                continue;
+            }
+            let v = self.var();
+            self.phis[first].push(Stmt::Assign(v, merged.stack[slot].clone()));
+            for (id, s) in &rest {
+                if let Some(e) = s.stack.get(slot) {
+                    self.phis[*id].push(Stmt::Assign(v, e.clone()));
+                }
+            }
+            merged.stack[slot] = Rc::new(Expr::Leaf(Leaf::Var(v)));
+        }
+        merged
+            .memory
+            .retain(|k, e| rest.iter().all(|(_, s)| s.memory.get(k) == Some(e)));
+        Some(merged)
+    }
+
+    fn walk_loop(
+        &mut self,
+        f: &Function,
+        header: usize,
+        mut state: State,
+        join: &mut Join,
+    ) -> Vec<Stmt> {
+        let body = f.loops[&header].clone();
+        let exits: Vec<_> = body
+            .iter()
+            .flat_map(|n| self.cfg.successors(*n))
+            .filter(|n| !body.contains(n))
+            .collect();
+        let exit = f.ipdom[header]
+            .filter(|n| exits.contains(n))
+            .or(exits.iter().min().copied());
+        let new_loop = |vars| Loop {
+            header,
+            body: body.clone(),
+            exit,
+            entered: false,
+            vars,
+            continues: vec![],
+            breaks: vec![],
+        };
+
+        // a first pass to see what changes between iterations
+        let (vars, phis) = (self.vars, self.phis.len());
+        self.loops.push(new_loop(None));
+        self.dry += 1;
+        self.walk(f, header, state.clone(), &mut Join::default());
+        self.dry -= 1;
+        let first = self.loops.pop().unwrap();
+        self.vars = vars;
+        self.phis.truncate(phis);
+
+        let mut out = vec![];
+        let mut vars = vec![];
+        for slot in 0..state.stack.len() {
+            let changes = first
+                .continues
+                .iter()
+                .any(|s| s.stack.get(slot).is_some_and(|e| *e != state.stack[slot]));
+            if changes {
+                let v = self.var();
+                out.push(Stmt::Assign(v, state.stack[slot].clone()));
+                state.stack[slot] = Rc::new(Expr::Leaf(Leaf::Var(v)));
+                vars.push((slot, v));
+            }
+        }
+        state
+            .memory
+            .retain(|k, e| first.continues.iter().all(|s| s.memory.get(k) == Some(e)));
+
+        self.loops.push(new_loop(Some(vars)));
+        let body = self.walk(f, header, state, &mut Join::default());
+        let l = self.loops.pop().unwrap();
+        out.push(Stmt::While(None, body));
+        if let (Some(exit), Some(state)) = (l.exit, self.merge(l.breaks)) {
+            out.extend(self.walk(f, exit, state, join));
+        }
+        out
+    }
+
+    fn continue_loop(&mut self, state: State) -> Vec<Stmt> {
+        let l = self.loops.last_mut().unwrap();
+        let Some(vars) = l.vars.clone() else {
+            l.continues.push(state);
+            return vec![Stmt::Continue];
+        };
+        let assignments: Vec<_> = vars
+            .iter()
+            .filter(|(slot, v)| *state.stack[*slot] != Expr::Leaf(Leaf::Var(*v)))
+            .map(|(slot, v)| (*v, state.stack[*slot].clone()))
+            .collect();
+        // assigned one after the other, unless a value reads another variable being assigned
+        let parallel = assignments.iter().any(|(v, _)| {
+            assignments
+                .iter()
+                .any(|(other, e)| other != v && references(e, *v))
+        });
+        let mut out = vec![];
+        if parallel {
+            let mut copies = vec![];
+            for (v, e) in assignments {
+                let temp = self.var();
+                out.push(Stmt::Assign(temp, e));
+                copies.push(Stmt::Assign(v, Rc::new(Expr::Leaf(Leaf::Var(temp)))));
+            }
+            out.extend(copies);
+        } else {
+            out.extend(assignments.into_iter().map(|(v, e)| Stmt::Assign(v, e)));
+        }
+        out.push(Stmt::Continue);
+        out
+    }
+
+    fn simplify(&self, stmts: Vec<Stmt>) -> Vec<Stmt> {
+        let mut out = vec![];
+        for s in stmts {
+            match s {
+                Stmt::Phi(id) => out.extend(self.simplify(self.phis[id].clone())),
+                Stmt::If(condition, then, otherwise) => out.extend(conditional(
+                    condition,
+                    self.simplify(then),
+                    self.simplify(otherwise),
+                )),
+                Stmt::While(condition, body) => out.push(looping(condition, self.simplify(body))),
+                s => out.push(s),
+            }
+        }
+        out
+    }
+}
+
+fn number(v: &Uint256) -> String {
+    if *v < 256u32.into() {
+        v.to_string()
+    } else {
+        format!("0x{:x}", v)
+    }
+}
+
+// type of a value masked by `mask`, like `address` for the low 160 bits
+fn cast(mask: &Expr) -> Option<String> {
+    let bits = mask.constant()?.bits();
+    let full = (Uint256::one() << bits) - 1u32;
+    if bits == 0 || bits % 8 != 0 || bits >= 256 || *mask.constant()? != full {
+        return None;
+    }
+    match bits {
+        160 => Some("address".into()),
+        bits => Some(format!("uint{}", bits)),
+    }
+}
+
+fn environment(opcode: &OpCode) -> Option<&'static str> {
+    use OpCode::*;
+
+    let name = match opcode {
+        ADDRESS => "address(this)",
+        ORIGIN => "tx.origin",
+        CALLER => "msg.sender",
+        CALLVALUE => "msg.value",
+        CALLDATASIZE => "msg.data.length",
+        GASPRICE => "tx.gasprice",
+        COINBASE => "block.coinbase",
+        TIMESTAMP => "block.timestamp",
+        NUMBER => "block.number",
+        DIFFICULTY => "block.prevrandao",
+        GASLIMIT => "block.gaslimit",
+        CHAINID => "block.chainid",
+        SELFBALANCE => "address(this).balance",
+        BASEFEE => "block.basefee",
+        BLOBBASEFEE => "block.blobbasefee",
+        GAS => "gasleft()",
+        _ => return None,
+    };
+    Some(name)
+}
+
+fn infix(opcode: &OpCode) -> Option<&'static str> {
+    use OpCode::*;
+
+    let symbol = match opcode {
+        ADD => "+",
+        SUB => "-",
+        MUL => "*",
+        DIV => "/",
+        MOD => "%",
+        EXP => "**",
+        LT => "<",
+        GT => ">",
+        EQ => "==",
+        AND => "&",
+        OR => "|",
+        XOR => "^",
+        SHL => "<<",
+        SHR => ">>",
+        _ => return None,
+    };
+    Some(symbol)
+}
+
+struct Printer<'a> {
+    db: Option<&'a SignatureDb>,
+}
+
+impl Printer<'_> {
+    fn expr(&self, e: &Expr) -> String {
+        self.term(e).0
+    }
+
+    // an operand of an infix operator
+    fn operand(&self, e: &Expr) -> String {
+        match self.term(e) {
+            (text, true) => format!("({})", text),
+            (text, false) => text,
+        }
+    }
+
+    fn condition(&self, e: &Expr) -> String {
+        if e.is_boolean() {
+            self.expr(e)
+        } else {
+            format!("{} != 0", self.operand(e))
+        }
+    }
+
+    fn list(&self, exprs: &[Rc<Expr>]) -> String {
+        let exprs: Vec<_> = exprs.iter().map(|e| self.expr(e)).collect();
+        exprs.join(", ")
+    }
+
+    // text and whether it is an infix operation
+    fn term(&self, e: &Expr) -> (String, bool) {
+        match e {
+            Expr::Const(v) => (number(v), false),
+            Expr::Leaf(Leaf::Var(v)) => (format!("v{}", v), false),
+            Expr::Leaf(Leaf::Stack(i)) => (format!("stack[{}]", i), false),
+            Expr::Leaf(Leaf::Hash(words)) => (format!("keccak256({})", self.list(words)), false),
+            Expr::Op(opcode, args) => self.op(opcode, args),
+        }
+    }
+
+    fn op(&self, opcode: &OpCode, args: &[Rc<Expr>]) -> (String, bool) {
+        use OpCode::*;
+
+        if let Some(name) = environment(opcode) {
+            return (name.into(), false);
+        }
+        let text = match (opcode, args) {
+            (AND, [a, b]) if cast(a).or(cast(b)).is_some() => {
+                let (ty, value) = match cast(a) {
+                    Some(ty) => (ty, b),
+                    None => (cast(b).unwrap(), a),
+                };
+                format!("{}({})", ty, self.expr(value))
+            }
+            (SHR, [shift, value])
+                if shift.constant() == Some(&0xe0u32.into())
+                    && **value == Expr::Op(CALLDATALOAD, vec![constant(0u32)]) =>
+            {
+                "msg.sig".into()
+            }
+            (SHL | SHR, [shift, value]) => {
+                let text = format!(
+                    "{} {} {}",
+                    self.operand(value),
+                    infix(opcode).unwrap(),
+                    self.operand(shift)
+                );
+                return (text, true);
+            }
+            (op, [a, b]) if infix(op).is_some() => {
+                let text = format!(
+                    "{} {} {}",
+                    self.operand(a),
+                    infix(op).unwrap(),
+                    self.operand(b)
+                );
+                return (text, true);
+            }
+            (ISZERO, [a]) => {
+                let text = match &**a {
+                    Expr::Op(LT, b) => {
+                        format!("{} >= {}", self.operand(&b[0]), self.operand(&b[1]))
+                    }
+                    Expr::Op(GT, b) => {
+                        format!("{} <= {}", self.operand(&b[0]), self.operand(&b[1]))
+                    }
+                    Expr::Op(EQ, b) => {
+                        format!("{} != {}", self.operand(&b[0]), self.operand(&b[1]))
+                    }
+                    Expr::Op(ISZERO, b) => self.condition(&b[0]),
+                    a if a.is_boolean() => format!("!{}", self.operand(a)),
+                    a => format!("{} == 0", self.operand(a)),
+                };
+                return (text, true);
+            }
+            (NOT, [a]) => format!("~{}", self.operand(a)),
+            (CALLDATALOAD, [a]) => match offset(a) {
+                Some(o) if o >= 4 && (o - 4) % 32 == 0 => format!("arg{}", (o - 4) / 32),
+                _ => format!("calldataload({})", self.expr(a)),
+            },
+            (SLOAD, [key]) => self.location(key),
+            (TLOAD, [key]) => format!("transient[{}]", self.expr(key)),
+            (MLOAD, [a]) => format!("memory[{}]", self.expr(a)),
+            (SHA3, [a, b]) => format!("keccak256({})", self.range(a, b)),
+            (BALANCE, [a]) => format!("{}.balance", self.operand(a)),
+            (EXTCODESIZE, [a]) => format!("{}.code.length", self.operand(a)),
+            (EXTCODEHASH, [a]) => format!("{}.codehash", self.operand(a)),
+            (op, args) => format!("{}({})", mnemonic(op).to_lowercase(), self.list(args)),
+        };
+        (text, false)
+    }
+
+    fn range(&self, offset: &Rc<Expr>, size: &Rc<Expr>) -> String {
+        let end = op(OpCode::ADD, vec![offset.clone(), size.clone()]);
+        format!("memory[{}:{}]", self.expr(offset), self.expr(&end))
+    }
+
+    // memory read by an instruction, as the words written there when known
+    fn data(&self, memory: &Option<Vec<Rc<Expr>>>, offset: &Rc<Expr>, size: &Rc<Expr>) -> String {
+        match memory {
+            Some(words) => self.list(words),
+            None => self.range(offset, size),
+        }
+    }
+
+    // base of the mapping at `slot`, nested mappings hash the key with the outer slot
+    fn mapping(&self, slot: &Expr) -> Option<String> {
+        match slot {
+            Expr::Const(v) => Some(format!("mapping_{}", v)),
+            Expr::Leaf(Leaf::Hash(words)) if words.len() == 2 => Some(format!(
+                "{}[{}]",
+                self.mapping(&words[1])?,
+                self.expr(&words[0])
+            )),
+            _ => None,
+        }
+    }
+
+    // storage slot `key` by the variable it likely holds
+    fn location(&self, key: &Expr) -> String {
+        let array = |base: &Expr| match base {
+            Expr::Leaf(Leaf::Hash(words)) if words.len() == 1 => words[0].constant().cloned(),
+            _ => None,
+        };
+        let name = match key {
+            Expr::Leaf(Leaf::Hash(words)) if words.len() == 2 => self
+                .mapping(&words[1])
+                .map(|base| format!("{}[{}]", base, self.expr(&words[0]))),
+            key if array(key).is_some() => Some(format!("array_{}[0]", array(key).unwrap())),
+            Expr::Op(OpCode::ADD, args) => match (array(&args[0]), array(&args[1])) {
+                (Some(slot), _) => Some(format!("array_{}[{}]", slot, self.expr(&args[1]))),
+                (_, Some(slot)) => Some(format!("array_{}[{}]", slot, self.expr(&args[0]))),
+                _ => None,
+            },
+            _ => None,
+        };
+        name.unwrap_or_else(|| format!("storage[{}]", self.expr(key)))
+    }
+
+    fn effect(&self, opcode: &OpCode, args: &[Rc<Expr>], memory: &Option<Vec<Rc<Expr>>>) -> String {
+        use OpCode::*;
+
+        match opcode {
+            SSTORE => format!("{} = {}", self.location(&args[0]), self.expr(&args[1])),
+            TSTORE => format!(
+                "transient[{}] = {}",
+                self.expr(&args[0]),
+                self.expr(&args[1])
+            ),
+            MSTORE => format!("memory[{}] = {}", self.expr(&args[0]), self.expr(&args[1])),
+            STOP => "return".into(),
+            INVALID(_) => "invalid()".into(),
+            RETURN => match memory.as_deref() {
+                Some([]) => "return".into(),
+                Some([word]) => format!("return {}", self.expr(word)),
+                Some(words) => format!("return ({})", self.list(words)),
+                None => format!("return {}", self.range(&args[0], &args[1])),
+            },
+            REVERT => format!("revert({})", self.data(memory, &args[0], &args[1])),
+            LOGN(_) => {
+                let topics = &args[2..];
+                let mut fields: Vec<_> = topics.iter().map(|t| self.expr(t)).collect();
+                if memory.as_ref().is_none_or(|words| !words.is_empty()) {
+                    fields.push(self.data(memory, &args[0], &args[1]));
+                }
+                let event = topics
+                    .first()
+                    .and_then(|t| t.constant())
+                    .and_then(|t| self.db?.event(t))
+                    .and_then(|e| e.split_once('('));
+                match event {
+                    Some((name, _)) => format!("emit {}({})", name, fields[1..].join(", ")),
+                    None => format!("log{}({})", topics.len(), fields.join(", ")),
+                }
+            }
+            CALL | CALLCODE | DELEGATECALL | STATICCALL => {
+                let (value, input) = match opcode {
+                    CALL | CALLCODE => (Some(&args[2]), 3),
+                    _ => (None, 2),
+                };
+                let mut options = vec![];
+                if let Some(value) = value.filter(|v| v.constant().is_none_or(|v| !v.is_zero())) {
+                    options.push(format!("value: {}", self.expr(value)));
+                }
+                if *args[0] != Expr::Op(GAS, vec![]) {
+                    options.push(format!("gas: {}", self.expr(&args[0])));
+                }
+                let options = match options.is_empty() {
+                    true => String::new(),
+                    false => format!("{{{}}}", options.join(", ")),
+                };
+                format!(
+                    "{}.{}{}({})",
+                    self.operand(&args[1]),
+                    mnemonic(opcode).to_lowercase(),
+                    options,
+                    self.data(memory, &args[input], &args[input + 1])
+                )
+            }
+            CREATE | CREATE2 => {
+                let mut fields = vec![self.expr(&args[0]), self.data(memory, &args[1], &args[2])];
+                fields.extend(args.get(3).map(|salt| self.expr(salt)));
+                format!("{}({})", mnemonic(opcode).to_lowercase(), fields.join(", "))
+            }
+            op => format!("{}({})", mnemonic(op).to_lowercase(), self.list(args)),
+        }
+    }
+
+    fn statements(&self, stmts: &[Stmt], indent: usize, out: &mut String) {
+        let pad = "    ".repeat(indent);
+        for s in stmts {
+            match s {
+                Stmt::Assign(v, e) => {
+                    let _ = writeln!(out, "{}v{} = {};", pad, v, self.expr(e));
+                }
+                Stmt::Effect {
+                    opcode,
+                    args,
+                    memory,
+                    result,
+                } => {
+                    let assign = result.map(|r| format!("v{} = ", r)).unwrap_or_default();
+                    let _ = writeln!(
+                        out,
+                        "{}{}{};",
+                        pad,
+                        assign,
+                        self.effect(opcode, args, memory)
+                    );
+                }
+                Stmt::If(condition, then, otherwise) => {
+                    let _ = writeln!(out, "{}if ({}) {{", pad, self.condition(condition));
+                    self.statements(then, indent + 1, out);
+                    let mut otherwise = otherwise.as_slice();
+                    loop {
+                        match otherwise {
+                            [] => break,
+                            [Stmt::If(condition, then, rest)] => {
+                                let _ = writeln!(
+                                    out,
+                                    "{}}} else if ({}) {{",
+                                    pad,
+                                    self.condition(condition)
+                                );
+                                self.statements(then, indent + 1, out);
+                                otherwise = rest;
+                            }
+                            _ => {
+                                let _ = writeln!(out, "{}}} else {{", pad);
+                                self.statements(otherwise, indent + 1, out);
+                                break;
+                            }
+                        }
+                    }
+                    let _ = writeln!(out, "{}}}", pad);
+                }
+                Stmt::Require(condition) => {
+                    let _ = writeln!(out, "{}require({});", pad, self.condition(condition));
+                }
+                Stmt::While(condition, body) => {
+                    let condition = match condition {
+                        Some(c) => self.condition(c),
+                        None => "true".into(),
+                    };
+                    let _ = writeln!(out, "{}while ({}) {{", pad, condition);
+                    self.statements(body, indent + 1, out);
+                    let _ = writeln!(out, "{}}}", pad);
+                }
+                Stmt::Break => {
+                    let _ = writeln!(out, "{}break;", pad);
+                }
+                Stmt::Continue => {
+                    let _ = writeln!(out, "{}continue;", pad);
+                }
+                Stmt::Goto(destination) => {
+                    let destination = match offset(destination) {
+                        Some(position) => format!("loc_{:x}", position),
+                        None => self.expr(destination),
+                    };
+                    let _ = writeln!(out, "{}goto {};", pad, destination);
+                }
+                Stmt::Phi(_) => {}
+                Stmt::Comment(text) => {
+                    let _ = writeln!(out, "{}// {}", pad, text);
+                }
+            }
+        }
+    }
+
+    fn function(&self, header: &str, comment: Option<String>, body: &[Stmt], out: &mut String) {
+        if !out.ends_with("{\n") {
+            out.push('\n');
+        }
+        if let Some(comment) = comment {
+            let _ = writeln!(out, "    // {}", comment);
+        }
+        let _ = writeln!(out, "    {} {{", header);
+        self.statements(body, 2, out);
+        out.push_str("    }\n");
+    }
+}
+
+// `function name(type arg0, ...)` of a text signature, arguments named like calldata reads
+fn declaration(signature: &str) -> Option<String> {
+    let (name, params) = signature.strip_suffix(')')?.split_once('(')?;
+    let mut types = vec![];
+    let (mut depth, mut start) = (0, 0);
+    for (i, c) in params.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                types.push(&params[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    if !params.is_empty() {
+        types.push(&params[start..]);
+    }
+    let params: Vec<_> = types
+        .iter()
+        .enumerate()
+        .map(|(i, ty)| format!("{} arg{}", ty, i))
+        .collect();
+    Some(format!("function {}({})", name, params.join(", ")))
+}
+
+// pseudo-Solidity of the code: one function per dispatcher entry, the rest as the fallback
+pub fn decompile(blocks: &[Block], db: Option<&SignatureDb>) -> String {
+    let cfg = Cfg::new(blocks);
+    let entries: Vec<_> = find_dispatcher(blocks)
+        .map(|d| d.entries)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|e| (e.destination, e.selector))
+        .collect();
+    let mut decompiler = Decompiler {
+        cfg: &cfg,
+        entries: entries.iter().copied().collect(),
+        functions: HashMap::new(),
+        phis: vec![],
+        loops: vec![],
+        path: vec![],
+        vars: 0,
+        steps: 0,
+        dry: 0,
+    };
+    let printer = Printer { db };
+
+    let mut result = String::from("contract Decompiled {\n");
+    if cfg.nodes.is_empty() {
+        result.push_str("}\n");
+        return result;
+    }
+    let main = decompiler.function(0, State::default(), true);
+    printer.function("fallback()", None, &main, &mut result);
+    for (destination, selector) in entries {
+        // an entry the walk did not reach starts from what the graph knows of its stack
+        let entry = decompiler.functions.get(&destination).cloned().or_else(|| {
+            let node = (0..cfg.nodes.len()).find(|n| decompiler.position(*n) == destination)?;
+            let stack = cfg.nodes[node]
+                .context
+                .iter()
+                .enumerate()
+                .map(|(i, v)| match v {
+                    Some(v) => constant(v.clone()),
+                    None => Rc::new(Expr::Leaf(Leaf::Stack(i))),
+                })
+                .collect();
+            Some((
+                node,
+                State {
+                    stack,
+                    memory: BTreeMap::new(),
+                },
+            ))
+        });
+        let Some((node, state)) = entry else {
+            continue;
+        };
+        let body = decompiler.function(node, state, false);
+        let signature = db.and_then(|db| db.function(selector));
+        let header = signature
+            .and_then(declaration)
+            .unwrap_or_else(|| format!("function selector_0x{:08x}()", selector));
+        let comment = match signature {
+            Some(signature) => format!("0x{:08x}: {}", selector, signature),
+            None => format!("0x{:08x}", selector),
+        };
+        printer.function(&header, Some(comment), &body, &mut result);
+    }
+    result.push_str("}\n");
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+
+    #[test]
+    fn test_function() {
+        // a9059cbb jumps to 0x14, which requires no value and stores
+        // calldataload(4) at keccak256(caller, 0)
+        let code = "60003560e01c8063a9059cbb14601457600080fd5b3415601e57600080fd5b\
+                    60043533600052600060205260406000205500";
+        let blocks = parse(code).unwrap();
+        assert_eq!(
+            decompile(&blocks, None),
+            "contract Decompiled {
+    fallback() {
+        revert();
+    }
+
+    // 0xa9059cbb
+    function selector_0xa9059cbb() {
+        require(msg.value == 0);
+        mapping_0[msg.sender] = arg0;
+        return;
+    }
+}
+"
+        );
+    }
+
+    #[test]
+    fn test_loop() {
+        // for (i = 0; i < 10; i++) sstore(i, i), the header at 0x2 exits to 0x14
+        let code = "60005b80600a11156014578080556001016002565b00";
+        let blocks = parse(code).unwrap();
+        assert_eq!(
+            decompile(&blocks, None),
+            "contract Decompiled {
+    fallback() {
+        v0 = 0;
+        while (10 > v0) {
+            storage[v0] = v0;
+            v0 = 1 + v0;
+        }
+        return;
+    }
+}
+"
+        );
+    }
+}
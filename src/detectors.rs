@@ -0,0 +1,427 @@
+use std::{
+    collections::{BTreeSet, HashSet},
+    fmt::{self, Write as _},
+    rc::Rc,
+};
+
+use serde_json::json;
+
+use crate::{
+    emulator::CallKind,
+    spec::SpecId,
+    symbolic::{explore, Constraint, Effect, Expr, Input, Limits, Path, Storage, Word},
+};
+
+// gas a call forwards at most to be unable to change state, like `transfer`
+const CALL_STIPEND: u32 = 2300;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Low,
+    Medium,
+    High,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Low => write!(f, "low"),
+            Severity::Medium => write!(f, "medium"),
+            Severity::High => write!(f, "high"),
+        }
+    }
+}
+
+impl Severity {
+    fn sarif_level(&self) -> &'static str {
+        match self {
+            Severity::Low => "note",
+            Severity::Medium => "warning",
+            Severity::High => "error",
+        }
+    }
+}
+
+// a check run on every symbolic path, returning positions and messages of what it found
+#[derive(Debug, Clone, Copy)]
+pub struct Detector {
+    pub id: &'static str,
+    pub description: &'static str,
+    pub severity: Severity,
+    pub check: fn(&Path) -> Vec<(usize, String)>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Finding {
+    // position of the instruction at fault
+    pub position: usize,
+    pub detector: &'static str,
+    pub severity: Severity,
+    pub message: String,
+}
+
+// whether `word` or anything it is made of is `matching`, not looking into hashed data
+// unless `hashes` is set
+fn contains(word: &Word, hashes: bool, matching: &dyn Fn(&Expr) -> bool) -> bool {
+    fn walk(
+        word: &Word,
+        hashes: bool,
+        matching: &dyn Fn(&Expr) -> bool,
+        seen: &mut HashSet<*const Expr>,
+    ) -> bool {
+        if !seen.insert(Rc::as_ptr(word)) {
+            return false;
+        }
+        if matching(word) {
+            return true;
+        }
+        let mut any = |words: &[Word]| words.iter().any(|w| walk(w, hashes, matching, seen));
+        match &**word {
+            Expr::Const(_) | Expr::Leaf(Input::Var(_)) => false,
+            Expr::Leaf(Input::Calldata(offset)) => any(std::slice::from_ref(offset)),
+            Expr::Op(_, args) => any(args),
+            Expr::Leaf(Input::Sha3(bytes)) => hashes && any(bytes),
+            Expr::Leaf(Input::Sload(storage, key)) => {
+                let mut storage = storage.clone();
+                let mut words = vec![key.clone()];
+                while let Storage::Store(previous, k, v) = &*storage.clone() {
+                    words.extend([k.clone(), v.clone()]);
+                    storage = previous.clone();
+                }
+                any(&words)
+            }
+        }
+    }
+    walk(word, hashes, matching, &mut HashSet::new())
+}
+
+fn is_var(name: &'static str) -> impl Fn(&Expr) -> bool {
+    move |e| matches!(e, Expr::Leaf(Input::Var(v)) if v == name)
+}
+
+fn is_calldata(e: &Expr) -> bool {
+    matches!(e, Expr::Leaf(Input::Calldata(_)))
+}
+
+fn mentions(word: &Word, name: &'static str) -> bool {
+    contains(word, true, &is_var(name))
+}
+
+// position of the JUMPI which made `constraint`
+fn constraint_position(path: &Path, constraint: &Constraint) -> usize {
+    path.visited[constraint.step - 1]
+}
+
+fn unprotected_selfdestruct(path: &Path) -> Vec<(usize, String)> {
+    let checked = path
+        .constraints
+        .iter()
+        .any(|c| mentions(&c.condition, "caller") || mentions(&c.condition, "origin"));
+    path.effects
+        .iter()
+        .filter(|_| !checked)
+        .filter_map(|e| match e {
+            Effect::Selfdestruct { position, .. } => Some((
+                *position,
+                "SELFDESTRUCT is reachable without checking the caller".to_string(),
+            )),
+            _ => None,
+        })
+        .collect()
+}
+
+fn controlled_delegatecall(path: &Path) -> Vec<(usize, String)> {
+    path.effects
+        .iter()
+        .filter_map(|e| match e {
+            Effect::Call {
+                position,
+                kind: kind @ (CallKind::DelegateCall | CallKind::CallCode),
+                address,
+                ..
+            } if contains(address, true, &is_calldata) => Some((
+                *position,
+                format!("{:?} to an address taken from calldata: {}", kind, address),
+            )),
+            _ => None,
+        })
+        .collect()
+}
+
+fn unchecked_call(path: &Path) -> Vec<(usize, String)> {
+    let uses = |success: &Word| -> bool {
+        let is_success = |e: &Expr| e == &**success;
+        path.constraints
+            .iter()
+            .map(|c| &c.condition)
+            .chain(path.effects.iter().flat_map(|e| match e {
+                Effect::Sstore { key, value, .. } => vec![key, value],
+                _ => vec![],
+            }))
+            .chain(&path.output)
+            .any(|w| contains(w, true, &is_success))
+    };
+    path.effects
+        .iter()
+        .filter_map(|e| match e {
+            Effect::Call {
+                position,
+                kind,
+                success,
+                ..
+            } if !uses(success) => Some((
+                *position,
+                format!("success of the {:?} is never checked", kind),
+            )),
+            _ => None,
+        })
+        .collect()
+}
+
+fn origin_authentication(path: &Path) -> Vec<(usize, String)> {
+    path.constraints
+        .iter()
+        // `tx.origin == msg.sender` only tells contracts apart
+        .filter(|c| mentions(&c.condition, "origin") && !mentions(&c.condition, "caller"))
+        .map(|c| {
+            (
+                constraint_position(path, c),
+                format!("branch on tx.origin: {}", c.condition),
+            )
+        })
+        .collect()
+}
+
+fn reentrancy(path: &Path) -> Vec<(usize, String)> {
+    let mut call = None;
+    let mut findings = vec![];
+    for effect in &path.effects {
+        match effect {
+            Effect::Call {
+                position,
+                kind: CallKind::Call | CallKind::CallCode,
+                gas,
+                ..
+            } if gas.constant().is_none_or(|gas| *gas > CALL_STIPEND.into()) => {
+                call = Some(*position);
+            }
+            Effect::Sstore { position, .. } => {
+                if let Some(call) = call {
+                    findings.push((
+                        *position,
+                        format!("SSTORE after the external call at 0x{:x}", call),
+                    ));
+                }
+            }
+            _ => {}
+        }
+    }
+    findings
+}
+
+fn arbitrary_storage_write(path: &Path) -> Vec<(usize, String)> {
+    path.effects
+        .iter()
+        .filter_map(|e| match e {
+            // mapping and array slots are hashed, a slot straight from calldata is not
+            Effect::Sstore { position, key, .. }
+                if contains(key, false, &is_calldata)
+                    && !contains(key, true, &|e| matches!(e, Expr::Leaf(Input::Sha3(_)))) =>
+            {
+                Some((
+                    *position,
+                    format!("SSTORE to a slot taken from calldata: {}", key),
+                ))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+pub fn builtin_detectors() -> Vec<Detector> {
+    vec![
+        Detector {
+            id: "unprotected-selfdestruct",
+            description: "SELFDESTRUCT reachable by any caller",
+            severity: Severity::High,
+            check: unprotected_selfdestruct,
+        },
+        Detector {
+            id: "controlled-delegatecall",
+            description: "DELEGATECALL or CALLCODE to an address from calldata",
+            severity: Severity::High,
+            check: controlled_delegatecall,
+        },
+        Detector {
+            id: "unchecked-call",
+            description: "Return value of a call not checked",
+            severity: Severity::Medium,
+            check: unchecked_call,
+        },
+        Detector {
+            id: "tx-origin",
+            description: "Authentication with tx.origin",
+            severity: Severity::Medium,
+            check: origin_authentication,
+        },
+        Detector {
+            id: "reentrancy",
+            description: "Storage written after an external call",
+            severity: Severity::High,
+            check: reentrancy,
+        },
+        Detector {
+            id: "arbitrary-storage-write",
+            description: "Storage slot chosen by the caller",
+            severity: Severity::High,
+            check: arbitrary_storage_write,
+        },
+    ]
+}
+
+// findings of `detectors` on the paths of `code`, once per detector and position
+pub fn detect(code: &[u8], spec: SpecId, limits: &Limits, detectors: &[Detector]) -> Vec<Finding> {
+    let paths = explore(code, spec, limits);
+    let mut findings = BTreeSet::new();
+    for detector in detectors {
+        let mut seen = HashSet::new();
+        for path in &paths {
+            for (position, message) in (detector.check)(path) {
+                if seen.insert(position) {
+                    findings.insert(Finding {
+                        position,
+                        detector: detector.id,
+                        severity: detector.severity,
+                        message,
+                    });
+                }
+            }
+        }
+    }
+    findings.into_iter().collect()
+}
+
+pub fn format_findings(findings: &[Finding]) -> String {
+    let mut result = String::new();
+    for f in findings {
+        let _ = writeln!(
+            result,
+            "{:08x}: [{}] {}: {}",
+            f.position, f.severity, f.detector, f.message
+        );
+    }
+    result
+}
+
+// SARIF 2.1.0 log of `findings` in the bytecode of `artifact`, where the analyzed code starts at
+// `code_offset`; the artifact may be hex text or JSON, so positions are logical rather than regions
+pub fn to_sarif(
+    findings: &[Finding],
+    detectors: &[Detector],
+    artifact: &str,
+    code_offset: usize,
+) -> String {
+    let rules: Vec<_> = detectors
+        .iter()
+        .map(|d| {
+            json!({
+                "id": d.id,
+                "shortDescription": {"text": d.description},
+                "defaultConfiguration": {"level": d.severity.sarif_level()},
+            })
+        })
+        .collect();
+    let results: Vec<_> = findings
+        .iter()
+        .map(|f| {
+            json!({
+                "ruleId": f.detector,
+                "level": f.severity.sarif_level(),
+                "message": {"text": f.message},
+                "locations": [{
+                    "physicalLocation": {"artifactLocation": {"uri": artifact}},
+                    "logicalLocations": [{
+                        "name": format!("pc 0x{:x}", f.position),
+                        "kind": "instruction",
+                    }],
+                }],
+                "properties": {"pc": f.position, "bytecodeOffset": code_offset + f.position},
+            })
+        })
+        .collect();
+    let log = json!({
+        "version": "2.1.0",
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "runs": [{
+            "tool": {"driver": {"name": env!("CARGO_PKG_NAME"), "rules": rules}},
+            "results": results,
+        }],
+    });
+    serde_json::to_string_pretty(&log).unwrap() + "\n"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{builtin_detectors, detect, to_sarif};
+    use crate::{parser::decode_hex, spec::SpecId, symbolic::Limits};
+
+    fn findings(code: &str) -> Vec<(usize, &'static str)> {
+        let code = decode_hex(code).unwrap();
+        detect(
+            &code,
+            SpecId::default(),
+            &Limits::default(),
+            &builtin_detectors(),
+        )
+        .into_iter()
+        .map(|f| (f.position, f.detector))
+        .collect()
+    }
+
+    #[test]
+    fn test_detect() {
+        // selfdestruct(caller)
+        assert_eq!(findings("33ff"), [(1, "unprotected-selfdestruct")]);
+        // if caller == sload(0) { selfdestruct(caller) }
+        assert!(findings("6000543314600957005b33ff").is_empty());
+        // call(gas, calldataload(0), 0, 0, 0, 0, 0), sstore(0, 1)
+        assert_eq!(
+            findings("600060006000600060006000355af1506001600055"),
+            [(0xe, "unchecked-call"), (0x14, "reentrancy")]
+        );
+        // delegatecall(gas, calldataload(0), 0, 0, 0, 0), sstore(calldataload(32), 1), if origin == 0 { stop }
+        assert_eq!(
+            findings("60006000600060006000355af450600160203555326019575b00"),
+            [
+                (0xc, "controlled-delegatecall"),
+                (0xc, "unchecked-call"),
+                (0x13, "arbitrary-storage-write"),
+                (0x17, "tx-origin")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_sarif() {
+        let code = decode_hex("33ff").unwrap();
+        let detectors = builtin_detectors();
+        let findings = detect(&code, SpecId::default(), &Limits::default(), &detectors);
+        let sarif: serde_json::Value =
+            serde_json::from_str(&to_sarif(&findings, &detectors, "code.hex", 0x20)).unwrap();
+        let result = &sarif["runs"][0]["results"][0];
+        assert_eq!(result["ruleId"], "unprotected-selfdestruct");
+        assert_eq!(result["level"], "error");
+        let location = &result["locations"][0];
+        assert!(location["physicalLocation"].get("region").is_none());
+        assert_eq!(location["logicalLocations"][0]["name"], "pc 0x1");
+        assert_eq!(result["properties"]["pc"], 1);
+        assert_eq!(result["properties"]["bytecodeOffset"], 0x21);
+        assert_eq!(
+            sarif["runs"][0]["tool"]["driver"]["rules"]
+                .as_array()
+                .unwrap()
+                .len(),
+            6
+        );
+    }
+}
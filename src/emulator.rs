@@ -1,6 +1,78 @@
+use std::fmt;
+
 use anyhow::Result;
+use num_bigint::BigInt;
+use num_traits::{One, Zero};
+
+use crate::{
+    block::Block,
+    cheatcodes::CHEATCODE_ADDRESS,
+    env::{Env, TxEnv},
+    formatter, gas,
+    opcode::OpCode,
+    parser::parse_bytes_with_spec,
+    precompiles::{self, PrecompileError},
+    signatures::SignatureDb,
+    spec::SpecId,
+    state::{create2_address, create_address, Log, State},
+    trace::TraceStep,
+    util::{
+        address_to_uint, from_signed, keccak256, max_uint256, to_bytes32, to_signed,
+        uint_to_address, Uint256Util,
+    },
+    Address, Uint256,
+};
+
+pub const STACK_LIMIT: usize = 1024;
+pub const CALL_DEPTH_LIMIT: usize = 1024;
+pub const DEFAULT_GAS_LIMIT: u64 = 30_000_000;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VmError {
+    StackUnderflow,
+    StackOverflow,
+    OutOfGas,
+    InvalidJump(usize),
+    InvalidOpcode(u8),
+    ReturnDataOutOfBounds,
+    // state modification in a STATICCALL
+    WriteProtection,
+    CodeSizeExceeded,
+    InitCodeSizeExceeded,
+    // EIP-3541
+    InvalidCodePrefix,
+    CreateCollision,
+    PrecompileFailure(PrecompileError),
+}
+
+impl fmt::Display for VmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VmError::StackUnderflow => write!(f, "Stack underflow"),
+            VmError::StackOverflow => write!(f, "Stack overflow"),
+            VmError::OutOfGas => write!(f, "Out of gas"),
+            VmError::InvalidJump(dest) => write!(f, "Invalid jump destination: 0x{:x}", dest),
+            VmError::InvalidOpcode(op) => write!(f, "Invalid opcode: 0x{:x}", op),
+            VmError::ReturnDataOutOfBounds => write!(f, "Return data out of bounds"),
+            VmError::WriteProtection => write!(f, "State modification in a static call"),
+            VmError::CodeSizeExceeded => write!(f, "Code size exceeded"),
+            VmError::InitCodeSizeExceeded => write!(f, "Init code size exceeded"),
+            VmError::InvalidCodePrefix => write!(f, "Code starting with 0xef"),
+            VmError::CreateCollision => write!(f, "Create collision"),
+            VmError::PrecompileFailure(e) => write!(f, "Precompile failure: {}", e),
+        }
+    }
+}
 
-use crate::{block::Block, opcode::OpCode, util::Uint256Util, Uint256};
+impl std::error::Error for VmError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Halt {
+    Stop,
+    Return,
+    Revert,
+    Error(VmError),
+}
 
 // TODO: use program counter
 #[derive(Debug, Default)]
@@ -12,6 +84,321 @@ pub struct Emulator<'a> {
     pub stack: Vec<Uint256>,
     pub memory: Vec<u8>,
     pub return_data: Vec<u8>,
+    pub spec: SpecId,
+    pub gas_limit: u64,
+    pub gas_used: u64,
+    pub halt: Option<Halt>,
+    pub env: Env,
+    pub state: State,
+    pub address: Address,
+    pub caller: Address,
+    pub value: Uint256,
+    // number of frames above this one
+    pub depth: usize,
+    pub is_static: bool,
+    // output of the last call or create made by this frame
+    pub last_return_data: Vec<u8>,
+    // gas the frame called or created by the current step used, less any stipend it was
+    // given, which the trace leaves out of the step's cost
+    child_gas_used: i64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CallKind {
+    Call,
+    CallCode,
+    DelegateCall,
+    StaticCall,
+}
+
+// a message call executed in a new frame
+#[derive(Debug, Clone)]
+pub struct Message {
+    pub kind: CallKind,
+    pub caller: Address,
+    // account whose storage and balance the frame uses
+    pub address: Address,
+    // account whose code runs
+    pub code_address: Address,
+    pub value: Uint256,
+    pub input: Vec<u8>,
+    pub gas: u64,
+    pub is_static: bool,
+    pub depth: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrameResult {
+    pub halt: Halt,
+    pub gas_left: u64,
+    pub output: Vec<u8>,
+    pub created_address: Option<Address>,
+}
+
+impl FrameResult {
+    pub fn is_success(&self) -> bool {
+        matches!(self.halt, Halt::Stop | Halt::Return)
+    }
+
+    fn error(error: VmError) -> Self {
+        FrameResult {
+            halt: Halt::Error(error),
+            gas_left: 0,
+            output: vec![],
+            created_address: None,
+        }
+    }
+}
+
+// checks made before any instruction runs, shared with the symbolic engine
+pub fn check_instruction(op: &OpCode, stack_len: usize, spec: SpecId) -> Result<(), VmError> {
+    let (inputs, outputs) = op.stack_io();
+    if stack_len < inputs {
+        return Err(VmError::StackUnderflow);
+    }
+    if stack_len - inputs + outputs > STACK_LIMIT {
+        return Err(VmError::StackOverflow);
+    }
+    if !spec.is_enabled_in(op.introduced_in()) {
+        return Err(VmError::InvalidOpcode(op.to_bytes()[0]));
+    }
+    Ok(())
+}
+
+// result of a pure word operation on its operands, top of the stack first;
+// `None` for instructions that read or change anything besides the stack
+pub fn arithmetic(op: &OpCode, args: &[Uint256]) -> Option<Uint256> {
+    let arg = |i: usize| args.get(i).cloned();
+    let shift = |i: usize| usize::try_from(&args[i]).ok().filter(|s| *s < 256);
+    let (a, b) = (arg(0)?, arg(1).unwrap_or_default());
+    let modulus = || -> Uint256 { Uint256::one() << 256 };
+    let result = match op {
+        OpCode::ADD => (a + b).fit(),
+        OpCode::MUL => (a * b).fit(),
+        OpCode::SUB => ((a + modulus()) - b).fit(),
+        OpCode::DIV | OpCode::MOD if b.is_zero() => Uint256::zero(),
+        OpCode::DIV => a / b,
+        OpCode::MOD => a % b,
+        OpCode::SDIV | OpCode::SMOD if b.is_zero() => Uint256::zero(),
+        // BigInt division truncates toward zero like the EVM
+        OpCode::SDIV => from_signed(&(to_signed(&a) / to_signed(&b))),
+        // the result takes the sign of the dividend
+        OpCode::SMOD => from_signed(&(to_signed(&a) % to_signed(&b))),
+        OpCode::ADDMOD | OpCode::MULMOD => {
+            let n = arg(2)?;
+            if n.is_zero() {
+                Uint256::zero()
+            } else if *op == OpCode::ADDMOD {
+                (a + b) % n
+            } else {
+                (a * b) % n
+            }
+        }
+        OpCode::EXP => a.modpow(&b, &modulus()),
+        OpCode::SIGNEXTEND => {
+            if a >= 31u32.into() {
+                return Some(b);
+            }
+            let bit = (u64::try_from(a).unwrap() * 8 + 7) as usize;
+            let mask = (Uint256::one() << bit) - 1u32;
+            if b.bit(bit as u64) {
+                b | (max_uint256() ^ mask)
+            } else {
+                b & mask
+            }
+        }
+        OpCode::LT => ((a < b) as usize).into(),
+        OpCode::GT => ((a > b) as usize).into(),
+        OpCode::SLT => ((to_signed(&a) < to_signed(&b)) as usize).into(),
+        OpCode::SGT => ((to_signed(&a) > to_signed(&b)) as usize).into(),
+        OpCode::EQ => ((a == b) as usize).into(),
+        OpCode::ISZERO => (a.is_zero() as usize).into(),
+        OpCode::AND => a & b,
+        OpCode::OR => a | b,
+        OpCode::XOR => a ^ b,
+        OpCode::NOT => max_uint256() ^ a,
+        OpCode::BYTE => match usize::try_from(&a) {
+            Ok(i) if i < 32 => to_bytes32(&b)[i].into(),
+            _ => Uint256::zero(),
+        },
+        OpCode::SHL => shift(0).map_or(Uint256::zero(), |s| (b << s).fit()),
+        OpCode::SHR => shift(0).map_or(Uint256::zero(), |s| b >> s),
+        OpCode::SAR => {
+            let value = to_signed(&b);
+            match shift(0) {
+                // shifting a negative BigInt rounds toward negative infinity
+                Some(s) => from_signed(&(value >> s)),
+                None if value < BigInt::zero() => max_uint256(),
+                None => Uint256::zero(),
+            }
+        }
+        _ => return None,
+    };
+    Some(result)
+}
+
+// run `code` in a new frame against `state` until it halts
+fn run_frame(
+    state: &mut State,
+    env: &mut Env,
+    spec: SpecId,
+    code: Vec<u8>,
+    message: &Message,
+) -> FrameResult {
+    let parsed = parse_bytes_with_spec(&code, spec);
+    let mut emu = Emulator::new(code, parsed, &message.input)
+        .with_spec(spec)
+        .with_gas_limit(message.gas)
+        .with_env(std::mem::take(env));
+    emu.state = std::mem::take(state);
+    emu.address = message.address;
+    emu.caller = message.caller;
+    emu.value = message.value.clone();
+    emu.is_static = message.is_static;
+    emu.depth = message.depth;
+    emu.run_to_end();
+    *state = std::mem::take(&mut emu.state);
+    *env = std::mem::take(&mut emu.env);
+    FrameResult {
+        // running past the end of the code is an implicit STOP
+        halt: emu.halt.clone().unwrap_or(Halt::Stop),
+        gas_left: emu.gas_left(),
+        output: std::mem::take(&mut emu.return_data),
+        created_address: None,
+    }
+}
+
+// execute a message call, the caller has checked depth and balance
+pub fn execute_call(
+    state: &mut State,
+    env: &mut Env,
+    spec: SpecId,
+    message: &Message,
+) -> FrameResult {
+    if message.code_address == CHEATCODE_ADDRESS {
+        if let Some(mut cheatcodes) = state.cheatcodes.take() {
+            let result = cheatcodes.call(state, env, message);
+            state.cheatcodes = Some(cheatcodes);
+            return result;
+        }
+    }
+    let snapshot = state.snapshot();
+    // only a pranked caller can fail to pay here
+    if message.kind != CallKind::DelegateCall
+        && !state.transfer(message.caller, message.address, &message.value)
+    {
+        return FrameResult {
+            halt: Halt::Revert,
+            gas_left: message.gas,
+            output: vec![],
+            created_address: None,
+        };
+    }
+    if let Some(precompile) = precompiles::get(&message.code_address, spec) {
+        // blake2f rounds and modexp exponents can be made arbitrarily slow, pay first
+        let cost = (precompile.gas)(&message.input, spec);
+        let result = if cost > message.gas {
+            FrameResult::error(VmError::OutOfGas)
+        } else {
+            match (precompile.run)(&message.input, spec) {
+                Ok(output) => FrameResult {
+                    halt: Halt::Return,
+                    gas_left: message.gas - cost,
+                    output,
+                    created_address: None,
+                },
+                Err(e) => FrameResult::error(VmError::PrecompileFailure(e)),
+            }
+        };
+        if !result.is_success() {
+            state.revert_to(snapshot);
+        }
+        return result;
+    }
+    let code = state.code(&message.code_address).to_vec();
+    if code.is_empty() {
+        return FrameResult {
+            halt: Halt::Stop,
+            gas_left: message.gas,
+            output: vec![],
+            created_address: None,
+        };
+    }
+    let result = run_frame(state, env, spec, code, message);
+    if !result.is_success() {
+        state.revert_to(snapshot);
+    }
+    result
+}
+
+// deploy `init_code` at `address`, the caller has bumped its nonce and checked depth and balance
+#[allow(clippy::too_many_arguments)]
+pub fn execute_create(
+    state: &mut State,
+    env: &mut Env,
+    spec: SpecId,
+    caller: Address,
+    address: Address,
+    value: Uint256,
+    init_code: Vec<u8>,
+    gas: u64,
+    depth: usize,
+) -> FrameResult {
+    if state
+        .account(&address)
+        .is_some_and(|a| a.nonce != 0 || !a.code.is_empty())
+    {
+        return FrameResult::error(VmError::CreateCollision);
+    }
+    let snapshot = state.snapshot();
+    state.access_address(address);
+    state.mark_created(address);
+    // EIP-161 starts new contracts at nonce 1
+    state.set_nonce(
+        address,
+        u64::from(spec.is_enabled_in(SpecId::SpuriousDragon)),
+    );
+    state.transfer(caller, address, &value);
+
+    let message = Message {
+        kind: CallKind::Call,
+        caller,
+        address,
+        code_address: address,
+        value,
+        input: vec![],
+        gas,
+        is_static: false,
+        depth,
+    };
+    let mut result = run_frame(state, env, spec, init_code, &message);
+    if !result.is_success() {
+        state.revert_to(snapshot);
+        return result;
+    }
+
+    let code = std::mem::take(&mut result.output);
+    let deposit = gas::CODE_DEPOSIT * code.len() as u64;
+    let error = if spec.is_enabled_in(SpecId::SpuriousDragon) && code.len() > gas::MAX_CODE_SIZE {
+        Some(VmError::CodeSizeExceeded)
+    } else if spec.is_enabled_in(SpecId::London) && code.first() == Some(&0xef) {
+        Some(VmError::InvalidCodePrefix)
+    } else if result.gas_left < deposit && spec.is_enabled_in(SpecId::Homestead) {
+        Some(VmError::OutOfGas)
+    } else {
+        None
+    };
+    if let Some(error) = error {
+        state.revert_to(snapshot);
+        return FrameResult::error(error);
+    }
+    // Frontier leaves the account without code when the deposit cannot be paid
+    if result.gas_left >= deposit {
+        result.gas_left -= deposit;
+        state.set_code(address, code);
+    }
+    result.created_address = Some(address);
+    result
 }
 
 impl<'a> Emulator<'a> {
@@ -20,18 +407,44 @@ impl<'a> Emulator<'a> {
             code,
             raw_code,
             calldata,
+            gas_limit: DEFAULT_GAS_LIMIT,
             ..Default::default()
         }
     }
 
+    pub fn with_spec(mut self, spec: SpecId) -> Self {
+        self.spec = spec;
+        self
+    }
+
+    pub fn with_gas_limit(mut self, gas_limit: u64) -> Self {
+        self.gas_limit = gas_limit;
+        self
+    }
+
+    pub fn with_env(mut self, env: Env) -> Self {
+        self.env = env;
+        self
+    }
+
+    // start a new transaction against the same world state
+    pub fn begin_transaction(&mut self, tx: TxEnv) {
+        self.env.tx = tx;
+        self.state.begin_transaction();
+    }
+
     pub fn is_end(&self) -> bool {
-        !self.return_data.is_empty() || self.block_index >= self.code.len()
+        self.halt.is_some() || self.block_index >= self.code.len()
     }
 
     pub fn current_block(&self) -> &Block {
         &self.code[self.block_index]
     }
 
+    pub fn gas_left(&self) -> u64 {
+        self.gas_limit - self.gas_used
+    }
+
     pub fn get_stack(&self, position: usize) -> Uint256 {
         self.stack
             .get::<usize>(self.stack.len() - position - 1)
@@ -39,31 +452,262 @@ impl<'a> Emulator<'a> {
             .clone()
     }
 
+    // signature of the function the current CALL-family instruction invokes
+    pub fn called_function<'b>(&self, db: &'b SignatureDb) -> Option<&'b str> {
+        // position of argsOffset on the stack
+        let args_position = match self.current_block().opcode {
+            OpCode::CALL | OpCode::CALLCODE => 3,
+            OpCode::DELEGATECALL | OpCode::STATICCALL => 2,
+            _ => return None,
+        };
+        let offset = usize::try_from(
+            self.stack
+                .get(self.stack.len().checked_sub(args_position + 1)?)?,
+        )
+        .ok()?;
+        let size = usize::try_from(
+            self.stack
+                .get(self.stack.len().checked_sub(args_position + 2)?)?,
+        )
+        .ok()?;
+        let calldata = self.memory.get(offset..offset.checked_add(size.min(4))?)?;
+        db.function_of_calldata(calldata)
+    }
+
     pub fn use_stack(&mut self) -> Uint256 {
-        self.stack.split_off(self.stack.len() - 1)[0].clone()
+        self.stack.pop().expect("stack checked before eval")
+    }
+
+    fn use_stack_usize(&mut self) -> usize {
+        self.use_stack().try_into().unwrap_or(usize::MAX)
+    }
+
+    // run until the frame halts, errors included
+    pub fn run_to_end(&mut self) {
+        while !self.is_end() {
+            if self.run().is_err() {
+                break;
+            }
+        }
     }
 
     pub fn run(&mut self) -> Result<()> {
+        let index = self.block_index;
         let block = self.current_block().clone();
+        let gas_left = self.gas_left();
+        let trace_index = self.trace_step(&block);
 
-        match block.opcode {
-            OpCode::PUSHN(n, v) => self.eval_pushn(n, v),
-            OpCode::DUPN(n) => self.eval_dupn(n)?,
-            OpCode::RETURNDATASIZE => self.eval_returndatasize(),
+        self.child_gas_used = 0;
+        let result = self.step(&block);
+        let gas_cost = (gas_left - self.gas_left()).saturating_add_signed(-self.child_gas_used);
+        // frames called by the step have added their own steps after this one
+        if let (Some(i), Some(trace)) = (trace_index, self.state.trace.as_mut()) {
+            trace[i].gas_cost = gas_cost;
+            trace[i].error = result.as_ref().err().map(|e| e.to_string());
+        }
+        if let Err(e) = result {
+            self.gas_used = self.gas_limit;
+            self.halt = Some(Halt::Error(e.clone()));
+            return Err(e.into());
+        }
+
+        // jumps move block_index themselves
+        if self.block_index == index {
+            self.block_index += 1;
+        }
+
+        Ok(())
+    }
+
+    // record the state before `block` runs, returns its index in the trace
+    fn trace_step(&mut self, block: &Block) -> Option<usize> {
+        self.state.trace.as_ref()?;
+        let step = TraceStep {
+            pc: block.position,
+            // blocks need not come from `raw_code`, e.g. when it was truncated
+            op: self
+                .raw_code
+                .get(block.position)
+                .copied()
+                .unwrap_or_else(|| block.opcode.to_bytes()[0]),
+            gas: self.gas_left(),
+            gas_cost: 0,
+            mem_size: self.memory.len(),
+            stack: self.stack.clone(),
+            depth: self.depth + 1,
+            refund: self.state.refund,
+            op_name: formatter::mnemonic(&block.opcode),
+            error: None,
+        };
+        let trace = self.state.trace.as_mut()?;
+        trace.push(step);
+        Some(trace.len() - 1)
+    }
+
+    fn step(&mut self, block: &Block) -> Result<(), VmError> {
+        check_instruction(&block.opcode, self.stack.len(), self.spec)?;
+        if self.is_static
+            && matches!(
+                block.opcode,
+                OpCode::SSTORE
+                    | OpCode::LOGN(_)
+                    | OpCode::CREATE
+                    | OpCode::CREATE2
+                    | OpCode::SELFDESTRUCT
+                    | OpCode::TSTORE
+            )
+        {
+            return Err(VmError::WriteProtection);
+        }
+        self.use_gas(gas::static_cost(&block.opcode, self.spec))?;
+
+        match block.opcode.clone() {
+            OpCode::STOP => self.halt = Some(Halt::Stop),
+            OpCode::EXP => self.eval_exp()?,
+            op @ (OpCode::ADD
+            | OpCode::MUL
+            | OpCode::SUB
+            | OpCode::DIV
+            | OpCode::SDIV
+            | OpCode::MOD
+            | OpCode::SMOD
+            | OpCode::ADDMOD
+            | OpCode::MULMOD
+            | OpCode::SIGNEXTEND
+            | OpCode::LT
+            | OpCode::GT
+            | OpCode::SLT
+            | OpCode::SGT
+            | OpCode::EQ
+            | OpCode::ISZERO
+            | OpCode::AND
+            | OpCode::OR
+            | OpCode::XOR
+            | OpCode::NOT
+            | OpCode::BYTE
+            | OpCode::SHL
+            | OpCode::SHR
+            | OpCode::SAR) => self.eval_arithmetic(&op),
+            OpCode::SHA3 => self.eval_sha3()?,
+            OpCode::ADDRESS => self.stack.push(address_to_uint(&self.address)),
+            OpCode::BALANCE => self.eval_balance()?,
+            OpCode::ORIGIN => self.stack.push(address_to_uint(&self.env.tx.origin)),
+            OpCode::CALLER => self.stack.push(address_to_uint(&self.caller)),
+            OpCode::CALLVALUE => self.stack.push(self.value.clone()),
             OpCode::CALLDATALOAD => self.eval_calldataload(),
-            OpCode::CODECOPY => self.eval_codecopy(),
-            OpCode::RETURN => self.eval_return(),
-            OpCode::SHR => self.eval_shr(),
-            OpCode::EQ => self.eval_eq(),
+            OpCode::CALLDATASIZE => self.eval_calldatasize(),
+            OpCode::CALLDATACOPY => self.eval_calldatacopy()?,
+            OpCode::CODESIZE => self.eval_codesize(),
+            OpCode::CODECOPY => self.eval_codecopy()?,
+            OpCode::GASPRICE => self.stack.push(self.env.tx.gas_price.clone()),
+            OpCode::EXTCODESIZE => self.eval_extcodesize()?,
+            OpCode::EXTCODECOPY => self.eval_extcodecopy()?,
+            OpCode::RETURNDATASIZE => self.eval_returndatasize(),
+            OpCode::RETURNDATACOPY => self.eval_returndatacopy()?,
+            OpCode::EXTCODEHASH => self.eval_extcodehash()?,
+            OpCode::BLOCKHASH => self.eval_blockhash(),
+            OpCode::COINBASE => self.stack.push(address_to_uint(&self.env.block.coinbase)),
+            OpCode::TIMESTAMP => self.stack.push(self.env.block.timestamp.into()),
+            OpCode::NUMBER => self.stack.push(self.env.block.number.into()),
+            OpCode::DIFFICULTY => self.eval_difficulty(),
+            OpCode::GASLIMIT => self.stack.push(self.env.block.gas_limit.into()),
+            OpCode::CHAINID => self.stack.push(self.env.chain_id.into()),
+            OpCode::SELFBALANCE => self.stack.push(self.state.balance(&self.address)),
+            OpCode::BASEFEE => self.stack.push(self.env.block.basefee.clone()),
+            OpCode::BLOBHASH => self.eval_blobhash(),
+            OpCode::BLOBBASEFEE => self.stack.push(self.env.block.blob_base_fee(self.spec)),
+            OpCode::POP => {
+                self.use_stack();
+            }
+            OpCode::MLOAD => self.eval_mload()?,
+            OpCode::MSTORE => self.eval_mstore()?,
+            OpCode::MSTORE8 => self.eval_mstore8()?,
+            OpCode::SLOAD => self.eval_sload()?,
+            OpCode::SSTORE => self.eval_sstore()?,
+            OpCode::JUMP => self.eval_jump()?,
             OpCode::JUMPI => self.eval_jumpi()?,
+            OpCode::PC => self.stack.push(block.position.into()),
+            OpCode::MSIZE => self.stack.push(self.memory.len().into()),
+            OpCode::GAS => self.stack.push(self.gas_left().into()),
             OpCode::JUMPDEST => self.eval_jumpdest(),
-            OpCode::ADD => self.eval_add(),
-            OpCode::MSTORE => self.eval_mstore()?,
-            _ => todo!(),
+            OpCode::TLOAD => self.eval_tload(),
+            OpCode::TSTORE => self.eval_tstore(),
+            OpCode::MCOPY => self.eval_mcopy()?,
+            OpCode::PUSH0 => self.stack.push(Uint256::zero()),
+            OpCode::PUSHN(n, v) => self.eval_pushn(n, v),
+            op @ OpCode::TRUNCATED_PUSHN(..) => self.stack.push(op.push_value().unwrap()),
+            OpCode::DUPN(n) => self.eval_dupn(n),
+            OpCode::SWAPN(n) => self.eval_swapn(n),
+            OpCode::LOGN(n) => self.eval_log(n)?,
+            OpCode::CREATE => self.eval_create(false)?,
+            OpCode::CALL => self.eval_call(CallKind::Call)?,
+            OpCode::CALLCODE => self.eval_call(CallKind::CallCode)?,
+            OpCode::RETURN => self.eval_return()?,
+            OpCode::DELEGATECALL => self.eval_call(CallKind::DelegateCall)?,
+            OpCode::CREATE2 => self.eval_create(true)?,
+            OpCode::STATICCALL => self.eval_call(CallKind::StaticCall)?,
+            OpCode::REVERT => self.eval_revert()?,
+            OpCode::SELFDESTRUCT => self.eval_selfdestruct()?,
+            OpCode::INVALID(op) => return Err(VmError::InvalidOpcode(op)),
+        }
+
+        Ok(())
+    }
+
+    fn use_gas(&mut self, amount: u64) -> Result<(), VmError> {
+        if self.gas_left() < amount {
+            return Err(VmError::OutOfGas);
+        }
+        self.gas_used += amount;
+        Ok(())
+    }
+
+    // charge for and grow memory so that `offset..offset + size` is addressable
+    fn expand_memory(&mut self, offset: usize, size: usize) -> Result<(), VmError> {
+        if size == 0 {
+            return Ok(());
+        }
+        let end = offset.checked_add(size).ok_or(VmError::OutOfGas)?;
+        // anything this large can never be paid for
+        if end > u32::MAX as usize {
+            return Err(VmError::OutOfGas);
+        }
+        let new_len = gas::to_words(end) as usize * 32;
+        if new_len > self.memory.len() {
+            let cost = gas::memory_cost(new_len) - gas::memory_cost(self.memory.len());
+            self.use_gas(cost)?;
+            self.memory.resize(new_len, 0);
         }
+        Ok(())
+    }
 
-        self.block_index += 1;
+    fn pop_memory_range(&mut self) -> Result<(usize, usize), VmError> {
+        let offset = self.use_stack_usize();
+        let size = self.use_stack_usize();
+        if size == 0 {
+            return Ok((0, 0));
+        }
+        self.expand_memory(offset, size)?;
+        Ok((offset, size))
+    }
 
+    // copy `data[offset..offset + size]` to memory, zero padding past the end of `data`
+    fn copy_to_memory(
+        &mut self,
+        dest_offset: usize,
+        data: &[u8],
+        offset: usize,
+        size: usize,
+    ) -> Result<(), VmError> {
+        self.use_gas(gas::copy_cost(size))?;
+        self.expand_memory(dest_offset, size)?;
+        for i in 0..size {
+            self.memory[dest_offset + i] = offset
+                .checked_add(i)
+                .and_then(|p| data.get(p))
+                .copied()
+                .unwrap_or(0);
+        }
         Ok(())
     }
 
@@ -71,122 +715,640 @@ impl<'a> Emulator<'a> {
         self.stack.push(value);
     }
 
-    fn eval_dupn(&mut self, n: u8) -> Result<()> {
-        let value = self.get_stack(n as usize - 1); // TODO: impl Error
+    fn eval_dupn(&mut self, n: u8) {
+        let value = self.get_stack(n as usize - 1);
         self.stack.push(value);
-        Ok(())
+    }
+
+    fn eval_swapn(&mut self, n: u8) {
+        let len = self.stack.len();
+        self.stack.swap(len - 1, len - 1 - n as usize);
     }
 
     fn eval_returndatasize(&mut self) {
-        self.stack.push(Uint256::from(0usize));
+        self.stack.push(self.last_return_data.len().into());
+    }
+
+    fn eval_returndatacopy(&mut self) -> Result<(), VmError> {
+        let dest_offset = self.use_stack_usize();
+        let offset = self.use_stack_usize();
+        let size = self.use_stack_usize();
+
+        match offset.checked_add(size) {
+            Some(end) if end <= self.last_return_data.len() => {}
+            _ => return Err(VmError::ReturnDataOutOfBounds),
+        }
+        let data = std::mem::take(&mut self.last_return_data);
+        let result = self.copy_to_memory(dest_offset, &data, offset, size);
+        self.last_return_data = data;
+        result
     }
 
     fn eval_calldataload(&mut self) {
-        let offset = self.use_stack().try_into().unwrap();
-        let v = Uint256::from_bytes_be(&self.calldata[offset..offset + 32]);
+        let offset = self.use_stack_usize();
+        let mut word = [0u8; 32];
+        for (i, b) in word.iter_mut().enumerate() {
+            *b = offset
+                .checked_add(i)
+                .and_then(|p| self.calldata.get(p))
+                .copied()
+                .unwrap_or(0);
+        }
+        self.stack.push(Uint256::from_bytes_be(&word));
+    }
+
+    fn eval_calldatasize(&mut self) {
+        self.stack.push(self.calldata.len().into());
+    }
+
+    fn eval_calldatacopy(&mut self) -> Result<(), VmError> {
+        let dest_offset = self.use_stack_usize();
+        let offset = self.use_stack_usize();
+        let size = self.use_stack_usize();
+        let calldata = self.calldata;
+        self.copy_to_memory(dest_offset, calldata, offset, size)
+    }
+
+    fn eval_codesize(&mut self) {
+        self.stack.push(self.raw_code.len().into());
+    }
+
+    fn eval_codecopy(&mut self) -> Result<(), VmError> {
+        let dest_offset = self.use_stack_usize();
+        let offset = self.use_stack_usize();
+        let size = self.use_stack_usize();
+        let code = std::mem::take(&mut self.raw_code);
+        let result = self.copy_to_memory(dest_offset, &code, offset, size);
+        self.raw_code = code;
+        result
+    }
+
+    fn eval_return(&mut self) -> Result<(), VmError> {
+        let (offset, size) = self.pop_memory_range()?;
+        self.return_data = self.memory[offset..offset + size].to_vec();
+        self.halt = Some(Halt::Return);
+        Ok(())
+    }
+
+    fn eval_revert(&mut self) -> Result<(), VmError> {
+        let (offset, size) = self.pop_memory_range()?;
+        self.return_data = self.memory[offset..offset + size].to_vec();
+        self.halt = Some(Halt::Revert);
+        Ok(())
+    }
+
+    // pops the operands of a pure word operation and pushes its result
+    fn eval_arithmetic(&mut self, op: &OpCode) {
+        let (inputs, _) = op.stack_io();
+        let args: Vec<_> = (0..inputs).map(|_| self.use_stack()).collect();
+        self.stack
+            .push(arithmetic(op, &args).expect("pure word operation"));
+    }
+
+    fn eval_exp(&mut self) -> Result<(), VmError> {
+        let exponent_bytes = self.get_stack(1).bits().div_ceil(8);
+        self.use_gas(gas::exp_byte_cost(self.spec) * exponent_bytes)?;
+        self.eval_arithmetic(&OpCode::EXP);
+        Ok(())
+    }
+
+    fn eval_sha3(&mut self) -> Result<(), VmError> {
+        let (offset, size) = self.pop_memory_range()?;
+        self.use_gas(gas::SHA3_WORD * gas::to_words(size))?;
+        let hash = keccak256(&self.memory[offset..offset + size]);
+        self.stack.push(Uint256::from_bytes_be(&hash));
+        Ok(())
+    }
+
+    fn eval_mload(&mut self) -> Result<(), VmError> {
+        let offset = self.use_stack_usize();
+        self.expand_memory(offset, 32)?;
+        let v = Uint256::from_bytes_be(&self.memory[offset..offset + 32]);
         self.stack.push(v);
+        Ok(())
+    }
+
+    fn eval_mstore(&mut self) -> Result<(), VmError> {
+        let offset = self.use_stack_usize();
+        let value = self.use_stack();
+        self.expand_memory(offset, 32)?;
+        self.memory[offset..offset + 32].copy_from_slice(&to_bytes32(&value));
+        Ok(())
+    }
+
+    fn eval_mstore8(&mut self) -> Result<(), VmError> {
+        let offset = self.use_stack_usize();
+        let value = self.use_stack();
+        self.expand_memory(offset, 1)?;
+        self.memory[offset] = to_bytes32(&value)[31];
+        Ok(())
+    }
+
+    // index of the JUMPDEST block at `counter`
+    fn jump_target(&self, counter: &Uint256) -> Result<usize, VmError> {
+        let counter: usize = counter.try_into().unwrap_or(usize::MAX);
+        match self.code.binary_search_by_key(&counter, |b| b.position) {
+            Ok(i) if self.code[i].opcode == OpCode::JUMPDEST => Ok(i),
+            _ => Err(VmError::InvalidJump(counter)),
+        }
+    }
+
+    fn eval_jump(&mut self) -> Result<(), VmError> {
+        let counter = self.use_stack();
+        self.block_index = self.jump_target(&counter)?;
+        Ok(())
+    }
+
+    fn eval_jumpi(&mut self) -> Result<(), VmError> {
+        let counter = self.use_stack();
+        let b = self.use_stack();
+
+        if !b.is_zero() {
+            self.block_index = self.jump_target(&counter)?;
+        }
+
+        Ok(())
+    }
+
+    fn eval_jumpdest(&mut self) {}
+
+    fn eval_difficulty(&mut self) {
+        if self.spec.is_enabled_in(SpecId::Merge) {
+            self.stack.push(self.env.block.prevrandao.clone());
+        } else {
+            self.stack.push(self.env.block.difficulty.clone());
+        }
+    }
+
+    fn eval_blobhash(&mut self) {
+        let index = self.use_stack_usize();
+        let hash = self.env.tx.blob_hashes.get(index).cloned();
+        self.stack.push(hash.unwrap_or_default());
+    }
+
+    fn eval_tload(&mut self) {
+        let key = self.use_stack();
+        self.stack.push(self.state.tload(self.address, &key));
     }
 
-    fn eval_codecopy(&mut self) {
-        let dest_offset = self.use_stack().try_into().unwrap();
-        let offset = self.use_stack().try_into().unwrap();
-        let size = self.use_stack().try_into().unwrap();
+    fn eval_tstore(&mut self) {
+        let key = self.use_stack();
+        let value = self.use_stack();
+        self.state.tstore(self.address, key, value);
+    }
 
-        let with_zero_padding = {
-            let mut v = self.raw_code[offset..].to_vec();
-            while v.len() < size {
-                v.push(0);
+    fn eval_log(&mut self, n: u8) -> Result<(), VmError> {
+        let (offset, size) = self.pop_memory_range()?;
+        let topics = (0..n).map(|_| self.use_stack()).collect();
+        self.use_gas(gas::LOG_DATA * size as u64)?;
+        let log = Log {
+            address: self.address,
+            topics,
+            data: self.memory[offset..offset + size].to_vec(),
+        };
+        if let Some(cheatcodes) = self.state.cheatcodes.as_mut() {
+            if cheatcodes.record_log(self.depth, &log) {
+                return Ok(());
             }
-            v
+        }
+        self.state.log(log);
+        Ok(())
+    }
+
+    // marks the account warm and returns whether it was, precompiles always are
+    fn warm_account(&mut self, address: Address) -> bool {
+        self.state.access_address(address) || precompiles::get(&address, self.spec).is_some()
+    }
+
+    // EIP-2929 surcharge for a cold account, the warm cost is part of the static cost
+    fn access_account(&mut self, address: Address) -> Result<(), VmError> {
+        let warm = self.warm_account(address);
+        if self.spec.is_enabled_in(SpecId::Berlin) && !warm {
+            self.use_gas(gas::COLD_ACCOUNT_ACCESS - gas::WARM_STORAGE_READ)?;
+        }
+        Ok(())
+    }
+
+    fn use_stack_address(&mut self) -> Address {
+        uint_to_address(&self.use_stack())
+    }
+
+    fn eval_balance(&mut self) -> Result<(), VmError> {
+        let address = self.use_stack_address();
+        self.access_account(address)?;
+        self.stack.push(self.state.balance(&address));
+        Ok(())
+    }
+
+    fn eval_extcodesize(&mut self) -> Result<(), VmError> {
+        let address = self.use_stack_address();
+        self.access_account(address)?;
+        self.stack.push(self.state.code(&address).len().into());
+        Ok(())
+    }
+
+    fn eval_extcodecopy(&mut self) -> Result<(), VmError> {
+        let address = self.use_stack_address();
+        let dest_offset = self.use_stack_usize();
+        let offset = self.use_stack_usize();
+        let size = self.use_stack_usize();
+        self.access_account(address)?;
+        let code = self.state.code(&address).to_vec();
+        self.copy_to_memory(dest_offset, &code, offset, size)
+    }
+
+    fn eval_extcodehash(&mut self) -> Result<(), VmError> {
+        let address = self.use_stack_address();
+        self.access_account(address)?;
+        self.stack.push(self.state.code_hash(&address));
+        Ok(())
+    }
+
+    // only the 256 most recent blocks are available
+    fn eval_blockhash(&mut self) {
+        let number: u64 = self.use_stack().try_into().unwrap_or(u64::MAX);
+        let current = self.env.block.number;
+        let hash = if number < current && current - number <= 256 {
+            self.env.block.block_hashes.get(&number).cloned()
+        } else {
+            None
         };
+        self.stack.push(hash.unwrap_or_default());
+    }
 
-        let mut memory_ = [
-            self.memory[..dest_offset].to_vec(),
-            with_zero_padding,
-            self.memory[dest_offset..].to_vec(),
-        ]
-        .concat();
-        let _ = memory_.split_off(32);
+    fn eval_sload(&mut self) -> Result<(), VmError> {
+        let key = self.use_stack();
+        let warm = self.state.access_storage(self.address, key.clone());
+        if self.spec.is_enabled_in(SpecId::Berlin) && !warm {
+            self.use_gas(gas::COLD_SLOAD - gas::WARM_STORAGE_READ)?;
+        }
+        self.stack.push(self.state.sload(&self.address, &key));
+        Ok(())
+    }
 
-        self.memory = memory_.try_into().unwrap();
+    fn eval_sstore(&mut self) -> Result<(), VmError> {
+        let key = self.use_stack();
+        let value = self.use_stack();
+        if self.spec.is_enabled_in(SpecId::Istanbul) && self.gas_left() <= gas::SSTORE_SENTRY {
+            return Err(VmError::OutOfGas);
+        }
+        let warm = self.state.access_storage(self.address, key.clone());
+        if self.spec.is_enabled_in(SpecId::Berlin) && !warm {
+            self.use_gas(gas::COLD_SLOAD)?;
+        }
+        let current = self.state.sload(&self.address, &key);
+        let original = self.state.original_storage(&self.address, &key);
+        let (cost, refund) = gas::sstore(&original, &current, &value, self.spec);
+        self.use_gas(cost)?;
+        self.state.add_refund(refund);
+        self.state.sstore(self.address, key, value);
+        Ok(())
     }
 
-    fn eval_return(&mut self) {
-        let offset: usize = self.use_stack().try_into().unwrap();
-        let size: usize = self.use_stack().try_into().unwrap();
+    fn eval_call(&mut self, kind: CallKind) -> Result<(), VmError> {
+        let requested: u64 = self.use_stack().try_into().unwrap_or(u64::MAX);
+        let to = self.use_stack_address();
+        let value = match kind {
+            CallKind::Call | CallKind::CallCode => self.use_stack(),
+            CallKind::DelegateCall | CallKind::StaticCall => Uint256::zero(),
+        };
+        let (args_offset, args_size) = self.pop_memory_range()?;
+        let (ret_offset, ret_size) = self.pop_memory_range()?;
+        if kind == CallKind::Call && self.is_static && !value.is_zero() {
+            return Err(VmError::WriteProtection);
+        }
 
-        self.return_data = {
-            let a = self.memory.get(offset..(offset + size));
-            let memory_len = self.memory.len();
-            if a == None {
-                let memory = self.memory.to_vec();
-                let zeros = std::iter::repeat(0).take(size - memory_len).collect();
-                [zeros, memory].concat()
-            } else {
-                a.unwrap().to_vec()
+        self.access_account(to)?;
+        let mut cost = 0;
+        if !value.is_zero() {
+            cost += gas::CALL_VALUE;
+        }
+        let new_account = if self.spec.is_enabled_in(SpecId::SpuriousDragon) {
+            !value.is_zero() && self.state.is_empty(&to)
+        } else {
+            !self.state.exists(&to)
+        };
+        if kind == CallKind::Call && new_account {
+            cost += gas::NEW_ACCOUNT;
+        }
+        self.use_gas(cost)?;
+        let gas = gas::call_gas(requested, self.gas_left(), self.spec);
+        self.use_gas(gas)?;
+
+        self.last_return_data.clear();
+        if self.depth >= CALL_DEPTH_LIMIT || self.state.balance(&self.address) < value {
+            self.gas_used -= gas;
+            self.stack.push(Uint256::zero());
+            return Ok(());
+        }
+        let stipend = if value.is_zero() {
+            0
+        } else {
+            gas::CALL_STIPEND
+        };
+        let (mut caller, address, value) = match kind {
+            CallKind::Call | CallKind::StaticCall => (self.address, to, value),
+            CallKind::CallCode => (self.address, self.address, value),
+            CallKind::DelegateCall => (self.caller, self.address, self.value.clone()),
+        };
+        let cheated = to != CHEATCODE_ADDRESS && self.state.cheatcodes.is_some();
+        // like Foundry, frames running in the caller's context keep their caller
+        let pranked = cheated && matches!(kind, CallKind::Call | CallKind::StaticCall);
+        let mut origin = None;
+        if let Some(cheatcodes) = self.state.cheatcodes.as_mut().filter(|_| pranked) {
+            if let Some(prank) = cheatcodes.take_prank(self.depth) {
+                caller = prank.0;
+                origin = prank
+                    .1
+                    .map(|o| std::mem::replace(&mut self.env.tx.origin, o));
             }
+        }
+        let message = Message {
+            kind,
+            caller,
+            address,
+            code_address: to,
+            value,
+            input: self.memory[args_offset..args_offset + args_size].to_vec(),
+            gas: gas + stipend,
+            is_static: self.is_static || kind == CallKind::StaticCall,
+            depth: self.depth + 1,
         };
+        let mut result = execute_call(&mut self.state, &mut self.env, self.spec, &message);
+        if let Some(origin) = origin {
+            self.env.tx.origin = origin;
+        }
+        if let Some(cheatcodes) = self.state.cheatcodes.as_mut().filter(|_| cheated) {
+            result = cheatcodes.check_call(self.depth, result);
+        }
+
+        // the stipend is free, so more than `gas` may come back
+        self.gas_used = self.gas_used.saturating_sub(result.gas_left);
+        self.child_gas_used = gas as i64 - result.gas_left as i64;
+        let size = ret_size.min(result.output.len());
+        self.memory[ret_offset..ret_offset + size].copy_from_slice(&result.output[..size]);
+        self.stack.push(u32::from(result.is_success()).into());
+        self.last_return_data = result.output;
+        Ok(())
     }
 
-    fn eval_shr(&mut self) {
-        let shift: usize = self.use_stack().try_into().unwrap();
+    fn eval_create(&mut self, is_create2: bool) -> Result<(), VmError> {
         let value = self.use_stack();
+        let offset = self.use_stack_usize();
+        let size = self.use_stack_usize();
+        let salt = if is_create2 {
+            self.use_stack()
+        } else {
+            Uint256::zero()
+        };
+        if self.spec.is_enabled_in(SpecId::Shanghai) && size > gas::MAX_INITCODE_SIZE {
+            return Err(VmError::InitCodeSizeExceeded);
+        }
+        self.expand_memory(offset, size)?;
+        let mut cost = 0;
+        if self.spec.is_enabled_in(SpecId::Shanghai) {
+            cost += gas::INITCODE_WORD * gas::to_words(size);
+        }
+        if is_create2 {
+            cost += gas::SHA3_WORD * gas::to_words(size);
+        }
+        self.use_gas(cost)?;
+        let init_code = if size == 0 {
+            vec![]
+        } else {
+            self.memory[offset..offset + size].to_vec()
+        };
 
-        if shift > 255 {
-            self.stack.push(0u32.into())
+        self.last_return_data.clear();
+        // a pranked create is made by the pranked address, with its nonce and balance
+        let creator = self
+            .state
+            .cheatcodes
+            .as_mut()
+            .and_then(|cheatcodes| cheatcodes.take_prank(self.depth))
+            .map_or(self.address, |prank| prank.0);
+        let nonce = self.state.nonce(&creator);
+        if self.depth >= CALL_DEPTH_LIMIT
+            || self.state.balance(&creator) < value
+            || nonce == u64::MAX
+        {
+            self.stack.push(Uint256::zero());
+            return Ok(());
+        }
+        self.state.set_nonce(creator, nonce + 1);
+        let address = if is_create2 {
+            create2_address(&creator, &salt, &init_code)
         } else {
-            self.stack.push(value >> shift);
+            create_address(&creator, nonce)
+        };
+        // everything before EIP-150, all but a 64th after
+        let gas = gas::call_gas(self.gas_left(), self.gas_left(), self.spec);
+        self.use_gas(gas)?;
+
+        let result = execute_create(
+            &mut self.state,
+            &mut self.env,
+            self.spec,
+            creator,
+            address,
+            value,
+            init_code,
+            gas,
+            self.depth + 1,
+        );
+        self.gas_used -= result.gas_left;
+        self.child_gas_used = gas as i64 - result.gas_left as i64;
+        if result.halt == Halt::Revert {
+            self.last_return_data = result.output;
+        }
+        match result.created_address {
+            Some(address) => self.stack.push(address_to_uint(&address)),
+            None => self.stack.push(Uint256::zero()),
         }
+        Ok(())
     }
 
-    fn eval_eq(&mut self) {
-        let left = self.use_stack();
-        let right = self.use_stack();
+    fn eval_selfdestruct(&mut self) -> Result<(), VmError> {
+        let beneficiary = self.use_stack_address();
+        if self.spec.is_enabled_in(SpecId::Berlin) && !self.warm_account(beneficiary) {
+            self.use_gas(gas::COLD_ACCOUNT_ACCESS)?;
+        }
+        let balance = self.state.balance(&self.address);
+        let new_account = if self.spec.is_enabled_in(SpecId::SpuriousDragon) {
+            !balance.is_zero() && self.state.is_empty(&beneficiary)
+        } else {
+            self.spec.is_enabled_in(SpecId::Tangerine) && !self.state.exists(&beneficiary)
+        };
+        if new_account {
+            self.use_gas(gas::NEW_ACCOUNT)?;
+        }
+        if !self.spec.is_enabled_in(SpecId::London)
+            && !self.state.selfdestructs.contains(&self.address)
+        {
+            self.state.add_refund(gas::SELFDESTRUCT_REFUND);
+        }
+
+        self.state.transfer(self.address, beneficiary, &balance);
+        // EIP-6780 only deletes accounts created in the same transaction
+        if !self.spec.is_enabled_in(SpecId::Cancun) || self.state.created.contains(&self.address) {
+            self.state.set_balance(self.address, Uint256::zero());
+            self.state.selfdestruct(self.address);
+        }
+        self.halt = Some(Halt::Stop);
+        Ok(())
+    }
 
-        self.stack.push(((left == right) as usize).into())
+    fn eval_mcopy(&mut self) -> Result<(), VmError> {
+        let dest_offset = self.use_stack_usize();
+        let offset = self.use_stack_usize();
+        let size = self.use_stack_usize();
+        if size == 0 {
+            return Ok(());
+        }
+        // both ranges are expanded before copying
+        self.expand_memory(offset, size)?;
+        let data = self.memory[offset..offset + size].to_vec();
+        self.copy_to_memory(dest_offset, &data, 0, size)
     }
+}
 
-    fn eval_jumpi(&mut self) -> Result<()> {
-        let counter: usize = self.use_stack().try_into()?;
-        let b = self.use_stack();
+#[cfg(test)]
+mod tests {
+    use super::{Emulator, Halt, VmError};
+    use crate::{
+        opcode::OpCode,
+        parser::{decode_hex, parse_bytes_with_spec},
+        signatures::SignatureDb,
+        spec::SpecId,
+        Uint256,
+    };
 
-        if !(b == 0usize.into()) {
-            self.block_index = self
-                .code
-                .iter()
-                .position(|x| x.position == counter)
-                .unwrap()
-                - 1;
+    fn run(bytecode: &str, spec: SpecId) -> Emulator<'static> {
+        let raw_code = decode_hex(bytecode).unwrap();
+        let parsed = parse_bytes_with_spec(&raw_code, spec);
+        let mut emu = Emulator::new(raw_code, parsed, &[]).with_spec(spec);
+        while !emu.is_end() {
+            if emu.run().is_err() {
+                break;
+            }
         }
+        emu
+    }
 
-        Ok(())
+    #[test]
+    fn test_arithmetic() {
+        // PUSH1 1 PUSH1 0 SUB  => 2**256 - 1
+        let emu = run("6001600003", SpecId::default());
+        assert_eq!(emu.stack, vec![crate::util::max_uint256()]);
+        // PUSH1 2 PUSH1 0 SUB PUSH1 2 SWAP1 SDIV  => -2 / 2 = -1
+        let emu = run("600260000360029005", SpecId::default());
+        assert_eq!(emu.stack, vec![crate::util::max_uint256()]);
+        assert_eq!(emu.gas_used, 3 * 5 + 5);
     }
 
-    fn eval_jumpdest(&mut self) {}
+    #[test]
+    fn test_fork_gating() {
+        // PUSH1 1 PUSH1 1 SHL
+        let emu = run("600160011b", SpecId::Constantinople);
+        assert_eq!(emu.stack, vec![Uint256::from(2u32)]);
+        let emu = run("600160011b", SpecId::Byzantium);
+        assert_eq!(emu.halt, Some(Halt::Error(VmError::InvalidOpcode(0x1b))));
+        assert_eq!(emu.gas_used, emu.gas_limit);
+    }
+
+    #[test]
+    fn test_cancun_opcodes() {
+        // PUSH1 0x2a PUSH0 TSTORE PUSH0 TLOAD PUSH0 MSTORE PUSH1 0x20 PUSH0 PUSH1 0x20 MCOPY
+        let bytecode = "602a5f5d5f5c5f5260205f60205e";
+        let emu = run(bytecode, SpecId::Cancun);
+        assert_eq!(emu.halt, None);
+        assert_eq!(emu.memory[31], 0x2a);
+        assert_eq!(emu.memory[63], 0x2a);
+        assert_eq!(emu.state.tload(emu.address, &0u32.into()), 0x2au32.into());
 
-    fn eval_add(&mut self) {
-        let l = self.use_stack();
-        let r = self.use_stack();
-        self.stack.push((l + r).fit())
+        let emu = run(bytecode, SpecId::Shanghai);
+        assert_eq!(emu.halt, Some(Halt::Error(VmError::InvalidOpcode(0x5d))));
     }
 
-    fn eval_mstore(&mut self) -> Result<()> {
-        let offset: usize = self.use_stack().try_into()?;
-        let value = self.use_stack();
-        let value_bytes = value.to_bytes_be();
-        let value_bytes_len = value_bytes.len();
+    #[test]
+    fn test_memory_gas() {
+        // PUSH1 1 PUSH1 0 MSTORE PUSH1 0x20 PUSH1 0 RETURN
+        let emu = run("600160005260206000f3", SpecId::default());
+        assert_eq!(emu.halt, Some(Halt::Return));
+        assert_eq!(emu.return_data[31], 1);
+        // 4 pushes + MSTORE + one word of memory
+        assert_eq!(emu.gas_used, 4 * 3 + 3 + 3);
+    }
 
-        let res = {
-            let a = self.memory.get(..offset).map_or(vec![], |v| v.to_vec());
-            let b = self
-                .memory
-                .get(offset + value_bytes_len..)
-                .map_or(vec![], |v| v.to_vec());
-            [a, value_bytes, b].concat()
-        };
+    #[test]
+    fn test_log() {
+        // PUSH1 0xaa PUSH0 MSTORE8 PUSH1 0x07 PUSH1 1 PUSH0 LOG1 STOP
+        let emu = run("60aa5f53600760015fa100", SpecId::default());
+        assert_eq!(emu.halt, Some(Halt::Stop));
+        assert_eq!(emu.state.logs.len(), 1);
+        assert_eq!(emu.state.logs[0].topics, vec![Uint256::from(7u32)]);
+        assert_eq!(emu.state.logs[0].data, vec![0xaa]);
+    }
 
-        self.memory = res;
+    #[test]
+    fn test_precompile_out_of_gas() {
+        // blake2f with 0xffffffff rounds and no gas must fail before running
+        // PUSH4 0xffffffff PUSH1 0xe0 SHL PUSH0 MSTORE
+        // CALL(0, 9, 0, 0, 213, 0, 0)
+        let emu = run("63ffffffff60e01b5f525f5f60d55f5f60095ff100", SpecId::Cancun);
+        assert_eq!(emu.halt, Some(Halt::Stop));
+        assert_eq!(emu.stack, vec![Uint256::from(0u32)]);
+    }
 
-        Ok(())
+    #[test]
+    fn test_create_before_tangerine() {
+        // PUSH1 0 PUSH1 0 PUSH1 0 CREATE STOP
+        for spec in [SpecId::Frontier, SpecId::Homestead, SpecId::Cancun] {
+            let emu = run("600060006000f000", spec);
+            assert_eq!(emu.halt, Some(Halt::Stop));
+            assert_ne!(emu.stack, vec![Uint256::from(0u32)]);
+        }
+    }
+
+    #[test]
+    fn test_trace_without_raw_code() {
+        // PUSH1 1 traced with the raw bytes missing
+        let code = parse_bytes_with_spec(&decode_hex("6001").unwrap(), SpecId::default());
+        let mut emu = Emulator::new(vec![], code, &[]);
+        emu.state.trace = Some(vec![]);
+        emu.run().unwrap();
+        assert_eq!(emu.state.trace.unwrap()[0].op, 0x60);
+    }
+
+    #[test]
+    fn test_called_function() {
+        // mstore the transfer selector, then CALL with argsOffset 0 and argsSize 4
+        let raw_code = decode_hex("63a9059cbb60e01b6000526000600060046000600060006000f1").unwrap();
+        let code = parse_bytes_with_spec(&raw_code, SpecId::default());
+        let mut emu = Emulator::new(raw_code, code, &[]);
+        let mut db = SignatureDb::new();
+        db.insert_function("transfer(address,uint256)");
+        while emu.current_block().opcode != OpCode::CALL {
+            assert_eq!(emu.called_function(&db), None);
+            emu.run().unwrap();
+        }
+        assert_eq!(emu.called_function(&db), Some("transfer(address,uint256)"));
+    }
+
+    #[test]
+    fn test_trace_call_cost() {
+        // CALL of the identity precompile with no input, which uses 15 gas
+        let code = parse_bytes_with_spec(
+            &decode_hex("5f5f5f5f5f600461fffff1").unwrap(),
+            SpecId::default(),
+        );
+        let mut emu = Emulator::new(vec![], code, &[]);
+        emu.state.trace = Some(vec![]);
+        emu.run_to_end();
+        let trace = emu.state.trace.unwrap();
+        let call = trace.iter().find(|s| s.op == 0xf1).unwrap();
+        // warm access only
+        assert_eq!(call.gas_cost, 100);
+        assert_eq!(emu.gas_used, 5 * 2 + 2 * 3 + 100 + 15);
     }
 }
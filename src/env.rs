@@ -0,0 +1,42 @@
+use std::collections::BTreeMap;
+
+use crate::{gas, spec::SpecId, Address, Uint256};
+
+// block being executed
+#[derive(Debug, Clone, Default)]
+pub struct BlockEnv {
+    pub number: u64,
+    pub coinbase: Address,
+    pub timestamp: u64,
+    pub gas_limit: u64,
+    pub basefee: Uint256,
+    // DIFFICULTY before the Merge
+    pub difficulty: Uint256,
+    // DIFFICULTY returns this from the Merge on (EIP-4399)
+    pub prevrandao: Uint256,
+    pub excess_blob_gas: u64,
+    // hashes of previous blocks by number, for BLOCKHASH
+    pub block_hashes: BTreeMap<u64, Uint256>,
+}
+
+// transaction being executed
+#[derive(Debug, Clone, Default)]
+pub struct TxEnv {
+    pub origin: Address,
+    pub gas_price: Uint256,
+    // versioned hashes of the blobs carried by the transaction (EIP-4844)
+    pub blob_hashes: Vec<Uint256>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Env {
+    pub chain_id: u64,
+    pub block: BlockEnv,
+    pub tx: TxEnv,
+}
+
+impl BlockEnv {
+    pub fn blob_base_fee(&self, spec: SpecId) -> Uint256 {
+        gas::blob_base_fee(self.excess_blob_gas, spec)
+    }
+}
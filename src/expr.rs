@@ -0,0 +1,115 @@
+use std::rc::Rc;
+
+use num_traits::{One, Zero};
+
+use crate::{emulator::arithmetic, opcode::OpCode, Uint256};
+
+// a 256-bit value built by the symbolic engine or the decompiler, over the leaves `L`
+// each of them knows about
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expr<L> {
+    Const(Uint256),
+    // the instruction applied to its operands, top of the stack first; pure instructions
+    // evaluate through `emulator::arithmetic` when their operands are constant, others like
+    // BALANCE are uninterpreted functions of their operands
+    Op(OpCode, Vec<Rc<Expr<L>>>),
+    Leaf(L),
+}
+
+impl<L> Expr<L> {
+    pub fn constant(&self) -> Option<&Uint256> {
+        match self {
+            Expr::Const(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    // 0 or 1
+    pub fn is_boolean(&self) -> bool {
+        use OpCode::*;
+
+        matches!(self, Expr::Op(LT | GT | SLT | SGT | EQ | ISZERO, _))
+    }
+}
+
+pub fn constant<L>(value: impl Into<Uint256>) -> Rc<Expr<L>> {
+    Rc::new(Expr::Const(value.into()))
+}
+
+pub fn address_mask() -> Uint256 {
+    (Uint256::one() << 160) - 1u32
+}
+
+// `opcode` applied to `args`, top of the stack first, folding constants and identities
+pub fn op<L>(opcode: OpCode, args: Vec<Rc<Expr<L>>>) -> Rc<Expr<L>> {
+    let values: Option<Vec<_>> = args.iter().map(|a| a.constant().cloned()).collect();
+    if let Some(result) = values.and_then(|values| arithmetic(&opcode, &values)) {
+        return constant(result);
+    }
+    let is_zero = |i: usize| args[i].constant().is_some_and(|v| v.is_zero());
+    match opcode {
+        OpCode::ADD | OpCode::OR | OpCode::XOR if is_zero(0) => return args[1].clone(),
+        OpCode::ADD | OpCode::OR | OpCode::XOR if is_zero(1) => return args[0].clone(),
+        OpCode::MUL | OpCode::AND if is_zero(0) || is_zero(1) => return constant(0u32),
+        OpCode::SHL | OpCode::SHR if is_zero(0) => return args[1].clone(),
+        // addresses need no masking
+        OpCode::AND => {
+            for (mask, value) in [(0, 1), (1, 0)] {
+                let is_address = matches!(
+                    &*args[value],
+                    Expr::Op(
+                        OpCode::CALLER | OpCode::ORIGIN | OpCode::ADDRESS | OpCode::COINBASE,
+                        _
+                    )
+                );
+                if is_address && args[mask].constant() == Some(&address_mask()) {
+                    return args[value].clone();
+                }
+            }
+        }
+        OpCode::ISZERO => {
+            if let Expr::Op(OpCode::ISZERO, inner) = &*args[0] {
+                if inner[0].is_boolean() {
+                    return inner[0].clone();
+                }
+            }
+        }
+        _ => {}
+    }
+    Rc::new(Expr::Op(opcode, args))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use super::{address_mask, constant, op, Expr};
+    use crate::opcode::OpCode;
+
+    type E = Rc<Expr<()>>;
+
+    #[test]
+    fn test_folding() {
+        let leaf: E = Rc::new(Expr::Leaf(()));
+        let sum: E = op(OpCode::ADD, vec![constant(2u32), constant(3u32)]);
+        assert_eq!(sum.constant(), Some(&5u32.into()));
+        assert_eq!(op(OpCode::ADD, vec![constant(0u32), leaf.clone()]), leaf);
+        assert_eq!(
+            op(OpCode::MUL, vec![leaf.clone(), constant(0u32)]),
+            constant(0u32)
+        );
+        let lt = op(OpCode::LT, vec![leaf.clone(), constant(1u32)]);
+        let not_not = op(OpCode::ISZERO, vec![op(OpCode::ISZERO, vec![lt.clone()])]);
+        assert_eq!(not_not, lt);
+    }
+
+    #[test]
+    fn test_address_mask() {
+        let caller: E = op(OpCode::CALLER, vec![]);
+        let masked = op(OpCode::AND, vec![constant(address_mask()), caller.clone()]);
+        assert_eq!(masked, caller);
+        let leaf: E = Rc::new(Expr::Leaf(()));
+        let masked = op(OpCode::AND, vec![constant(address_mask()), leaf]);
+        assert!(matches!(&*masked, Expr::Op(OpCode::AND, _)));
+    }
+}
@@ -1,15 +1,337 @@
-use crate::{block::Block, opcode::OpCode};
-use std::fmt::Write as _;
+use crate::{
+    analysis::stack::ConstStack, block::Block, decompiler::decompile, metadata::Metadata,
+    opcode::OpCode, render::render, signatures::SignatureDb,
+};
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Write as _},
+    str::FromStr,
+};
+
+use anyhow::anyhow;
+use serde::Serialize;
+
+// layout of the disassembly
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Syntax {
+    // `{:08x}: OP` lines
+    #[default]
+    Plain,
+    // a Huff MAIN macro, jump destinations become labels
+    Huff,
+    // an array of {position, byte, mnemonic, immediate} objects
+    Json,
+    // colored listing with jump arrows and unreachable code, for terminals
+    Pretty,
+    // pseudo-Solidity from the decompiler, one function per selector
+    Solidity,
+}
+
+impl fmt::Display for Syntax {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Syntax::Plain => write!(f, "plain"),
+            Syntax::Huff => write!(f, "huff"),
+            Syntax::Json => write!(f, "json"),
+            Syntax::Pretty => write!(f, "pretty"),
+            Syntax::Solidity => write!(f, "solidity"),
+        }
+    }
+}
+
+impl FromStr for Syntax {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "plain" => Ok(Syntax::Plain),
+            "huff" => Ok(Syntax::Huff),
+            "json" => Ok(Syntax::Json),
+            "pretty" => Ok(Syntax::Pretty),
+            "solidity" => Ok(Syntax::Solidity),
+            _ => Err(anyhow!("Unknown syntax: {}", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FormatOptions {
+    pub syntax: Syntax,
+    // raw bytes of each instruction after its position, plain syntax only
+    pub bytes: bool,
+    // number of stack items each instruction pops and pushes
+    pub stack_effect: bool,
+    // ANSI colors, pretty syntax only
+    pub color: bool,
+}
 
 pub fn format(blocks: &'_ [Block]) -> String {
-    let mut result = String::new();
+    format_with_options(
+        blocks,
+        &BTreeMap::new(),
+        None,
+        &BTreeMap::new(),
+        &FormatOptions::default(),
+    )
+}
+
+// `label:` lines before labelled positions, `// comment` lines before commented ones, and
+// selector pushes and LOGn topics named when found in `db`
+pub fn format_with_options(
+    blocks: &'_ [Block],
+    labels: &BTreeMap<usize, String>,
+    db: Option<&SignatureDb>,
+    comments: &BTreeMap<usize, String>,
+    options: &FormatOptions,
+) -> String {
+    let names = match db {
+        Some(db) => signature_names(blocks, db),
+        None => vec![None; blocks.len()],
+    };
+    let lines = Lines {
+        blocks,
+        labels,
+        comments,
+        names,
+        options,
+    };
+    match options.syntax {
+        Syntax::Plain => lines.plain(),
+        Syntax::Huff => lines.huff(),
+        Syntax::Json => lines.json(),
+        Syntax::Pretty => render(blocks, labels, &lines.names, comments, options),
+        Syntax::Solidity => decompile(blocks, db),
+    }
+}
+
+// signature named by each instruction
+fn signature_names<'a>(blocks: &[Block], db: &'a SignatureDb) -> Vec<Option<&'a str>> {
+    // tracks topic0 of LOGn within straight-line code
+    let mut stack = ConstStack::new();
+    let mut names = vec![];
     for b in blocks {
-        let fmted_op = fmt_opcode(&b.opcode);
-        let _ = writeln!(result, "{:08x}: {}", b.position, fmted_op);
+        if b.opcode == OpCode::JUMPDEST {
+            stack.clear();
+        }
+        names.push(annotation(&b.opcode, &stack, db));
+        stack.apply(&b.opcode);
+        if b.opcode.is_terminator() {
+            stack.clear();
+        }
+    }
+    names
+}
+
+fn annotation<'a>(op: &OpCode, stack: &ConstStack, db: &'a SignatureDb) -> Option<&'a str> {
+    match op {
+        OpCode::PUSHN(1..=4, v) => db.function(u32::try_from(v).ok()?),
+        OpCode::PUSHN(32, v) => db.event(v),
+        OpCode::LOGN(n) if *n > 0 => db.event(stack.peek(2)?),
+        _ => None,
+    }
+}
+
+pub(crate) fn stack_effect(op: &OpCode) -> String {
+    let (inputs, outputs) = op.stack_io();
+    format!("pops {}, pushes {}", inputs, outputs)
+}
+
+// immediate bytes of a PUSH
+fn immediate(op: &OpCode) -> Option<Vec<u8>> {
+    match op {
+        OpCode::PUSHN(..) | OpCode::TRUNCATED_PUSHN(..) => Some(op.to_bytes()[1..].to_vec()),
+        _ => None,
+    }
+}
+
+// Huff identifiers are alphanumeric
+fn huff_label(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect::<String>()
+        .trim_matches('_')
+        .to_string()
+}
+
+#[derive(Serialize)]
+struct JsonInstruction<'a> {
+    position: usize,
+    byte: u8,
+    mnemonic: String,
+    immediate: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    label: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    comment: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    signature: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    inputs: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    outputs: Option<usize>,
+}
+
+// what is printed around each instruction
+struct Lines<'a> {
+    blocks: &'a [Block],
+    labels: &'a BTreeMap<usize, String>,
+    comments: &'a BTreeMap<usize, String>,
+    names: Vec<Option<&'a str>>,
+    options: &'a FormatOptions,
+}
+
+impl Lines<'_> {
+    // trailing comment of the instruction at `index`
+    fn notes(&self, index: usize) -> Option<String> {
+        let effect = self
+            .options
+            .stack_effect
+            .then(|| stack_effect(&self.blocks[index].opcode));
+        let notes: Vec<_> = effect
+            .into_iter()
+            .chain(self.names[index].map(String::from))
+            .collect();
+        (!notes.is_empty()).then(|| notes.join(", "))
+    }
+
+    fn plain(&self) -> String {
+        let mut result = String::new();
+        for (i, b) in self.blocks.iter().enumerate() {
+            if let Some(label) = self.labels.get(&b.position) {
+                let _ = writeln!(result, "{}:", label);
+            }
+            if let Some(comment) = self.comments.get(&b.position) {
+                let _ = writeln!(result, "// {}", comment);
+            }
+            let _ = write!(result, "{:08x}: ", b.position);
+            if self.options.bytes {
+                let _ = write!(result, "{:<12}  ", hex::encode(b.opcode.to_bytes()));
+            }
+            result.push_str(&fmt_opcode(&b.opcode));
+            match self.notes(i) {
+                Some(notes) => {
+                    let _ = writeln!(result, "\t// {}", notes);
+                }
+                None => result.push('\n'),
+            }
+        }
+        result
+    }
+
+    fn huff(&self) -> String {
+        // jump destinations are label definitions, each emitting a JUMPDEST
+        let mut names = BTreeMap::new();
+        for b in self.blocks.iter().filter(|b| b.opcode == OpCode::JUMPDEST) {
+            let name = match self.labels.get(&b.position).map(|l| huff_label(l)) {
+                Some(name) if !name.is_empty() && !names.values().any(|n| *n == name) => name,
+                _ => format!("label_{:x}", b.position),
+            };
+            names.insert(b.position, name);
+        }
+
+        let mut result = String::from("#define macro MAIN() = takes(0) returns(0) {\n");
+        for (i, b) in self.blocks.iter().enumerate() {
+            if let Some(comment) = self.comments.get(&b.position) {
+                let _ = writeln!(result, "    // {}", comment);
+            }
+            if let Some(name) = names.get(&b.position) {
+                let _ = writeln!(result, "  {}:", name);
+                continue;
+            }
+            if let Some(label) = self.labels.get(&b.position) {
+                let _ = writeln!(result, "    // {}", label);
+            }
+            let line = match &b.opcode {
+                OpCode::PUSHN(2, v) if usize::try_from(v).is_ok_and(|v| names.contains_key(&v)) => {
+                    names[&usize::try_from(v).unwrap()].clone()
+                }
+                // a literal is pushed with the fewest bytes
+                OpCode::PUSHN(n, v)
+                    if *v != Default::default() && v.bits().div_ceil(8) == *n as u64 =>
+                {
+                    format!("0x{}", hex::encode(immediate(&b.opcode).unwrap()))
+                }
+                OpCode::PUSHN(n, _) => {
+                    format!("push{} 0x{}", n, hex::encode(immediate(&b.opcode).unwrap()))
+                }
+                OpCode::INVALID(0xfe) => "invalid".into(),
+                op @ (OpCode::TRUNCATED_PUSHN(..) | OpCode::INVALID(_)) => {
+                    format!("__VERBATIM(0x{})", hex::encode(op.to_bytes()))
+                }
+                op => mnemonic(op).to_lowercase(),
+            };
+            match self.notes(i) {
+                Some(notes) => {
+                    let _ = writeln!(result, "    {:<24}// {}", line, notes);
+                }
+                None => {
+                    let _ = writeln!(result, "    {}", line);
+                }
+            }
+        }
+        result.push_str("}\n");
+        result
+    }
+
+    fn json(&self) -> String {
+        let instructions: Vec<_> = self
+            .blocks
+            .iter()
+            .enumerate()
+            .map(|(i, b)| {
+                let (inputs, outputs) = b.opcode.stack_io();
+                let effect = self.options.stack_effect;
+                JsonInstruction {
+                    position: b.position,
+                    byte: b.opcode.to_bytes()[0],
+                    mnemonic: mnemonic(&b.opcode),
+                    immediate: immediate(&b.opcode).map(|v| format!("0x{}", hex::encode(v))),
+                    label: self.labels.get(&b.position).map(|s| s.as_str()),
+                    comment: self.comments.get(&b.position).map(|s| s.as_str()),
+                    signature: self.names[i],
+                    inputs: effect.then_some(inputs),
+                    outputs: effect.then_some(outputs),
+                }
+            })
+            .collect();
+        serde_json::to_string_pretty(&instructions).unwrap() + "\n"
+    }
+}
+
+pub fn format_metadata(metadata: &Metadata) -> String {
+    let mut result = String::new();
+    let _ = writeln!(
+        result,
+        "{:08x}: METADATA\t({} bytes)",
+        metadata.position,
+        metadata.raw.len()
+    );
+    if let Some(cid) = metadata.ipfs_cid() {
+        let _ = writeln!(result, "\tipfs: {}", cid);
+    }
+    if let Some(hash) = &metadata.bzzr0 {
+        let _ = writeln!(result, "\tbzzr0: 0x{}", hex::encode(hash));
+    }
+    if let Some(hash) = &metadata.bzzr1 {
+        let _ = writeln!(result, "\tbzzr1: 0x{}", hex::encode(hash));
+    }
+    if let Some(solc) = &metadata.solc {
+        let _ = writeln!(result, "\tsolc: {}", solc);
+    }
+    if metadata.experimental {
+        let _ = writeln!(result, "\texperimental: true");
     }
     result
 }
 
+// name of the instruction without its operand
+pub fn mnemonic(op: &OpCode) -> String {
+    match op {
+        OpCode::PUSHN(n, _) | OpCode::TRUNCATED_PUSHN(n, _) => format!("PUSH{}", n),
+        op => fmt_opcode(op),
+    }
+}
+
 fn fmt_opcode(op: &OpCode) -> String {
     use OpCode::*;
 
@@ -66,6 +388,8 @@ fn fmt_opcode(op: &OpCode) -> String {
         CHAINID => "CHAINID".into(),
         SELFBALANCE => "SELFBALANCE".into(),
         BASEFEE => "BASEFEE".into(),
+        BLOBHASH => "BLOBHASH".into(),
+        BLOBBASEFEE => "BLOBBASEFEE".into(),
         POP => "POP".into(),
         MLOAD => "MLOAD".into(),
         MSTORE => "MSTORE".into(),
@@ -78,9 +402,10 @@ fn fmt_opcode(op: &OpCode) -> String {
         MSIZE => "MSIZE".into(),
         GAS => "GAS".into(),
         JUMPDEST => "JUMPDEST".into(),
-        PUSH => "PUSH".into(),
-        DUP => "DUP".into(),
-        SWAP => "SWAP".into(),
+        TLOAD => "TLOAD".into(),
+        TSTORE => "TSTORE".into(),
+        MCOPY => "MCOPY".into(),
+        PUSH0 => "PUSH0".into(),
         CREATE => "CREATE".into(),
         CALL => "CALL".into(),
         CALLCODE => "CALLCODE".into(),
@@ -92,8 +417,64 @@ fn fmt_opcode(op: &OpCode) -> String {
         SELFDESTRUCT => "SELFDESTRUCT".into(),
         INVALID(op) => format!("INVALID(0x{:x})", op),
         PUSHN(n, v) => format!("PUSH{}\t0x{:x}", n, v),
+        TRUNCATED_PUSHN(n, v) => format!("PUSH{}\t0x{}\t(truncated)", n, hex::encode(v)),
         DUPN(n) => format!("DUP{}", n),
         SWAPN(n) => format!("SWAP{}", n),
         LOGN(n) => format!("LOG{}", n),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::{format_with_options, FormatOptions, Syntax};
+    use crate::{
+        huff::{HuffFile, HuffProject},
+        parser::parse,
+    };
+
+    #[test]
+    fn test_format_options() {
+        // PUSH1 0 CALLDATALOAD PUSH2 0x0008 JUMP STOP JUMPDEST PUSH2 0x0001 STOP
+        let blocks = parse("60003561000856005b61000100").unwrap();
+        let labels = BTreeMap::from([(8, "done".to_string())]);
+        let format =
+            |options| format_with_options(&blocks, &labels, None, &BTreeMap::new(), &options);
+
+        let plain = format(FormatOptions {
+            bytes: true,
+            stack_effect: true,
+            ..Default::default()
+        });
+        assert_eq!(
+            plain.lines().nth(2),
+            Some("00000003: 610008        PUSH2\t0x8\t// pops 0, pushes 1")
+        );
+
+        let huff = format(FormatOptions {
+            syntax: Syntax::Huff,
+            ..Default::default()
+        });
+        assert!(huff.contains("    done\n    jump\n    stop\n  done:\n    push2 0x0001\n"));
+        // readable by the Huff source mapper, instruction for instruction
+        let project = HuffProject::from_sources(vec![HuffFile {
+            path: "out.huff".into(),
+            source: huff,
+        }]);
+        assert_eq!(
+            project.source_map("MAIN", &blocks).locations.len(),
+            blocks.len()
+        );
+
+        let json = format(FormatOptions {
+            syntax: Syntax::Json,
+            ..Default::default()
+        });
+        let json: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(json[2]["position"], 3);
+        assert_eq!(json[2]["byte"], 0x61);
+        assert_eq!(json[2]["immediate"], "0x0008");
+        assert_eq!(json[5]["label"], "done");
+    }
+}
@@ -0,0 +1,423 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    emulator::{Emulator, Halt},
+    opcode::OpCode,
+    parser::parse_bytes_with_spec,
+    spec::SpecId,
+    statetest::{parse_bytes, parse_u64},
+    trace::{JsonStep, JsonSummary, Trace, TraceStep, TraceSummary},
+    util::keccak256,
+    Address,
+};
+
+// geth's `evm run` runs the code at "receiver", called by "sender"
+pub const RECEIVER: Address = *b"\0\0\0\0\0\0\0\0\0\0\0\0receiver";
+pub const SENDER: Address = *b"\0\0\0\0\0\0\0\0\0\0\0\0\0\0sender";
+
+pub const DEFAULT_REFERENCE: &str =
+    "evm --json --code {code} --input {input} --gas {gas} --state.fork {fork} run";
+
+// instructions whose result depends on how the tool sets up the environment
+const EXCLUDED: &[u8] = &[
+    0x30, 0x31, 0x32, 0x33, 0x34, 0x3a, 0x40, 0x41, 0x42, 0x43, 0x44, 0x45, 0x46, 0x47, 0x48, 0x49,
+    0x4a, 0xf0, 0xf5, 0xff,
+];
+
+// splitmix64, so that a run can be replayed from its seed
+#[derive(Debug, Clone)]
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Rng(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+
+    fn bytes(&mut self, n: usize) -> Vec<u8> {
+        (0..n).map(|_| self.next_u64() as u8).collect()
+    }
+}
+
+// code run with `calldata` and `gas` at `RECEIVER`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Case {
+    pub spec: SpecId,
+    pub code: Vec<u8>,
+    pub calldata: Vec<u8>,
+    pub gas: u64,
+}
+
+fn push(code: &mut Vec<u8>, value: &[u8]) {
+    code.push(0x5f + value.len() as u8);
+    code.extend_from_slice(value);
+}
+
+impl Case {
+    // mostly valid code: small operands, jumps to JUMPDESTs and the odd random byte
+    pub fn generate(rng: &mut Rng, spec: SpecId) -> Case {
+        let opcodes: Vec<u8> = (0..=255u8)
+            .filter(|op| !EXCLUDED.contains(op) && !(0x5f..0x80).contains(op))
+            .filter(|op| {
+                !matches!(
+                    parse_bytes_with_spec(&[*op], spec)[0].opcode,
+                    OpCode::INVALID(_)
+                )
+            })
+            .collect();
+        let mut code = vec![];
+        let mut jumps = vec![];
+        let mut destinations = vec![];
+        for _ in 0..rng.below(64) + 1 {
+            match rng.below(16) {
+                0..=5 if rng.below(4) == 0 => {
+                    let size = rng.below(32) + 1;
+                    push(&mut code, &rng.bytes(size));
+                }
+                0..=5 if rng.below(8) == 0 && spec.is_enabled_in(SpecId::Shanghai) => {
+                    push(&mut code, &[])
+                }
+                0..=5 => push(&mut code, &[rng.below(64) as u8]),
+                6 => {
+                    jumps.push(code.len() + 1);
+                    push(&mut code, &[0, 0]);
+                    code.push(if rng.below(2) == 0 { 0x56 } else { 0x57 });
+                }
+                7 => {
+                    destinations.push(code.len());
+                    code.push(0x5b);
+                }
+                8 if rng.below(8) == 0 => code.push(rng.next_u64() as u8),
+                _ => code.push(opcodes[rng.below(opcodes.len())]),
+            }
+        }
+        for jump in jumps {
+            let target = match destinations.len() {
+                n if n > 0 && rng.below(8) != 0 => destinations[rng.below(n)],
+                _ => rng.below(code.len()),
+            };
+            code[jump..jump + 2].copy_from_slice(&(target as u16).to_be_bytes());
+        }
+
+        let calldata = match rng.below(3) {
+            0 => vec![],
+            1 => {
+                let words = rng.below(3);
+                let mut calldata = rng.bytes(4);
+                for _ in 0..words {
+                    calldata.extend([vec![0; 31], rng.bytes(1)].concat());
+                }
+                calldata
+            }
+            _ => {
+                let size = rng.below(100);
+                rng.bytes(size)
+            }
+        };
+        let gas = if rng.below(8) == 0 {
+            rng.below(5000) as u64
+        } else {
+            100_000
+        };
+        Case {
+            spec,
+            code,
+            calldata,
+            gas,
+        }
+    }
+}
+
+// trace of `case` in this emulator
+pub fn run(case: &Case) -> Trace {
+    let parsed = parse_bytes_with_spec(&case.code, case.spec);
+    let mut emu = Emulator::new(case.code.clone(), parsed, &case.calldata)
+        .with_spec(case.spec)
+        .with_gas_limit(case.gas);
+    emu.address = RECEIVER;
+    emu.caller = SENDER;
+    emu.state.set_code(RECEIVER, case.code.clone());
+    emu.state.access_address(RECEIVER);
+    emu.state.access_address(SENDER);
+    emu.state.trace = Some(vec![]);
+    emu.run_to_end();
+    let error = match &emu.halt {
+        Some(Halt::Error(e)) => Some(e.to_string()),
+        Some(Halt::Revert) => Some("execution reverted".into()),
+        _ => None,
+    };
+    Trace {
+        steps: emu.state.trace.take().unwrap_or_default(),
+        summary: Some(TraceSummary {
+            output: emu.return_data.clone(),
+            gas_used: emu.gas_used,
+            error,
+            state_root: Some(emu.state.state_root()),
+        }),
+    }
+}
+
+// fork names of ethereum/tests, which geth takes too
+fn fork_name(spec: SpecId) -> &'static str {
+    match spec {
+        SpecId::Tangerine => "EIP150",
+        SpecId::SpuriousDragon => "EIP158",
+        SpecId::Petersburg => "ConstantinopleFix",
+        spec => spec.name(),
+    }
+}
+
+// an external EVM printing EIP-3155 traces
+#[derive(Debug, Clone)]
+pub struct Reference {
+    // {code}, {input}, {gas} and {fork} in the arguments are replaced by the case
+    pub command: Vec<String>,
+}
+
+impl Reference {
+    pub fn parse(command: &str) -> Result<Reference> {
+        let command: Vec<String> = command.split_whitespace().map(String::from).collect();
+        if command.is_empty() {
+            return Err(anyhow!("Empty reference command"));
+        }
+        Ok(Reference { command })
+    }
+
+    pub fn run(&self, case: &Case) -> Result<Trace> {
+        let args: Vec<String> = self
+            .command
+            .iter()
+            .map(|arg| {
+                arg.replace("{code}", &hex::encode(&case.code))
+                    .replace("{input}", &hex::encode(&case.calldata))
+                    .replace("{gas}", &case.gas.to_string())
+                    .replace("{fork}", fork_name(case.spec))
+            })
+            .collect();
+        let output = Command::new(&args[0])
+            .args(&args[1..])
+            .output()
+            .with_context(|| format!("Failed to run {}", args[0]))?;
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        if !output.status.success() {
+            return Err(anyhow!(
+                "{} failed with {}: {}",
+                args[0],
+                output.status,
+                stderr
+            ));
+        }
+        // geth prints the trace on stderr, other tools on stdout
+        let text = [output.stdout, output.stderr].concat();
+        let trace = Trace::parse(&String::from_utf8_lossy(&text))?;
+        if trace.steps.is_empty() {
+            return Err(anyhow!("No trace steps from {}: {}", args[0], stderr));
+        }
+        Ok(trace)
+    }
+}
+
+fn candidates(case: &Case) -> Vec<Case> {
+    let blocks = parse_bytes_with_spec(&case.code, case.spec);
+    let mut candidates = vec![];
+    let with_code = |code| Case {
+        code,
+        ..case.clone()
+    };
+    // drop whole instructions, later ones first
+    for (i, block) in blocks.iter().enumerate().rev() {
+        let end = blocks.get(i + 1).map_or(case.code.len(), |b| b.position);
+        let code = [&case.code[..block.position], &case.code[end..]].concat();
+        candidates.push(with_code(code));
+    }
+    // zero push data, which keeps jump targets in place
+    for block in &blocks {
+        if let OpCode::PUSHN(n, value) = &block.opcode {
+            if *value != Default::default() {
+                let mut code = case.code.clone();
+                let start = block.position + 1;
+                code[start..start + *n as usize].fill(0);
+                candidates.push(with_code(code));
+            }
+        }
+    }
+    let len = case.calldata.len();
+    for calldata in [vec![], case.calldata[..len / 2].to_vec()] {
+        if calldata.len() < len {
+            candidates.push(Case {
+                calldata,
+                ..case.clone()
+            });
+        }
+    }
+    if len > 0 {
+        candidates.push(Case {
+            calldata: case.calldata[..len - 1].to_vec(),
+            ..case.clone()
+        });
+    }
+    candidates
+}
+
+// smallest case derived from `case` for which `fails` still holds
+pub fn shrink(case: &Case, mut fails: impl FnMut(&Case) -> bool) -> Case {
+    let mut case = case.clone();
+    while let Some(smaller) = candidates(&case).into_iter().find(|c| fails(c)) {
+        case = smaller;
+    }
+    case
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct JsonFixture {
+    fork: String,
+    code: String,
+    calldata: String,
+    gas: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    divergence: Option<String>,
+    steps: Vec<JsonStep>,
+    result: Option<JsonSummary>,
+}
+
+// a case with its expected trace, saved when a divergence is found
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fixture {
+    pub case: Case,
+    pub expected: Trace,
+    // what differed when it was recorded
+    pub divergence: Option<String>,
+}
+
+impl Fixture {
+    pub fn from_json(json: &str) -> Result<Fixture> {
+        let json: JsonFixture = serde_json::from_str(json)?;
+        let steps = json
+            .steps
+            .iter()
+            .map(TraceStep::from_json)
+            .collect::<Result<_>>()?;
+        let summary = json
+            .result
+            .as_ref()
+            .map(TraceSummary::from_json)
+            .transpose()?;
+        Ok(Fixture {
+            case: Case {
+                spec: json.fork.parse()?,
+                code: parse_bytes(&json.code)?,
+                calldata: parse_bytes(&json.calldata)?,
+                gas: parse_u64(&json.gas)?,
+            },
+            expected: Trace { steps, summary },
+            divergence: json.divergence,
+        })
+    }
+
+    pub fn to_json(&self) -> String {
+        let json = JsonFixture {
+            fork: self.case.spec.name().into(),
+            code: format!("0x{}", hex::encode(&self.case.code)),
+            calldata: format!("0x{}", hex::encode(&self.case.calldata)),
+            gas: format!("0x{:x}", self.case.gas),
+            divergence: self.divergence.clone(),
+            steps: self.expected.steps.iter().map(|s| s.to_json()).collect(),
+            result: self.expected.summary.as_ref().map(|s| s.to_json()),
+        };
+        serde_json::to_string_pretty(&json).unwrap()
+    }
+
+    pub fn load(path: &Path) -> Result<Fixture> {
+        let json = fs::read_to_string(path)?;
+        Fixture::from_json(&json).with_context(|| format!("Invalid fixture {}", path.display()))
+    }
+
+    // named after the case, so that the same divergence is saved once
+    pub fn save(&self, dir: &Path) -> Result<PathBuf> {
+        let id = keccak256(&[&self.case.code[..], &self.case.calldata].concat());
+        let path = dir.join(format!("{}.json", hex::encode(&id[..8])));
+        fs::create_dir_all(dir)?;
+        fs::write(&path, self.to_json())?;
+        Ok(path)
+    }
+
+    // difference between this emulator and the recorded trace
+    pub fn check(&self) -> Option<String> {
+        run(&self.case).divergence(&self.expected)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{run, shrink, Case, Fixture, Reference, Rng};
+    use crate::spec::SpecId;
+
+    #[test]
+    fn test_generate() {
+        let mut rng = Rng::new(1);
+        for spec in [SpecId::Frontier, SpecId::Cancun] {
+            for _ in 0..50 {
+                let case = Case::generate(&mut rng, spec);
+                let trace = run(&case);
+                assert!(!trace.steps.is_empty());
+                let fixture = Fixture {
+                    case,
+                    expected: trace,
+                    divergence: None,
+                };
+                assert_eq!(Fixture::from_json(&fixture.to_json()).unwrap(), fixture);
+                assert_eq!(fixture.check(), None);
+            }
+        }
+    }
+
+    #[test]
+    fn test_shrink() {
+        // PUSH1 1, PUSH1 2, ADD, PUSH1 0, MSTORE, STOP
+        let case = Case {
+            spec: SpecId::Cancun,
+            code: vec![0x60, 0x01, 0x60, 0x02, 0x01, 0x60, 0x00, 0x52, 0x00],
+            calldata: vec![1, 2, 3],
+            gas: 100_000,
+        };
+        let shrunk = shrink(&case, |c| run(c).steps.iter().any(|s| s.op == 0x01));
+        assert_eq!(shrunk.code, [0x01]);
+        assert!(shrunk.calldata.is_empty());
+    }
+
+    #[test]
+    fn test_reference_failure() {
+        let case = Case {
+            spec: SpecId::Cancun,
+            code: vec![0x00],
+            calldata: vec![],
+            gas: 100_000,
+        };
+        // fails, naming the missing file on stderr
+        let error = Reference::parse("ls /nonexistent/{fork}")
+            .unwrap()
+            .run(&case)
+            .unwrap_err();
+        assert!(error.to_string().contains("Cancun"));
+        // exits fine without printing a trace
+        assert!(Reference::parse("true").unwrap().run(&case).is_err());
+    }
+}
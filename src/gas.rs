@@ -0,0 +1,314 @@
+use num_traits::Zero;
+
+use crate::{opcode::OpCode, spec::SpecId, util::max_uint256, Uint256};
+
+pub const ZERO: u64 = 0;
+pub const BASE: u64 = 2;
+pub const VERYLOW: u64 = 3;
+pub const LOW: u64 = 5;
+pub const MID: u64 = 8;
+pub const HIGH: u64 = 10;
+pub const JUMPDEST_BASE: u64 = 1;
+pub const SHA3_BASE: u64 = 30;
+pub const SHA3_WORD: u64 = 6;
+pub const COPY_WORD: u64 = 3;
+pub const MEMORY_WORD: u64 = 3;
+pub const LOG_BASE: u64 = 375;
+pub const LOG_TOPIC: u64 = 375;
+pub const LOG_DATA: u64 = 8;
+pub const CREATE_BASE: u64 = 32000;
+pub const BLOCKHASH_BASE: u64 = 20;
+pub const CALL_VALUE: u64 = 9000;
+pub const CALL_STIPEND: u64 = 2300;
+pub const NEW_ACCOUNT: u64 = 25000;
+pub const CODE_DEPOSIT: u64 = 200;
+pub const SELFDESTRUCT_REFUND: i64 = 24000;
+pub const SSTORE_SET: u64 = 20000;
+pub const SSTORE_RESET: u64 = 5000;
+// EIP-2200, SSTORE fails when no more than this is left
+pub const SSTORE_SENTRY: u64 = 2300;
+
+// transactions
+pub const TX_BASE: u64 = 21000;
+pub const TX_CREATE: u64 = 32000;
+pub const TX_DATA_ZERO: u64 = 4;
+pub const TX_ACCESS_LIST_ADDRESS: u64 = 2400;
+pub const TX_ACCESS_LIST_STORAGE_KEY: u64 = 1900;
+// EIP-7623
+pub const TX_FLOOR_PER_TOKEN: u64 = 10;
+
+// EIP-170 and EIP-3860
+pub const MAX_CODE_SIZE: usize = 24576;
+pub const MAX_INITCODE_SIZE: usize = 2 * MAX_CODE_SIZE;
+pub const INITCODE_WORD: u64 = 2;
+
+// EIP-4844
+pub const GAS_PER_BLOB: u64 = 131072;
+
+// EIP-2929
+pub const WARM_STORAGE_READ: u64 = 100;
+pub const COLD_SLOAD: u64 = 2100;
+pub const COLD_ACCOUNT_ACCESS: u64 = 2600;
+
+// cost charged before execution; dynamic parts are charged by the emulator
+pub fn static_cost(op: &OpCode, spec: SpecId) -> u64 {
+    use OpCode::*;
+
+    match op {
+        STOP | RETURN | REVERT | INVALID(_) => ZERO,
+        ADDRESS | ORIGIN | CALLER | CALLVALUE | CALLDATASIZE | CODESIZE | GASPRICE | COINBASE
+        | TIMESTAMP | NUMBER | DIFFICULTY | GASLIMIT | RETURNDATASIZE | POP | PC | MSIZE | GAS
+        | CHAINID | BASEFEE | BLOBBASEFEE | PUSH0 => BASE,
+        ADD
+        | SUB
+        | NOT
+        | LT
+        | GT
+        | SLT
+        | SGT
+        | EQ
+        | ISZERO
+        | AND
+        | OR
+        | XOR
+        | BYTE
+        | SHL
+        | SHR
+        | SAR
+        | CALLDATALOAD
+        | MLOAD
+        | MSTORE
+        | MSTORE8
+        | PUSHN(_, _)
+        | TRUNCATED_PUSHN(_, _)
+        | DUPN(_)
+        | SWAPN(_)
+        | CALLDATACOPY
+        | CODECOPY
+        | RETURNDATACOPY
+        | MCOPY
+        | BLOBHASH => VERYLOW,
+        MUL | DIV | SDIV | MOD | SMOD | SIGNEXTEND | SELFBALANCE => LOW,
+        ADDMOD | MULMOD | JUMP => MID,
+        JUMPI | EXP => HIGH,
+        JUMPDEST => JUMPDEST_BASE,
+        SHA3 => SHA3_BASE,
+        BLOCKHASH => BLOCKHASH_BASE,
+        LOGN(n) => LOG_BASE + LOG_TOPIC * *n as u64,
+        CREATE | CREATE2 => CREATE_BASE,
+        BALANCE => match spec {
+            s if s.is_enabled_in(SpecId::Berlin) => WARM_STORAGE_READ,
+            s if s.is_enabled_in(SpecId::Istanbul) => 700,
+            s if s.is_enabled_in(SpecId::Tangerine) => 400,
+            _ => 20,
+        },
+        EXTCODESIZE | EXTCODECOPY => match spec {
+            s if s.is_enabled_in(SpecId::Berlin) => WARM_STORAGE_READ,
+            s if s.is_enabled_in(SpecId::Tangerine) => 700,
+            _ => 20,
+        },
+        EXTCODEHASH => match spec {
+            s if s.is_enabled_in(SpecId::Berlin) => WARM_STORAGE_READ,
+            s if s.is_enabled_in(SpecId::Istanbul) => 700,
+            _ => 400,
+        },
+        TLOAD | TSTORE => WARM_STORAGE_READ,
+        SLOAD => match spec {
+            s if s.is_enabled_in(SpecId::Berlin) => WARM_STORAGE_READ,
+            s if s.is_enabled_in(SpecId::Istanbul) => 800,
+            s if s.is_enabled_in(SpecId::Tangerine) => 200,
+            _ => 50,
+        },
+        // charged entirely by the SSTORE rules of the active fork
+        SSTORE => ZERO,
+        CALL | CALLCODE | DELEGATECALL | STATICCALL => match spec {
+            s if s.is_enabled_in(SpecId::Berlin) => WARM_STORAGE_READ,
+            s if s.is_enabled_in(SpecId::Tangerine) => 700,
+            _ => 40,
+        },
+        SELFDESTRUCT => match spec {
+            s if s.is_enabled_in(SpecId::Tangerine) => 5000,
+            _ => ZERO,
+        },
+    }
+}
+
+// EIP-160 raised the per-byte exponent cost
+pub fn exp_byte_cost(spec: SpecId) -> u64 {
+    if spec.is_enabled_in(SpecId::SpuriousDragon) {
+        50
+    } else {
+        10
+    }
+}
+
+pub fn to_words(size: usize) -> u64 {
+    (size as u64).div_ceil(32)
+}
+
+// total cost of a memory of `size` bytes
+pub fn memory_cost(size: usize) -> u64 {
+    let words = to_words(size);
+    MEMORY_WORD * words + words * words / 512
+}
+
+// EIP-4844 blob base fee from the parent's excess blob gas
+pub fn blob_base_fee(excess_blob_gas: u64, spec: SpecId) -> Uint256 {
+    let update_fraction = if spec.is_enabled_in(SpecId::Prague) {
+        5_007_716u32
+    } else {
+        3_338_477
+    };
+    fake_exponential(1u32.into(), excess_blob_gas.into(), update_fraction.into())
+}
+
+// saturates at the largest word, the series stops once the result can't fit in one
+fn fake_exponential(factor: Uint256, numerator: Uint256, denominator: Uint256) -> Uint256 {
+    let limit = max_uint256() * &denominator;
+    let mut i = 1u32;
+    let mut output = Uint256::zero();
+    let mut accum = factor * &denominator;
+    while !accum.is_zero() {
+        output += &accum;
+        if output > limit {
+            return max_uint256();
+        }
+        accum = accum * &numerator / (&denominator * i);
+        i += 1;
+    }
+    output / denominator
+}
+
+pub fn copy_cost(size: usize) -> u64 {
+    COPY_WORD * to_words(size)
+}
+
+// EIP-150 keeps back a 64th of the remaining gas in the caller
+pub fn call_gas(requested: u64, available: u64, spec: SpecId) -> u64 {
+    if spec.is_enabled_in(SpecId::Tangerine) {
+        requested.min(available - available / 64)
+    } else {
+        requested
+    }
+}
+
+// EIP-3529 lowered the refund for clearing a slot
+pub fn sstore_clears_refund(spec: SpecId) -> i64 {
+    if spec.is_enabled_in(SpecId::London) {
+        (SSTORE_RESET - COLD_SLOAD + TX_ACCESS_LIST_STORAGE_KEY) as i64
+    } else {
+        15000
+    }
+}
+
+// cost and refund change of an SSTORE, without the EIP-2929 cold surcharge
+pub fn sstore(original: &Uint256, current: &Uint256, new: &Uint256, spec: SpecId) -> (u64, i64) {
+    let clears = sstore_clears_refund(spec);
+    // Constantinople's EIP-1283 was reverted by Petersburg, treated as such here
+    if !spec.is_enabled_in(SpecId::Istanbul) {
+        return match (current.is_zero(), new.is_zero()) {
+            (true, false) => (SSTORE_SET, 0),
+            (false, true) => (SSTORE_RESET, clears),
+            _ => (SSTORE_RESET, 0),
+        };
+    }
+    let (sload, reset) = if spec.is_enabled_in(SpecId::Berlin) {
+        (WARM_STORAGE_READ, SSTORE_RESET - COLD_SLOAD)
+    } else {
+        (800, SSTORE_RESET)
+    };
+    if current == new {
+        return (sload, 0);
+    }
+    if original == current {
+        let refund = if !original.is_zero() && new.is_zero() {
+            clears
+        } else {
+            0
+        };
+        let cost = if original.is_zero() {
+            SSTORE_SET
+        } else {
+            reset
+        };
+        return (cost, refund);
+    }
+    let mut refund = 0;
+    if !original.is_zero() {
+        if current.is_zero() {
+            refund -= clears;
+        } else if new.is_zero() {
+            refund += clears;
+        }
+    }
+    if original == new {
+        refund += if original.is_zero() {
+            (SSTORE_SET - sload) as i64
+        } else {
+            (reset - sload) as i64
+        };
+    }
+    (sload, refund)
+}
+
+pub fn tx_data_nonzero(spec: SpecId) -> u64 {
+    if spec.is_enabled_in(SpecId::Istanbul) {
+        16
+    } else {
+        68
+    }
+}
+
+// gas charged before a transaction executes
+pub fn intrinsic_gas(
+    data: &[u8],
+    is_create: bool,
+    access_list_addresses: usize,
+    access_list_keys: usize,
+    spec: SpecId,
+) -> u64 {
+    let zeros = data.iter().filter(|b| **b == 0).count() as u64;
+    let nonzeros = data.len() as u64 - zeros;
+    let mut gas = TX_BASE + zeros * TX_DATA_ZERO + nonzeros * tx_data_nonzero(spec);
+    if is_create && spec.is_enabled_in(SpecId::Homestead) {
+        gas += TX_CREATE;
+    }
+    if is_create && spec.is_enabled_in(SpecId::Shanghai) {
+        gas += INITCODE_WORD * to_words(data.len());
+    }
+    gas + access_list_addresses as u64 * TX_ACCESS_LIST_ADDRESS
+        + access_list_keys as u64 * TX_ACCESS_LIST_STORAGE_KEY
+}
+
+// EIP-7623 minimum gas used by a transaction, 0 before Prague
+pub fn calldata_floor(data: &[u8], spec: SpecId) -> u64 {
+    if !spec.is_enabled_in(SpecId::Prague) {
+        return 0;
+    }
+    let zeros = data.iter().filter(|b| **b == 0).count() as u64;
+    let tokens = zeros + (data.len() as u64 - zeros) * 4;
+    TX_BASE + tokens * TX_FLOOR_PER_TOKEN
+}
+
+// EIP-3529 lowered the refund cap from a half to a fifth of the gas used
+pub fn max_refund(gas_used: u64, spec: SpecId) -> u64 {
+    if spec.is_enabled_in(SpecId::London) {
+        gas_used / 5
+    } else {
+        gas_used / 2
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::blob_base_fee;
+    use crate::{spec::SpecId, util::max_uint256};
+
+    #[test]
+    fn test_blob_base_fee() {
+        assert_eq!(blob_base_fee(0, SpecId::Cancun), 1u32.into());
+        // e^(10_000_000 / 3_338_477) ~ 19.99
+        assert_eq!(blob_base_fee(10_000_000, SpecId::Cancun), 19u32.into());
+        assert_eq!(blob_base_fee(u64::MAX, SpecId::Cancun), max_uint256());
+    }
+}
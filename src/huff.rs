@@ -0,0 +1,561 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::Path,
+};
+
+use anyhow::{anyhow, bail, Result};
+use serde::Deserialize;
+
+use crate::{block::Block, formatter::mnemonic, opcode::OpCode, parser::decode_hex};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum TokenKind {
+    Ident(String),
+    // hex or decimal
+    Literal(String),
+    Str(String),
+    Punct(char),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Token {
+    kind: TokenKind,
+    file: usize,
+    line: usize,
+}
+
+#[derive(Debug, Clone)]
+struct MacroDef {
+    params: Vec<String>,
+    body: Vec<Token>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HuffFile {
+    pub path: String,
+    pub source: String,
+}
+
+// the sources of a Huff contract and the macros defined in them
+#[derive(Debug, Clone, Default)]
+pub struct HuffProject {
+    pub files: Vec<HuffFile>,
+    macros: HashMap<String, MacroDef>,
+    // code of a compiler artifact, if loaded from one
+    pub bytecode: Vec<u8>,
+    pub runtime: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HuffLocation {
+    pub file: usize,
+    // 1-based
+    pub line: usize,
+    // invoked macros, outermost first
+    pub macros: Vec<String>,
+}
+
+// source location of each instruction, keyed by position
+#[derive(Debug, Clone, Default)]
+pub struct HuffSourceMap {
+    pub locations: BTreeMap<usize, HuffLocation>,
+    pub labels: BTreeMap<usize, String>,
+}
+
+fn lex(source: &str, file: usize) -> Vec<Token> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = vec![];
+    let mut line = 1;
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c == '\n' {
+            line += 1;
+            i += 1;
+        } else if c.is_whitespace() {
+            i += 1;
+        } else if c == '/' && chars.get(i + 1) == Some(&'/') {
+            while i < chars.len() && chars[i] != '\n' {
+                i += 1;
+            }
+        } else if c == '/' && chars.get(i + 1) == Some(&'*') {
+            i += 2;
+            while i < chars.len() && !(chars[i] == '*' && chars.get(i + 1) == Some(&'/')) {
+                if chars[i] == '\n' {
+                    line += 1;
+                }
+                i += 1;
+            }
+            i += 2;
+        } else if c == '"' || c == '\'' {
+            let start = i + 1;
+            i = start;
+            while i < chars.len() && chars[i] != c {
+                i += 1;
+            }
+            let text = chars[start..i.min(chars.len())].iter().collect();
+            tokens.push(Token {
+                kind: TokenKind::Str(text),
+                file,
+                line,
+            });
+            i += 1;
+        } else if c.is_alphanumeric() || c == '_' || c == '#' {
+            let start = i;
+            i += 1;
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            let text: String = chars[start..i].iter().collect();
+            let kind = if c.is_ascii_digit() {
+                TokenKind::Literal(text)
+            } else {
+                TokenKind::Ident(text)
+            };
+            tokens.push(Token { kind, file, line });
+        } else {
+            tokens.push(Token {
+                kind: TokenKind::Punct(c),
+                file,
+                line,
+            });
+            i += 1;
+        }
+    }
+    tokens
+}
+
+fn is_punct(token: Option<&Token>, c: char) -> bool {
+    matches!(token, Some(Token { kind: TokenKind::Punct(p), .. }) if *p == c)
+}
+
+fn ident(token: Option<&Token>) -> Option<&str> {
+    match token {
+        Some(Token {
+            kind: TokenKind::Ident(s),
+            ..
+        }) => Some(s),
+        _ => None,
+    }
+}
+
+// index just past the bracket closing the one at `start`
+fn skip_group(tokens: &[Token], start: usize, open: char, close: char) -> usize {
+    let mut depth = 0;
+    for (i, token) in tokens.iter().enumerate().skip(start) {
+        if is_punct(Some(token), open) {
+            depth += 1;
+        } else if is_punct(Some(token), close) {
+            depth -= 1;
+            if depth == 0 {
+                return i + 1;
+            }
+        }
+    }
+    tokens.len()
+}
+
+// split the tokens inside `(..)` at top level commas
+fn split_args(tokens: &[Token]) -> Vec<Vec<Token>> {
+    let mut args = vec![vec![]];
+    let mut depth = 0;
+    for token in tokens {
+        match token.kind {
+            TokenKind::Punct('(') | TokenKind::Punct('[') | TokenKind::Punct('<') => depth += 1,
+            TokenKind::Punct(')') | TokenKind::Punct(']') | TokenKind::Punct('>') => depth -= 1,
+            TokenKind::Punct(',') if depth == 0 => {
+                args.push(vec![]);
+                continue;
+            }
+            _ => {}
+        }
+        args.last_mut().unwrap().push(token.clone());
+    }
+    args.retain(|a| !a.is_empty());
+    args
+}
+
+#[derive(Debug, Deserialize)]
+struct JsonFile {
+    #[serde(default)]
+    path: String,
+    #[serde(default)]
+    source: Option<String>,
+    #[serde(default)]
+    dependencies: Option<Vec<JsonFile>>,
+}
+
+// output of `huffc -a`
+#[derive(Debug, Deserialize)]
+struct JsonArtifact {
+    file: JsonFile,
+    #[serde(default)]
+    bytecode: String,
+    #[serde(default)]
+    runtime: String,
+}
+
+impl HuffProject {
+    pub fn from_sources(files: Vec<HuffFile>) -> Self {
+        let mut project = HuffProject {
+            files,
+            ..Default::default()
+        };
+        for i in 0..project.files.len() {
+            let tokens = lex(&project.files[i].source, i);
+            project.collect_macros(&tokens);
+        }
+        project
+    }
+
+    // a `.huff` file and its includes, or a compiler artifact
+    pub fn load(path: &Path) -> Result<Self> {
+        if path.extension().is_some_and(|e| e == "json") {
+            return Self::from_artifact(&fs::read_to_string(path)?);
+        }
+        let mut files = vec![];
+        load_with_includes(path, &mut files)?;
+        Ok(Self::from_sources(files))
+    }
+
+    pub fn from_artifact(json: &str) -> Result<Self> {
+        let artifact: JsonArtifact = serde_json::from_str(json)?;
+        let mut files = vec![];
+        let mut pending = vec![artifact.file];
+        while let Some(file) = pending.pop() {
+            if let Some(source) = file.source {
+                if !files.iter().any(|f: &HuffFile| f.path == file.path) {
+                    files.push(HuffFile {
+                        path: file.path,
+                        source,
+                    });
+                }
+            }
+            pending.extend(file.dependencies.unwrap_or_default());
+        }
+        if files.is_empty() {
+            bail!("Artifact has no sources");
+        }
+        Ok(HuffProject {
+            bytecode: decode_hex(&artifact.bytecode)?,
+            runtime: decode_hex(&artifact.runtime)?,
+            ..Self::from_sources(files)
+        })
+    }
+
+    // `#define macro NAME(params) = takes(n) returns(m) { body }`, `fn` alike
+    fn collect_macros(&mut self, tokens: &[Token]) {
+        let mut i = 0;
+        while i < tokens.len() {
+            if ident(tokens.get(i)) != Some("#define")
+                || !matches!(ident(tokens.get(i + 1)), Some("macro") | Some("fn"))
+            {
+                i += 1;
+                continue;
+            }
+            let Some(name) = ident(tokens.get(i + 2)) else {
+                i += 1;
+                continue;
+            };
+            let params_end = skip_group(tokens, i + 3, '(', ')');
+            let params = tokens[(i + 4).min(params_end)..params_end.saturating_sub(1)]
+                .iter()
+                .filter_map(|t| ident(Some(t)).map(|s| s.to_string()))
+                .collect();
+            let Some(open) = (params_end..tokens.len()).find(|j| is_punct(tokens.get(*j), '{'))
+            else {
+                break;
+            };
+            let close = skip_group(tokens, open, '{', '}');
+            self.macros.insert(
+                name.to_string(),
+                MacroDef {
+                    params,
+                    body: tokens[open + 1..close.saturating_sub(1)].to_vec(),
+                },
+            );
+            i = close;
+        }
+    }
+
+    pub fn source_line(&self, location: &HuffLocation) -> Option<&str> {
+        self.files
+            .get(location.file)?
+            .source
+            .lines()
+            .nth(location.line.checked_sub(1)?)
+            .map(|l| l.trim())
+    }
+
+    // `MAIN > ADD main.huff:3`
+    pub fn describe(&self, location: &HuffLocation) -> String {
+        let path = self.files.get(location.file).map_or("?", |f| &f.path);
+        format!("{} {}:{}", location.macros.join(" > "), path, location.line)
+    }
+
+    // align the instructions of `entry` (usually MAIN or CONSTRUCTOR) with `blocks`,
+    // stopping at the first instruction that does not match the source
+    pub fn source_map(&self, entry: &str, blocks: &[Block]) -> HuffSourceMap {
+        let mut walker = Walker {
+            project: self,
+            blocks,
+            index: 0,
+            map: HuffSourceMap::default(),
+        };
+        if let Some(def) = self.macros.get(entry) {
+            let _ = walker.walk(&def.body, &mut vec![entry.to_string()], &HashMap::new());
+        }
+        walker.map
+    }
+}
+
+fn load_with_includes(path: &Path, files: &mut Vec<HuffFile>) -> Result<()> {
+    let display = path.display().to_string();
+    if files.iter().any(|f| f.path == display) {
+        return Ok(());
+    }
+    let source = fs::read_to_string(path)?;
+    let tokens = lex(&source, files.len());
+    files.push(HuffFile {
+        path: display,
+        source,
+    });
+    for pair in tokens.windows(2) {
+        if let (Some("#include"), TokenKind::Str(include)) = (ident(Some(&pair[0])), &pair[1].kind)
+        {
+            let dir = path.parent().ok_or(anyhow!("Invalid path"))?;
+            load_with_includes(&dir.join(include), files)?;
+        }
+    }
+    Ok(())
+}
+
+// macro arguments, with the macro stack they were written in
+type Args = HashMap<String, (Vec<Token>, Vec<String>)>;
+
+struct Walker<'a> {
+    project: &'a HuffProject,
+    blocks: &'a [Block],
+    index: usize,
+    map: HuffSourceMap,
+}
+
+// Huff and the formatter disagree on a couple of names
+fn normalize(name: &str) -> String {
+    match name.to_lowercase().as_str() {
+        "keccak256" | "sha3" => "sha3".into(),
+        "prevrandao" | "difficulty" => "difficulty".into(),
+        name => name.into(),
+    }
+}
+
+impl Walker<'_> {
+    // consume the next instruction if `expected` accepts it
+    fn expect(
+        &mut self,
+        token: &Token,
+        macros: &[String],
+        expected: impl Fn(&OpCode) -> bool,
+    ) -> Option<()> {
+        let block = self.blocks.get(self.index)?;
+        if !expected(&block.opcode) {
+            return None;
+        }
+        self.map.locations.insert(
+            block.position,
+            HuffLocation {
+                file: token.file,
+                line: token.line,
+                macros: macros.to_vec(),
+            },
+        );
+        self.index += 1;
+        Some(())
+    }
+
+    fn expect_push(&mut self, token: &Token, macros: &[String]) -> Option<()> {
+        self.expect(token, macros, |op| op.push_value().is_some())
+    }
+
+    fn walk(&mut self, tokens: &[Token], macros: &mut Vec<String>, args: &Args) -> Option<()> {
+        let mut i = 0;
+        while i < tokens.len() {
+            let token = &tokens[i];
+            match &token.kind {
+                TokenKind::Literal(_) => self.expect_push(token, macros)?,
+                // constant
+                TokenKind::Punct('[') => {
+                    self.expect_push(token, macros)?;
+                    i = skip_group(tokens, i, '[', ']');
+                    continue;
+                }
+                TokenKind::Punct('<') => {
+                    let name = ident(tokens.get(i + 1))?;
+                    let (arg, arg_macros) = args.get(name)?;
+                    self.walk(arg, &mut arg_macros.clone(), &HashMap::new())?;
+                    i += 3;
+                    continue;
+                }
+                TokenKind::Ident(name) if is_punct(tokens.get(i + 1), ':') => {
+                    let position = self.blocks.get(self.index)?.position;
+                    self.expect(token, macros, |op| *op == OpCode::JUMPDEST)?;
+                    self.map.labels.insert(position, name.clone());
+                    i += 2;
+                    continue;
+                }
+                TokenKind::Ident(name) if is_punct(tokens.get(i + 1), '(') => {
+                    let end = skip_group(tokens, i + 1, '(', ')');
+                    let inner = &tokens[i + 2..end.saturating_sub(1).max(i + 2)];
+                    match self.project.macros.get(name) {
+                        // huffc rejects recursive macros, they would never end here
+                        Some(_) if macros.contains(name) => return None,
+                        Some(def) => {
+                            let mut call_args = Args::new();
+                            for (param, arg) in def.params.iter().zip(split_args(inner)) {
+                                // arguments forwarded with `<x>` keep their origin
+                                let resolved = match arg.as_slice() {
+                                    [open, name, close]
+                                        if is_punct(Some(open), '<')
+                                            && is_punct(Some(close), '>') =>
+                                    {
+                                        args.get(ident(Some(name))?)?.clone()
+                                    }
+                                    _ => (arg, macros.clone()),
+                                };
+                                call_args.insert(param.clone(), resolved);
+                            }
+                            macros.push(name.clone());
+                            let result = self.walk(&def.body, macros, &call_args);
+                            macros.pop();
+                            result?;
+                        }
+                        // builtins such as __FUNC_SIG and FREE_STORAGE_POINTER push a value
+                        None => self.expect_push(token, macros)?,
+                    }
+                    i = end;
+                    continue;
+                }
+                TokenKind::Ident(name) => {
+                    let name = normalize(name);
+                    let is_opcode = |op: &OpCode| normalize(&mnemonic(op)) == name;
+                    let opcode = self.blocks.get(self.index).map(|b| is_opcode(&b.opcode));
+                    if opcode == Some(true) {
+                        self.expect(token, macros, is_opcode)?;
+                        // the value of an explicit `push2 0x0001`
+                        if name.starts_with("push")
+                            && matches!(
+                                tokens.get(i + 1).map(|t| &t.kind),
+                                Some(TokenKind::Literal(_))
+                            )
+                        {
+                            i += 1;
+                        }
+                    } else {
+                        // a label reference
+                        self.expect_push(token, macros)?;
+                    }
+                }
+                _ => {}
+            }
+            i += 1;
+        }
+        Some(())
+    }
+}
+
+impl HuffSourceMap {
+    pub fn location(&self, position: usize) -> Option<&HuffLocation> {
+        self.locations.get(&position)
+    }
+
+    // one comment per source line, for `formatter::format_with_options`
+    pub fn comments(&self, project: &HuffProject) -> BTreeMap<usize, String> {
+        let mut comments = BTreeMap::new();
+        let mut previous = None;
+        for (position, location) in &self.locations {
+            if previous == Some(location) {
+                continue;
+            }
+            let line = project.source_line(location).unwrap_or_default();
+            comments.insert(
+                *position,
+                format!("{}\t{}", project.describe(location), line),
+            );
+            previous = Some(location);
+        }
+        comments
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{HuffFile, HuffProject};
+    use crate::parser::parse;
+
+    const SOURCE: &str = r#"
+/* adds two numbers */
+#define constant OFFSET = 0x20
+
+#define macro LOAD(offset) = takes(0) returns(1) {
+    <offset> calldataload
+}
+
+#define macro MAIN() = takes(0) returns(0) {
+    LOAD(0x00)
+    LOAD([OFFSET])      // second number
+    add
+    done jump
+    done:
+        0x00 mstore
+        0x20 0x00 return
+}
+"#;
+
+    #[test]
+    fn test_source_map() {
+        let project = HuffProject::from_sources(vec![HuffFile {
+            path: "add.huff".into(),
+            source: SOURCE.into(),
+        }]);
+        // PUSH1 0 CALLDATALOAD PUSH1 0x20 CALLDATALOAD ADD PUSH2 0x000b JUMP
+        // JUMPDEST PUSH1 0 MSTORE PUSH1 0x20 PUSH1 0 RETURN
+        let parsed = parse("6000356020350161000b565b60005260206000f3").unwrap();
+        let map = project.source_map("MAIN", &parsed);
+        assert_eq!(map.locations.len(), parsed.len());
+
+        let load = map.location(2).unwrap();
+        assert_eq!(load.line, 6);
+        assert_eq!(load.macros, vec!["MAIN", "LOAD"]);
+        // the argument is attributed to the caller
+        let offset = map.location(3).unwrap();
+        assert_eq!(offset.line, 11);
+        assert_eq!(offset.macros, vec!["MAIN"]);
+        assert_eq!(
+            project.source_line(offset),
+            Some("LOAD([OFFSET])      // second number")
+        );
+
+        assert_eq!(map.labels.get(&0x0b).map(|s| s.as_str()), Some("done"));
+        assert_eq!(map.location(0x0c).unwrap().line, 15);
+    }
+
+    #[test]
+    fn test_recursive_macro() {
+        let source = "#define macro A() = takes(0) returns(0) { B() }\n\
+                      #define macro B() = takes(0) returns(0) { A() }\n\
+                      #define macro MAIN() = takes(0) returns(0) { A() }";
+        let project = HuffProject::from_sources(vec![HuffFile {
+            path: "loop.huff".into(),
+            source: source.into(),
+        }]);
+        let parsed = parse("00").unwrap();
+        assert!(project.source_map("MAIN", &parsed).locations.is_empty());
+        let source = "#define macro A() = takes(0) returns(0) { A() }";
+        let project = HuffProject::from_sources(vec![HuffFile {
+            path: "self.huff".into(),
+            source: source.into(),
+        }]);
+        assert!(project.source_map("A", &parsed).locations.is_empty());
+    }
+}
@@ -1,10 +1,36 @@
 use num_bigint::BigUint;
 
 pub type Uint256 = BigUint;
+pub type Address = [u8; 20];
 
+pub mod abi;
+pub mod analysis;
+pub mod assembler;
 pub mod block;
+pub mod cheatcodes;
+pub mod decompiler;
+pub mod detectors;
 pub mod emulator;
+pub mod env;
+pub mod expr;
 pub mod formatter;
+pub mod fuzz;
+pub mod gas;
+pub mod huff;
+pub mod metadata;
 pub mod opcode;
 pub mod parser;
+pub mod precompiles;
+pub mod render;
+pub mod rlp;
+pub mod signatures;
+pub mod smt;
+pub mod sourcemap;
+pub mod spec;
+pub mod state;
+pub mod statetest;
+pub mod symbolic;
+pub mod trace;
+pub mod transaction;
+pub mod trie;
 pub mod util;
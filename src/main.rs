@@ -1,39 +1,633 @@
-use anyhow::Result;
+use anyhow::{anyhow, Result};
+use clap::{Parser, ValueEnum};
 
-use std::{env, fs, path::Path};
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::IsTerminal,
+    path::{Path, PathBuf},
+};
 
-use evm_utils::{emulator::Emulator, formatter, parser};
+use evm_utils::{
+    abi::{self, json::Artifact},
+    analysis::{deploy::find_deployment, dispatcher::find_dispatcher},
+    block::Block,
+    detectors,
+    emulator::{Emulator, Halt},
+    formatter::{self, FormatOptions, Syntax},
+    huff::HuffProject,
+    parser,
+    signatures::SignatureDb,
+    smt::{self, Goal, Solver},
+    sourcemap::{CallStack, SourceFiles, SourceMap},
+    spec::SpecId,
+    state::Log,
+    symbolic::{self, Limits},
+};
 
-// TODO: add CLI
-fn main() -> Result<()> {
-    let args: Vec<String> = env::args().collect();
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Part {
+    /// The whole bytecode
+    All,
+    /// Constructor code in front of the embedded runtime
+    Init,
+    /// Runtime code deployed by the constructor
+    Runtime,
+}
 
-    let bytecode = if !args[1].is_empty() && Path::new(&args[1]).exists() {
-        let file = Path::new(&args[1]);
-        fs::read_to_string(file)?
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Color {
+    /// Color when stdout is a terminal and NO_COLOR is unset
+    Auto,
+    /// Always color
+    Always,
+    /// Never color
+    Never,
+}
+
+#[derive(Debug, Parser)]
+#[command(about = "Disassemble and emulate EVM bytecode")]
+struct Args {
+    /// Hex bytecode, or a file containing it
+    #[arg(required_unless_present_any = ["artifact", "huff"])]
+    bytecode: Option<String>,
+    /// Hard fork used to decode and execute
+    #[arg(long, default_value_t = SpecId::default())]
+    spec: SpecId,
+    /// Part of deploy code to disassemble and execute
+    #[arg(long, value_enum, default_value_t = Part::All)]
+    part: Part,
+    /// Hex calldata
+    #[arg(long, conflicts_with_all = ["sig", "function"])]
+    calldata: Option<String>,
+    /// Function signature to encode calldata for, e.g. "add(uint256,uint256)"
+    #[arg(long, conflicts_with = "function")]
+    sig: Option<String>,
+    /// Arguments for --sig, negative numbers go after `--`
+    #[arg(requires = "sig")]
+    values: Vec<String>,
+    /// Output types to decode return data with, e.g. "uint256,bool"
+    #[arg(long)]
+    out: Option<String>,
+    /// Foundry or Hardhat artifact, or ABI JSON, providing code and function names
+    #[arg(long)]
+    artifact: Option<PathBuf>,
+    /// Function of --artifact to call, followed by its arguments
+    #[arg(
+        long = "fn",
+        requires = "artifact",
+        num_args = 1..,
+        allow_negative_numbers = true,
+        value_names = ["NAME", "ARGS"]
+    )]
+    function: Vec<String>,
+    /// Huff source file or `huffc -a` artifact to map instructions back to macros
+    #[arg(long)]
+    huff: Option<PathBuf>,
+    /// Compressed solc source map of the code, or a file containing it
+    #[arg(long)]
+    source_map: Option<String>,
+    /// Source files referenced by the source map, in source id order
+    #[arg(long, num_args = 1..)]
+    sources: Vec<PathBuf>,
+    /// Disassembly syntax: plain, huff, json, pretty or solidity [default: pretty on a terminal, else plain].
+    /// JSON output disassembles only
+    #[arg(long)]
+    syntax: Option<Syntax>,
+    /// Show the raw bytes of each instruction
+    #[arg(long)]
+    bytes: bool,
+    /// Show how many stack items each instruction pops and pushes
+    #[arg(long)]
+    stack_effect: bool,
+    /// Color the pretty syntax
+    #[arg(long, value_enum, default_value_t = Color::Auto)]
+    color: Color,
+    /// Disassemble only
+    #[arg(long)]
+    no_run: bool,
+    /// Explore every path with symbolic inputs instead of running
+    #[arg(long, conflicts_with_all = ["calldata", "sig", "function"])]
+    symbolic: bool,
+    /// Print the SMT-LIB2 constraints of each symbolic path
+    #[arg(long, requires = "symbolic")]
+    smt: bool,
+    /// Drop symbolic paths the solver proves infeasible
+    #[arg(long, requires = "symbolic")]
+    prune: bool,
+    /// Find calldata reaching a position, or triggering a revert or assert
+    #[arg(long, value_name = "POSITION|revert|assert", conflicts_with_all = ["symbolic", "calldata", "sig", "function"])]
+    solve: Option<Goal>,
+    /// Report likely vulnerabilities found on the symbolic paths
+    #[arg(long, conflicts_with_all = ["symbolic", "solve"])]
+    detect: bool,
+    /// Print the findings of --detect as SARIF
+    #[arg(long, requires = "detect")]
+    sarif: bool,
+    /// SMT-LIB2 solver reading commands on stdin
+    #[arg(long, default_value = smt::DEFAULT_SOLVER)]
+    solver: String,
+    /// Handle Foundry cheatcodes (vm.warp, vm.prank, ...)
+    #[arg(long)]
+    cheatcodes: bool,
+    /// List the function selectors of the runtime and exit
+    #[arg(long)]
+    interface: bool,
+    /// Extra signature list or ABI JSON file used to name selectors and events
+    #[arg(long)]
+    signatures: Vec<PathBuf>,
+}
+
+// which part of the code is shown or executed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Section {
+    // code without a deploy prologue
+    Whole,
+    Init,
+    Runtime,
+}
+
+// source level information the code was compiled from
+#[derive(Debug, Default)]
+struct Sources {
+    huff: Option<HuffProject>,
+    // solc source maps of the code as given and of the runtime part of deploy code
+    source_map: Option<SourceMap>,
+    runtime_source_map: Option<SourceMap>,
+    files: SourceFiles,
+}
+
+impl Sources {
+    fn huff_entry(section: Section) -> &'static str {
+        match section {
+            Section::Init => "CONSTRUCTOR",
+            _ => "MAIN",
+        }
+    }
+
+    fn solidity_map(&self, section: Section) -> Option<&SourceMap> {
+        match section {
+            Section::Runtime => self.runtime_source_map.as_ref(),
+            _ => self.source_map.as_ref(),
+        }
+    }
+
+    // labels and one comment per source line for the disassembly
+    fn annotate(
+        &self,
+        section: Section,
+        blocks: &[Block],
+    ) -> (BTreeMap<usize, String>, BTreeMap<usize, String>) {
+        if let Some(project) = &self.huff {
+            let source_map = project.source_map(Self::huff_entry(section), blocks);
+            return (source_map.labels.clone(), source_map.comments(project));
+        }
+        let comments = self
+            .solidity_map(section)
+            .map(|map| map.comments(blocks, &self.files))
+            .unwrap_or_default();
+        (BTreeMap::new(), comments)
+    }
+
+    // source location of every instruction, for the trace
+    fn locations(&self, section: Section, blocks: &[Block]) -> BTreeMap<usize, String> {
+        if let Some(project) = &self.huff {
+            let source_map = project.source_map(Self::huff_entry(section), blocks);
+            return source_map
+                .locations
+                .iter()
+                .map(|(position, location)| {
+                    let line = project.source_line(location).unwrap_or_default();
+                    (
+                        *position,
+                        format!("{}\t{}", project.describe(location), line),
+                    )
+                })
+                .collect();
+        }
+        let Some(map) = self.solidity_map(section) else {
+            return BTreeMap::new();
+        };
+        blocks
+            .iter()
+            .zip(&map.ranges)
+            .filter_map(|(block, range)| {
+                let location = self.files.location(range)?;
+                Some((block.position, location.to_string()))
+            })
+            .collect()
+    }
+
+    fn describe_call_stack(&self, call_stack: &CallStack) -> String {
+        let frames: Vec<_> = call_stack
+            .frames
+            .iter()
+            .map(|range| {
+                let Some(location) = self.files.location(range) else {
+                    return format!("{}:{}", range.offset, range.length);
+                };
+                // the call expression the frame was entered from, first line only
+                match self.files.snippet(range).and_then(|s| s.lines().next()) {
+                    Some(call) => format!("{}:{} {}", location.path, location.line, call.trim()),
+                    None => format!("{}:{}", location.path, location.line),
+                }
+            })
+            .collect();
+        if frames.is_empty() {
+            "(top level)".into()
+        } else {
+            frames.join(" > ")
+        }
+    }
+}
+
+fn read_or_string(value: &str) -> Result<String> {
+    if !value.is_empty() && Path::new(value).exists() {
+        Ok(fs::read_to_string(Path::new(value))?)
     } else {
-        args[1].to_string()
+        Ok(value.to_string())
+    }
+}
+
+// the blocks of `code` and their listing
+fn disassemble(
+    code: &[u8],
+    spec: SpecId,
+    db: &SignatureDb,
+    sources: &Sources,
+    section: Section,
+    options: &FormatOptions,
+) -> (Vec<Block>, String) {
+    let (parsed, metadata) = parser::parse_bytes_with_metadata(code, spec);
+    let mut labels = find_dispatcher(&parsed)
+        .map(|d| d.named_labels(db))
+        .unwrap_or_default();
+    let (source_labels, comments) = sources.annotate(section, &parsed);
+    labels.extend(source_labels);
+    let mut listing =
+        formatter::format_with_options(&parsed, &labels, Some(db), &comments, options);
+    // keep JSON output parseable
+    if let Some(metadata) = metadata.as_ref().filter(|_| options.syntax != Syntax::Json) {
+        listing += &formatter::format_metadata(metadata);
+    }
+    (parsed, listing)
+}
+
+fn format_tokens(name: &str, tokens: &[abi::Token]) -> String {
+    let tokens: Vec<_> = tokens.iter().map(|t| t.to_string()).collect();
+    format!("{}({})", name, tokens.join(", "))
+}
+
+// decoded with the artifact's events when possible, otherwise only named
+fn format_log(log: &Log, artifact: &Artifact, db: &SignatureDb) -> String {
+    let event = log
+        .topics
+        .first()
+        .and_then(|topic| artifact.abi.event_by_topic(topic));
+    if let Some(event) = event {
+        if let Ok(tokens) = event.decode_log(&log.topics, &log.data) {
+            return format_tokens(&event.name, &tokens);
+        }
+    }
+    let topics: Vec<_> = log.topics.iter().map(|t| format!("0x{:064x}", t)).collect();
+    let name = log.topics.first().and_then(|topic| db.event(topic));
+    format!(
+        "{}topics: [{}], data: 0x{}",
+        name.map_or(String::new(), |n| format!("{} ", n)),
+        topics.join(", "),
+        hex::encode(&log.data)
+    )
+}
+
+// custom errors of the artifact, then Error(string) and Panic(uint256)
+fn format_revert(data: &[u8], artifact: &Artifact) -> String {
+    let error = data.get(..4).and_then(|s| {
+        artifact
+            .abi
+            .error_by_selector(u32::from_be_bytes(s.try_into().unwrap()))
+    });
+    if let Some(error) = error {
+        let types: Vec<_> = error.inputs.iter().map(|p| p.kind.clone()).collect();
+        if let Ok(tokens) = abi::decode(&types, &data[4..]) {
+            return format_tokens(&error.name, &tokens);
+        }
+    }
+    abi::decode_revert(data).to_string()
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+
+    let artifact = match &args.artifact {
+        Some(path) => Artifact::load(path)?,
+        None => Artifact::default(),
+    };
+    let huff = match &args.huff {
+        Some(path) => HuffProject::load(path)?,
+        None => HuffProject::default(),
+    };
+    let function = match args.function.first() {
+        Some(name) => Some(artifact.abi.function(name)?),
+        None => None,
+    };
+
+    let raw_code = match &args.bytecode {
+        Some(bytecode) => parser::decode_hex(&read_or_string(bytecode)?)?,
+        // calling a function only makes sense on the runtime code
+        None if function.is_some() && !artifact.deployed_bytecode.is_empty() => {
+            artifact.deployed_bytecode.clone()
+        }
+        None if !artifact.bytecode.is_empty() => artifact.bytecode.clone(),
+        None if !artifact.deployed_bytecode.is_empty() => artifact.deployed_bytecode.clone(),
+        None if !huff.bytecode.is_empty() => huff.bytecode.clone(),
+        None => huff.runtime.clone(),
     };
-    let bytecode = bytecode.trim();
+    if raw_code.is_empty() {
+        return Err(anyhow!("No bytecode"));
+    }
+
+    let source_map = match &args.source_map {
+        Some(map) => Some(read_or_string(map)?),
+        None if raw_code == artifact.deployed_bytecode => artifact.deployed_source_map.clone(),
+        None if raw_code == artifact.bytecode => artifact.source_map.clone(),
+        None => None,
+    };
+    let mut sources = Sources {
+        huff: args.huff.is_some().then_some(huff),
+        source_map: source_map.as_deref().map(SourceMap::parse).transpose()?,
+        runtime_source_map: artifact
+            .deployed_source_map
+            .as_deref()
+            .map(SourceMap::parse)
+            .transpose()?,
+        ..Default::default()
+    };
+    for (id, path) in &artifact.sources {
+        // paths are relative to the project root, which may not be the working directory
+        if Path::new(path).exists() {
+            sources.files.load(*id, Path::new(path))?;
+        }
+    }
+    for (id, path) in args.sources.iter().enumerate() {
+        sources.files.load(id, path)?;
+    }
+
+    let mut db = SignatureDb::bundled();
+    for path in &args.signatures {
+        db.load_file(path)?;
+    }
+    db.insert_abi(&artifact.abi);
 
-    let parsed = parser::parse(bytecode);
-    print!("{}", formatter::format(&parsed));
-    let calldata = hex::decode("0f52d66e00000000000000000000000000000000000000000000000000000000000000640000000000000000000000000000000000000000000000000000000000000064").unwrap();
-    let mut emu = Emulator::new(
-        hex::decode(bytecode).expect("Invalid bytecode"),
-        parsed,
-        &calldata,
+    let deployment = find_deployment(
+        &parser::parse_bytes_with_spec(&raw_code, args.spec),
+        raw_code.len(),
     );
+    if args.interface {
+        let runtime = deployment
+            .as_ref()
+            .map_or(&raw_code[..], |d| d.runtime_code(&raw_code));
+        let (parsed, _) = parser::parse_bytes_with_metadata(runtime, args.spec);
+        let dispatcher = find_dispatcher(&parsed).ok_or(anyhow!("No dispatcher found"))?;
+        for entry in &dispatcher.entries {
+            match db.function(entry.selector) {
+                Some(name) => println!(
+                    "0x{:08x}: {:08x}\t{}",
+                    entry.selector, entry.destination, name
+                ),
+                None => println!("0x{:08x}: {:08x}", entry.selector, entry.destination),
+            }
+        }
+        return Ok(());
+    }
+    if args.detect {
+        let detectors = detectors::builtin_detectors();
+        // deploy code is checked as deployed unless --part init is given, the constructor
+        // then runs as a whole so it can copy the runtime and its arguments
+        let (code, code_offset) = match (args.part, &deployment) {
+            (Part::Init, _) | (_, None) => (&raw_code[..], 0),
+            (_, Some(deployment)) => (deployment.runtime_code(&raw_code), deployment.runtime.start),
+        };
+        let findings = detectors::detect(code, args.spec, &Limits::default(), &detectors);
+        if args.sarif {
+            let artifact = args
+                .bytecode
+                .as_ref()
+                .filter(|b| Path::new(b).is_file())
+                .map(PathBuf::from)
+                .or(args.artifact.clone())
+                .or(args.huff.clone())
+                .unwrap_or_else(|| PathBuf::from("bytecode"));
+            print!(
+                "{}",
+                detectors::to_sarif(
+                    &findings,
+                    &detectors,
+                    &artifact.to_string_lossy(),
+                    code_offset
+                )
+            );
+        } else {
+            print!("{}", detectors::format_findings(&findings));
+        }
+        return Ok(());
+    }
+
+    let terminal = std::io::stdout().is_terminal();
+    let options = FormatOptions {
+        syntax: args.syntax.unwrap_or(if terminal {
+            Syntax::Pretty
+        } else {
+            Syntax::Plain
+        }),
+        bytes: args.bytes,
+        stack_effect: args.stack_effect,
+        color: match args.color {
+            Color::Auto => terminal && std::env::var_os("NO_COLOR").is_none(),
+            Color::Always => true,
+            Color::Never => false,
+        },
+    };
+    let (raw_code, parsed, section) = match (args.part, &deployment) {
+        (Part::All, Some(deployment)) => {
+            let (_, init) = disassemble(
+                deployment.init_code(&raw_code),
+                args.spec,
+                &db,
+                &sources,
+                Section::Init,
+                &options,
+            );
+            let (_, runtime) = disassemble(
+                deployment.runtime_code(&raw_code),
+                args.spec,
+                &db,
+                &sources,
+                Section::Runtime,
+                &options,
+            );
+            let constructor_args =
+                format!("0x{}", hex::encode(deployment.constructor_args(&raw_code)));
+            if options.syntax == Syntax::Json {
+                // both parts in one document
+                let document = serde_json::json!({
+                    "init": serde_json::from_str::<serde_json::Value>(&init)?,
+                    "runtime": serde_json::from_str::<serde_json::Value>(&runtime)?,
+                    "constructor_args": constructor_args,
+                });
+                println!("{}", serde_json::to_string_pretty(&document)?);
+            } else {
+                print!("init code:\n{}runtime code:\n{}", init, runtime);
+                if !deployment.constructor_args.is_empty() {
+                    println!("constructor args:\n{}", constructor_args);
+                }
+            }
+            let parsed = parser::parse_bytes_with_spec(&raw_code, args.spec);
+            (raw_code, parsed, Section::Init)
+        }
+        (Part::All, None) => {
+            let (parsed, listing) = disassemble(
+                &raw_code,
+                args.spec,
+                &db,
+                &sources,
+                Section::Whole,
+                &options,
+            );
+            print!("{}", listing);
+            (raw_code, parsed, Section::Whole)
+        }
+        (Part::Init, Some(deployment)) => {
+            let (_, listing) = disassemble(
+                deployment.init_code(&raw_code),
+                args.spec,
+                &db,
+                &sources,
+                Section::Init,
+                &options,
+            );
+            print!("{}", listing);
+            // the constructor copies the runtime and its arguments out of the whole code
+            let parsed = parser::parse_bytes_with_spec(&raw_code, args.spec);
+            (raw_code, parsed, Section::Init)
+        }
+        (_, Some(deployment)) => {
+            let code = deployment.runtime_code(&raw_code).to_vec();
+            let (parsed, listing) =
+                disassemble(&code, args.spec, &db, &sources, Section::Runtime, &options);
+            print!("{}", listing);
+            (code, parsed, Section::Runtime)
+        }
+        (_, None) => return Err(anyhow!("No deploy prologue found")),
+    };
+
+    // nothing but the listing goes to stdout with JSON
+    if args.no_run || options.syntax == Syntax::Json {
+        return Ok(());
+    }
+    if args.symbolic {
+        let mut solver = Solver::parse(&args.solver)?;
+        let mut error = None;
+        let paths = symbolic::explore_with(
+            &raw_code,
+            args.spec,
+            &Limits::default(),
+            &mut |constraints| {
+                !args.prune
+                    || solver.is_feasible(constraints).unwrap_or_else(|e| {
+                        error.get_or_insert(e);
+                        true
+                    })
+            },
+        );
+        if let Some(e) = error {
+            return Err(e);
+        }
+        for (i, path) in paths.iter().enumerate() {
+            println!("---------");
+            println!("path {}:", i);
+            print!("{}", path);
+            if args.smt {
+                print!("{}", smt::to_smtlib(&path.constraints));
+            }
+        }
+        return Ok(());
+    }
+    if let Some(goal) = args.solve {
+        let mut solver = Solver::parse(&args.solver)?;
+        match smt::solve(&raw_code, args.spec, &Limits::default(), goal, &mut solver)? {
+            Some((path, model)) => {
+                print!("{}", path);
+                println!("calldata: 0x{}", hex::encode(&model.calldata));
+                for (name, value) in &model.values {
+                    println!("{}: 0x{:x}", name, value);
+                }
+            }
+            None => println!("No inputs found"),
+        }
+        return Ok(());
+    }
+
+    let calldata = match (function, &args.sig, &args.calldata) {
+        (Some(function), _, _) => {
+            let values: Vec<_> = args.function[1..].iter().map(|v| v.as_str()).collect();
+            function.encode_input_str(&values)?
+        }
+        (None, Some(sig), _) => {
+            let (_, types) = abi::parse_signature(sig)?;
+            let values: Vec<_> = args.values.iter().map(|v| v.as_str()).collect();
+            abi::encode_function_call(sig, &abi::parse_tokens(&types, &values)?)?
+        }
+        (None, None, Some(calldata)) => parser::decode_hex(calldata)?,
+        (None, None, None) => vec![],
+    };
+    let locations = sources.locations(section, &parsed);
+    let ranges = sources.solidity_map(section);
+    let mut call_stack = CallStack::default();
+    let mut emu = Emulator::new(raw_code, parsed, &calldata).with_spec(args.spec);
+    if args.cheatcodes {
+        emu.state.enable_cheatcodes();
+    }
     println!("Stack: {:02x?}", emu.stack);
     println!("Memory: {:02x?}", emu.memory);
     while !emu.is_end() {
         println!("---------");
         print!("{}", formatter::format(&[emu.current_block().clone()]));
+        if let Some(location) = locations.get(&emu.current_block().position) {
+            println!("Source: {}", location);
+        }
+        let (index, op) = (emu.block_index, emu.current_block().opcode.clone());
+        if let Some(name) = emu.called_function(&db) {
+            println!("Call: {}", name);
+        }
         emu.run()?;
+        if let Some(range) = ranges.and_then(|m| m.get(index)) {
+            if call_stack.step(&op, range) {
+                println!("Call stack: {}", sources.describe_call_stack(&call_stack));
+            }
+        }
         println!("Stack: {:02x?}", emu.stack);
         println!("Memory: {:02x?}", emu.memory);
         println!("Return data: {:02x?}", emu.return_data);
     }
 
+    for log in &emu.state.logs {
+        println!("Log: {}", format_log(log, &artifact, &db));
+    }
+
+    match emu.halt {
+        Some(Halt::Revert) => println!("Reverted: {}", format_revert(&emu.return_data, &artifact)),
+        _ => {
+            if let Some(function) = function {
+                let tokens = function.decode_output(&emu.return_data)?;
+                let tokens: Vec<_> = tokens.iter().map(|t| t.to_string()).collect();
+                println!("Output: ({})", tokens.join(", "));
+            } else if let Some(out) = &args.out {
+                let tokens = abi::decode(&abi::parse_types(out)?, &emu.return_data)?;
+                let tokens: Vec<_> = tokens.iter().map(|t| t.to_string()).collect();
+                println!("Output: ({})", tokens.join(", "));
+            }
+        }
+    }
+
     Ok(())
 }
@@ -0,0 +1,198 @@
+use std::fmt;
+
+// CBOR metadata appended by solc, `<cbor map> <length: u16>`
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Metadata {
+    // offset of the CBOR map in the code
+    pub position: usize,
+    // CBOR map and the two length bytes
+    pub raw: Vec<u8>,
+    pub ipfs: Option<Vec<u8>>,
+    pub bzzr0: Option<Vec<u8>>,
+    pub bzzr1: Option<Vec<u8>>,
+    pub solc: Option<SolcVersion>,
+    pub experimental: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SolcVersion {
+    Release(u8, u8, u8),
+    // non-release builds store the full version string
+    Prerelease(String),
+}
+
+impl fmt::Display for SolcVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SolcVersion::Release(major, minor, patch) => write!(f, "{}.{}.{}", major, minor, patch),
+            SolcVersion::Prerelease(s) => f.write_str(s),
+        }
+    }
+}
+
+impl Metadata {
+    // CIDv0 of the metadata JSON, as used by IPFS gateways
+    pub fn ipfs_cid(&self) -> Option<String> {
+        self.ipfs.as_deref().map(base58_encode)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Value {
+    Uint(u64),
+    Bytes(Vec<u8>),
+    Text(String),
+    Bool(bool),
+}
+
+// split `code` into the instructions and the trailing metadata, if any
+pub fn split_metadata(code: &[u8]) -> (&[u8], Option<Metadata>) {
+    match find_metadata(code) {
+        Some(metadata) => (&code[..metadata.position], Some(metadata)),
+        None => (code, None),
+    }
+}
+
+pub fn find_metadata(code: &[u8]) -> Option<Metadata> {
+    if code.len() < 2 {
+        return None;
+    }
+    let len = u16::from_be_bytes([code[code.len() - 2], code[code.len() - 1]]) as usize;
+    let position = (code.len() - 2).checked_sub(len)?;
+    let map = decode_map(&code[position..code.len() - 2])?;
+
+    let mut metadata = Metadata {
+        position,
+        raw: code[position..].to_vec(),
+        ..Default::default()
+    };
+    for (key, value) in map {
+        match (key.as_str(), value) {
+            ("ipfs", Value::Bytes(b)) => metadata.ipfs = Some(b),
+            ("bzzr0", Value::Bytes(b)) => metadata.bzzr0 = Some(b),
+            ("bzzr1", Value::Bytes(b)) => metadata.bzzr1 = Some(b),
+            ("solc", Value::Bytes(b)) if b.len() == 3 => {
+                metadata.solc = Some(SolcVersion::Release(b[0], b[1], b[2]))
+            }
+            ("solc", Value::Text(s)) => metadata.solc = Some(SolcVersion::Prerelease(s)),
+            ("experimental", Value::Bool(b)) => metadata.experimental = b,
+            // unknown keys are kept in `raw` only
+            (_, _) => {}
+        }
+    }
+    Some(metadata)
+}
+
+// a CBOR map with text keys which spans the whole input
+fn decode_map(input: &[u8]) -> Option<Vec<(String, Value)>> {
+    let (major, len, mut rest) = decode_head(input)?;
+    if major != 5 || len == 0 {
+        return None;
+    }
+    let mut result = vec![];
+    for _ in 0..len {
+        let (key, rest_) = decode_value(rest)?;
+        let (value, rest_) = decode_value(rest_)?;
+        rest = rest_;
+        match key {
+            Value::Text(key) => result.push((key, value)),
+            _ => return None,
+        }
+    }
+    if !rest.is_empty() {
+        return None;
+    }
+    Some(result)
+}
+
+fn decode_value(input: &[u8]) -> Option<(Value, &[u8])> {
+    let (major, arg, rest) = decode_head(input)?;
+    match major {
+        0 => Some((Value::Uint(arg), rest)),
+        2 | 3 => {
+            let len = usize::try_from(arg).ok()?;
+            if rest.len() < len {
+                return None;
+            }
+            let (data, rest) = rest.split_at(len);
+            let value = if major == 2 {
+                Value::Bytes(data.to_vec())
+            } else {
+                Value::Text(String::from_utf8(data.to_vec()).ok()?)
+            };
+            Some((value, rest))
+        }
+        7 if arg == 20 => Some((Value::Bool(false), rest)),
+        7 if arg == 21 => Some((Value::Bool(true), rest)),
+        _ => None,
+    }
+}
+
+// (major type, argument, rest)
+fn decode_head(input: &[u8]) -> Option<(u8, u64, &[u8])> {
+    let (&first, rest) = input.split_first()?;
+    let major = first >> 5;
+    let info = first & 0x1f;
+    let size = match info {
+        0..=23 => return Some((major, info as u64, rest)),
+        24 => 1,
+        25 => 2,
+        26 => 4,
+        27 => 8,
+        _ => return None,
+    };
+    if rest.len() < size {
+        return None;
+    }
+    let arg = rest[..size]
+        .iter()
+        .fold(0u64, |acc, b| acc << 8 | *b as u64);
+    Some((major, arg, &rest[size..]))
+}
+
+fn base58_encode(data: &[u8]) -> String {
+    const ALPHABET: &[u8] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+    let mut digits: Vec<u8> = vec![];
+    for &byte in data {
+        let mut carry = byte as u32;
+        for digit in digits.iter_mut() {
+            carry += (*digit as u32) << 8;
+            *digit = (carry % 58) as u8;
+            carry /= 58;
+        }
+        while carry > 0 {
+            digits.push((carry % 58) as u8);
+            carry /= 58;
+        }
+    }
+    let zeros = data.iter().take_while(|b| **b == 0).count();
+    std::iter::repeat_n(b'1', zeros)
+        .chain(digits.iter().rev().map(|d| ALPHABET[*d as usize]))
+        .map(char::from)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{split_metadata, SolcVersion};
+
+    #[test]
+    fn test_split_metadata() {
+        // STOP followed by metadata from solc 0.8.19
+        let metadata = "a264697066735822122058d0cf2ad1d74d1d5e3a0cbd4e6b7bd3c4a1c7c1c3be1e6d3d3b54ec9d4ab09a64736f6c63430008130033";
+        let code = hex::decode(format!("00{}", metadata)).unwrap();
+        let (instructions, found) = split_metadata(&code);
+        let found = found.unwrap();
+        assert_eq!(instructions, &[0x00]);
+        assert_eq!(found.position, 1);
+        assert_eq!(found.solc, Some(SolcVersion::Release(0, 8, 19)));
+        assert_eq!(found.ipfs.as_ref().unwrap().len(), 34);
+        assert!(found.ipfs_cid().unwrap().starts_with("Qm"));
+        assert!(!found.experimental);
+
+        // a plain runtime has nothing to split off
+        let code = hex::decode("6000356020350160005260206000f3").unwrap();
+        assert_eq!(split_metadata(&code), (&code[..], None));
+    }
+}
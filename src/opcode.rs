@@ -1,4 +1,4 @@
-use crate::Uint256;
+use crate::{spec::SpecId, util::to_bytes32, Uint256};
 
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
 pub enum OpCode {
@@ -54,6 +54,8 @@ pub enum OpCode {
     CHAINID,
     SELFBALANCE,
     BASEFEE,
+    BLOBHASH,
+    BLOBBASEFEE,
     POP,
     MLOAD,
     MSTORE,
@@ -66,13 +68,17 @@ pub enum OpCode {
     MSIZE,
     GAS,
     JUMPDEST,
+    TLOAD,
+    TSTORE,
+    MCOPY,
+    PUSH0,
     PUSHN(u8, Uint256),
+    // PUSH whose immediate runs past the end of the code
+    #[allow(non_camel_case_types)]
+    TRUNCATED_PUSHN(u8, Vec<u8>),
     DUPN(u8),
     SWAPN(u8),
     LOGN(u8),
-    PUSH,
-    DUP,
-    SWAP,
     CREATE,
     CALL,
     CALLCODE,
@@ -84,3 +90,189 @@ pub enum OpCode {
     SELFDESTRUCT,
     INVALID(u8),
 }
+
+impl OpCode {
+    // hard fork which introduced the opcode
+    pub fn introduced_in(&self) -> SpecId {
+        use OpCode::*;
+
+        match self {
+            DELEGATECALL => SpecId::Homestead,
+            RETURNDATASIZE | RETURNDATACOPY | STATICCALL | REVERT => SpecId::Byzantium,
+            SHL | SHR | SAR | EXTCODEHASH | CREATE2 => SpecId::Constantinople,
+            CHAINID | SELFBALANCE => SpecId::Istanbul,
+            BASEFEE => SpecId::London,
+            PUSH0 => SpecId::Shanghai,
+            BLOBHASH | BLOBBASEFEE | TLOAD | TSTORE | MCOPY => SpecId::Cancun,
+            _ => SpecId::Frontier,
+        }
+    }
+
+    // number of (inputs, outputs) on the stack
+    pub fn stack_io(&self) -> (usize, usize) {
+        use OpCode::*;
+
+        match self {
+            STOP | JUMPDEST | INVALID(_) => (0, 0),
+            ADD | MUL | SUB | DIV | SDIV | MOD | SMOD | EXP | SIGNEXTEND | LT | GT | SLT | SGT
+            | EQ | AND | OR | XOR | BYTE | SHL | SHR | SAR | SHA3 => (2, 1),
+            ADDMOD | MULMOD => (3, 1),
+            ISZERO | NOT | BALANCE | CALLDATALOAD | EXTCODESIZE | EXTCODEHASH | BLOCKHASH
+            | MLOAD | SLOAD | TLOAD | BLOBHASH => (1, 1),
+            ADDRESS
+            | ORIGIN
+            | CALLER
+            | CALLVALUE
+            | CALLDATASIZE
+            | CODESIZE
+            | GASPRICE
+            | RETURNDATASIZE
+            | COINBASE
+            | TIMESTAMP
+            | NUMBER
+            | DIFFICULTY
+            | GASLIMIT
+            | CHAINID
+            | SELFBALANCE
+            | BASEFEE
+            | PC
+            | MSIZE
+            | GAS
+            | BLOBBASEFEE
+            | PUSH0
+            | PUSHN(_, _)
+            | TRUNCATED_PUSHN(_, _) => (0, 1),
+            CALLDATACOPY | CODECOPY | RETURNDATACOPY | MCOPY => (3, 0),
+            EXTCODECOPY => (4, 0),
+            POP | JUMP | SELFDESTRUCT => (1, 0),
+            MSTORE | MSTORE8 | SSTORE | TSTORE | JUMPI | RETURN | REVERT => (2, 0),
+            DUPN(n) => (*n as usize, *n as usize + 1),
+            SWAPN(n) => (*n as usize + 1, *n as usize + 1),
+            LOGN(n) => (*n as usize + 2, 0),
+            CREATE => (3, 1),
+            CREATE2 => (4, 1),
+            CALL | CALLCODE => (7, 1),
+            DELEGATECALL | STATICCALL => (6, 1),
+        }
+    }
+
+    // value pushed by PUSH0-32, a truncated immediate is zero padded on the right
+    pub fn push_value(&self) -> Option<Uint256> {
+        match self {
+            OpCode::PUSH0 => Some(Uint256::default()),
+            OpCode::PUSHN(_, v) => Some(v.clone()),
+            OpCode::TRUNCATED_PUSHN(n, bytes) => {
+                let mut padded = bytes.clone();
+                padded.resize(*n as usize, 0);
+                Some(Uint256::from_bytes_be(&padded))
+            }
+            _ => None,
+        }
+    }
+
+    // byte encoding of the instruction, immediate included
+    pub fn to_bytes(&self) -> Vec<u8> {
+        use OpCode::*;
+
+        let op = match self {
+            STOP => 0x00,
+            ADD => 0x01,
+            MUL => 0x02,
+            SUB => 0x03,
+            DIV => 0x04,
+            SDIV => 0x05,
+            MOD => 0x06,
+            SMOD => 0x07,
+            ADDMOD => 0x08,
+            MULMOD => 0x09,
+            EXP => 0x0a,
+            SIGNEXTEND => 0x0b,
+            LT => 0x10,
+            GT => 0x11,
+            SLT => 0x12,
+            SGT => 0x13,
+            EQ => 0x14,
+            ISZERO => 0x15,
+            AND => 0x16,
+            OR => 0x17,
+            XOR => 0x18,
+            NOT => 0x19,
+            BYTE => 0x1a,
+            SHL => 0x1b,
+            SHR => 0x1c,
+            SAR => 0x1d,
+            SHA3 => 0x20,
+            ADDRESS => 0x30,
+            BALANCE => 0x31,
+            ORIGIN => 0x32,
+            CALLER => 0x33,
+            CALLVALUE => 0x34,
+            CALLDATALOAD => 0x35,
+            CALLDATASIZE => 0x36,
+            CALLDATACOPY => 0x37,
+            CODESIZE => 0x38,
+            CODECOPY => 0x39,
+            GASPRICE => 0x3a,
+            EXTCODESIZE => 0x3b,
+            EXTCODECOPY => 0x3c,
+            RETURNDATASIZE => 0x3d,
+            RETURNDATACOPY => 0x3e,
+            EXTCODEHASH => 0x3f,
+            BLOCKHASH => 0x40,
+            COINBASE => 0x41,
+            TIMESTAMP => 0x42,
+            NUMBER => 0x43,
+            DIFFICULTY => 0x44,
+            GASLIMIT => 0x45,
+            CHAINID => 0x46,
+            SELFBALANCE => 0x47,
+            BASEFEE => 0x48,
+            BLOBHASH => 0x49,
+            BLOBBASEFEE => 0x4a,
+            POP => 0x50,
+            MLOAD => 0x51,
+            MSTORE => 0x52,
+            MSTORE8 => 0x53,
+            SLOAD => 0x54,
+            SSTORE => 0x55,
+            JUMP => 0x56,
+            JUMPI => 0x57,
+            PC => 0x58,
+            MSIZE => 0x59,
+            GAS => 0x5a,
+            JUMPDEST => 0x5b,
+            TLOAD => 0x5c,
+            TSTORE => 0x5d,
+            MCOPY => 0x5e,
+            PUSH0 => 0x5f,
+            CREATE => 0xf0,
+            CALL => 0xf1,
+            CALLCODE => 0xf2,
+            RETURN => 0xf3,
+            DELEGATECALL => 0xf4,
+            CREATE2 => 0xf5,
+            STATICCALL => 0xfa,
+            REVERT => 0xfd,
+            SELFDESTRUCT => 0xff,
+            PUSHN(n, value) => {
+                let value = to_bytes32(value);
+                return [&[0x5f + n], &value[32 - *n as usize..]].concat();
+            }
+            TRUNCATED_PUSHN(n, bytes) => return [&[0x5f + n], &bytes[..]].concat(),
+            DUPN(n) => 0x7f + n,
+            SWAPN(n) => 0x8f + n,
+            LOGN(n) => 0xa0 + n,
+            INVALID(op) => *op,
+        };
+        vec![op]
+    }
+
+    pub fn is_terminator(&self) -> bool {
+        use OpCode::*;
+
+        matches!(
+            self,
+            STOP | JUMP | RETURN | REVERT | SELFDESTRUCT | INVALID(_)
+        )
+    }
+}
@@ -1,45 +1,102 @@
-use nom::{bytes::complete::take_while_m_n, combinator::map_res, multi::many0, IResult};
+use std::fmt;
+
+use nom::{bytes::complete::take, multi::many0, number::complete::u8 as parse_u8, IResult};
 use nom_locate::LocatedSpan;
-use num_traits::Num;
 
-use crate::{block::Block, opcode::OpCode, Uint256};
+use crate::{
+    block::Block,
+    metadata::{split_metadata, Metadata},
+    opcode::OpCode,
+    spec::SpecId,
+    Uint256,
+};
 
-type Span<'a> = LocatedSpan<&'a str>;
+type Span<'a> = LocatedSpan<&'a [u8]>;
 
-fn from_hex(input: &str) -> Result<u8, std::num::ParseIntError> {
-    u8::from_str_radix(input, 16)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    InvalidCharacter(char),
+    OddLength,
 }
 
-fn is_hex_digit(c: char) -> bool {
-    c.is_ascii_hexdigit()
+// error in hex input, located by character offset, line and column
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub kind: ParseErrorKind,
+    pub offset: usize,
+    pub line: usize,
+    pub column: usize,
 }
 
-fn parse_hex_u8(input: Span) -> IResult<Span, u8> {
-    let input_str = input.fragment();
-    let raw: IResult<&str, u8> = map_res(take_while_m_n(2, 2, is_hex_digit), from_hex)(input_str);
-
-    raw.map(|r| {
-        (
-            unsafe {
-                Span::new_from_raw_offset(
-                    input.location_offset() + 2,
-                    input.location_line(),
-                    r.0,
-                    (),
-                )
-            },
-            r.1,
-        )
-    })
-    .map_err(|e| {
-        e.map_input(|e| unsafe {
-            Span::new_from_raw_offset(input.location_offset() + 2, input.location_line(), e, ())
-        })
-    })
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            ParseErrorKind::InvalidCharacter(c) => write!(
+                f,
+                "Invalid character {:?} at line {}, column {}",
+                c, self.line, self.column
+            ),
+            ParseErrorKind::OddLength => write!(
+                f,
+                "Odd number of hex digits, last digit at line {}, column {}",
+                self.line, self.column
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+// decode hex bytecode, ignoring whitespace and a leading `0x`
+pub fn decode_hex(input: &str) -> Result<Vec<u8>, ParseError> {
+    let trimmed = input.trim_start();
+    let skip = input.len() - trimmed.len()
+        + if trimmed.starts_with("0x") || trimmed.starts_with("0X") {
+            2
+        } else {
+            0
+        };
+
+    let mut bytes = Vec::with_capacity(input.len() / 2);
+    let mut high: Option<(u8, usize, usize, usize)> = None;
+    let (mut line, mut column) = (1, 1);
+    for (offset, c) in input.char_indices() {
+        let location = (offset, line, column);
+        if c == '\n' {
+            line += 1;
+            column = 1;
+        } else {
+            column += 1;
+        }
+        if offset < skip || c.is_whitespace() {
+            continue;
+        }
+
+        let nibble = c.to_digit(16).ok_or(ParseError {
+            kind: ParseErrorKind::InvalidCharacter(c),
+            offset: location.0,
+            line: location.1,
+            column: location.2,
+        })? as u8;
+        match high.take() {
+            Some((h, ..)) => bytes.push(h << 4 | nibble),
+            None => high = Some((nibble, location.0, location.1, location.2)),
+        }
+    }
+
+    if let Some((_, offset, line, column)) = high {
+        return Err(ParseError {
+            kind: ParseErrorKind::OddLength,
+            offset,
+            line,
+            column,
+        });
+    }
+    Ok(bytes)
 }
 
-fn parse_opcode(input: Span) -> IResult<Span, OpCode> {
-    let (input, op) = parse_hex_u8(input)?;
+fn parse_opcode(input: Span, spec: SpecId) -> IResult<Span, OpCode> {
+    let (input, op) = parse_u8(input)?;
 
     let (input, result) = match op {
         0x00 => (input, OpCode::STOP),
@@ -94,6 +151,8 @@ fn parse_opcode(input: Span) -> IResult<Span, OpCode> {
         0x46 => (input, OpCode::CHAINID),
         0x47 => (input, OpCode::SELFBALANCE),
         0x48 => (input, OpCode::BASEFEE),
+        0x49 => (input, OpCode::BLOBHASH),
+        0x4a => (input, OpCode::BLOBBASEFEE),
         0x50 => (input, OpCode::POP),
         0x51 => (input, OpCode::MLOAD),
         0x52 => (input, OpCode::MSTORE),
@@ -106,9 +165,10 @@ fn parse_opcode(input: Span) -> IResult<Span, OpCode> {
         0x59 => (input, OpCode::MSIZE),
         0x5a => (input, OpCode::GAS),
         0x5b => (input, OpCode::JUMPDEST),
-        0xb0 => (input, OpCode::PUSH),
-        0xb1 => (input, OpCode::DUP),
-        0xb2 => (input, OpCode::SWAP),
+        0x5c => (input, OpCode::TLOAD),
+        0x5d => (input, OpCode::TSTORE),
+        0x5e => (input, OpCode::MCOPY),
+        0x5f => (input, OpCode::PUSH0),
         0xf0 => (input, OpCode::CREATE),
         0xf1 => (input, OpCode::CALL),
         0xf2 => (input, OpCode::CALLCODE),
@@ -124,21 +184,27 @@ fn parse_opcode(input: Span) -> IResult<Span, OpCode> {
             let result: OpCode = if (0x60..0x80).contains(&op) {
                 // PUSH1-32
                 let n = op - 0x60 + 1;
-                let (input_, value) =
-                    take_while_m_n((n * 2).into(), (n * 2).into(), is_hex_digit)(input)?;
-                input = input_;
-                OpCode::PUSHN(n, Uint256::from_str_radix(value.fragment(), 16).unwrap())
+                if input.len() < n as usize {
+                    // immediate runs past the end of the code
+                    let (input_, value) = take(input.len())(input)?;
+                    input = input_;
+                    OpCode::TRUNCATED_PUSHN(n, value.fragment().to_vec())
+                } else {
+                    let (input_, value) = take(n)(input)?;
+                    input = input_;
+                    OpCode::PUSHN(n, Uint256::from_bytes_be(value.fragment()))
+                }
             } else if (0x80..0x90).contains(&op) {
-                // DUP1-32
+                // DUP1-16
                 let n = op - 0x80 + 1;
                 OpCode::DUPN(n)
             } else if (0x90..0xA0).contains(&op) {
-                // SWAP1-32
+                // SWAP1-16
                 let n = op - 0x90 + 1;
                 OpCode::SWAPN(n)
             } else if (0xA0..0xA5).contains(&op) {
-                // LOG1-32
-                let n = op - 0xA0 + 1;
+                // LOG0-4
+                let n = op - 0xA0;
                 OpCode::LOGN(n)
             } else {
                 OpCode::INVALID(op)
@@ -148,33 +214,65 @@ fn parse_opcode(input: Span) -> IResult<Span, OpCode> {
         }
     };
 
+    // opcodes from later hard forks are undefined under `spec`
+    let result = if spec.is_enabled_in(result.introduced_in()) {
+        result
+    } else {
+        OpCode::INVALID(op)
+    };
+
     Ok((input, result))
 }
 
-fn parse_block(span: Span) -> IResult<Span, Block> {
-    let position = span.location_offset() / 2;
-    let (span, opcode) = parse_opcode(span)?;
+fn parse_block(span: Span, spec: SpecId) -> IResult<Span, Block> {
+    let position = span.location_offset();
+    let (span, opcode) = parse_opcode(span, spec)?;
     let block = Block::new(opcode, position);
     Ok((span, block))
 }
 
-fn parse_root(span: Span) -> IResult<Span, Vec<Block>> {
+fn parse_root(span: Span, spec: SpecId) -> IResult<Span, Vec<Block>> {
     let input = span;
-    let (input, result) = many0(parse_block)(input)?;
+    let (input, result) = many0(|s| parse_block(s, spec))(input)?;
     Ok((input, result))
 }
 
-pub fn parse(input: &str) -> Vec<Block> {
+pub fn parse(input: &str) -> Result<Vec<Block>, ParseError> {
+    parse_with_spec(input, SpecId::default())
+}
+
+pub fn parse_with_spec(input: &str, spec: SpecId) -> Result<Vec<Block>, ParseError> {
+    let bytes = decode_hex(input)?;
+    Ok(parse_bytes_with_spec(&bytes, spec))
+}
+
+pub fn parse_bytes(input: &[u8]) -> Vec<Block> {
+    parse_bytes_with_spec(input, SpecId::default())
+}
+
+// instructions of `input` excluding the trailing solc metadata
+pub fn parse_bytes_with_metadata(input: &[u8], spec: SpecId) -> (Vec<Block>, Option<Metadata>) {
+    let (code, metadata) = split_metadata(input);
+    (parse_bytes_with_spec(code, spec), metadata)
+}
+
+pub fn parse_bytes_with_spec(input: &[u8], spec: SpecId) -> Vec<Block> {
     let span = Span::new(input);
-    let (_, parsed) = parse_root(span).expect("Failed to parse"); // TODO: remove expect
+    // every byte decodes to some opcode, so this consumes the whole input
+    let (_, parsed) = parse_root(span, spec).expect("Failed to parse");
     parsed
 }
 
 #[cfg(test)]
 mod tests {
+    use proptest::prelude::*;
+
     use crate::block::Block;
 
-    use super::{parse, OpCode::*};
+    use super::{
+        parse, parse_bytes, parse_bytes_with_spec, parse_with_spec, OpCode::*, ParseErrorKind,
+    };
+    use crate::spec::SpecId;
 
     #[test]
     fn test_parse() {
@@ -189,7 +287,7 @@ mod tests {
         }
          */
         let bytecode = "600f8060093d393df36000356020350160005260206000f3";
-        let parsed = parse(bytecode);
+        let parsed = parse(bytecode).unwrap();
         assert_eq!(
             parsed,
             vec![
@@ -264,4 +362,72 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn test_parse_with_spec() {
+        // RETURNDATASIZE SHL LOG0 0xb0
+        let bytecode = "3d1ba0b0";
+        let opcodes = |spec| -> Vec<_> {
+            parse_with_spec(bytecode, spec)
+                .unwrap()
+                .into_iter()
+                .map(|b| b.opcode)
+                .collect()
+        };
+        assert_eq!(
+            opcodes(SpecId::Frontier),
+            vec![INVALID(0x3d), INVALID(0x1b), LOGN(0), INVALID(0xb0)]
+        );
+        assert_eq!(
+            opcodes(SpecId::Constantinople),
+            vec![RETURNDATASIZE, SHL, LOGN(0), INVALID(0xb0)]
+        );
+    }
+
+    #[test]
+    fn test_parse_hex_input() {
+        let parsed = parse(" 0x6001\n  6002\n").unwrap();
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(parsed[1], Block::new(PUSHN(1, 2u32.into()), 2));
+
+        let err = parse("6001\n60zz").unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::InvalidCharacter('z'));
+        assert_eq!((err.offset, err.line, err.column), (7, 2, 3));
+
+        let err = parse("60016").unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::OddLength);
+        assert_eq!(err.offset, 4);
+    }
+
+    #[test]
+    fn test_parse_truncated_push() {
+        // PUSH1 0x01 PUSH4 0xaabb..
+        let parsed = parse_bytes(&[0x60, 0x01, 0x63, 0xaa, 0xbb]);
+        assert_eq!(
+            parsed,
+            vec![
+                Block::new(PUSHN(1, 1u32.into()), 0),
+                Block::new(TRUNCATED_PUSHN(4, vec![0xaa, 0xbb]), 2),
+            ]
+        );
+        assert_eq!(parsed[1].opcode.push_value(), Some(0xaabb0000u32.into()));
+    }
+
+    proptest! {
+        #[test]
+        fn test_positions(code in prop::collection::vec(any::<u8>(), 0..512)) {
+            for spec in [SpecId::Frontier, SpecId::LATEST] {
+                // every byte belongs to exactly one instruction, in order
+                let mut position = 0;
+                for block in parse_bytes_with_spec(&code, spec) {
+                    prop_assert_eq!(block.position, position);
+                    let bytes = block.opcode.to_bytes();
+                    prop_assert!(!bytes.is_empty());
+                    prop_assert_eq!(&bytes[..], &code[position..position + bytes.len()]);
+                    position += bytes.len();
+                }
+                prop_assert_eq!(position, code.len());
+            }
+        }
+    }
 }
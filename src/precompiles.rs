@@ -0,0 +1,620 @@
+use std::fmt;
+
+use k256::ecdsa::{RecoveryId, Signature, VerifyingKey};
+use num_traits::{One, Zero};
+use ripemd::Ripemd160;
+use sha2::{Digest, Sha256};
+
+use crate::{gas::to_words, spec::SpecId, util::keccak256, Address, Uint256};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PrecompileError {
+    InvalidInput(&'static str),
+}
+
+impl fmt::Display for PrecompileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PrecompileError::InvalidInput(reason) => write!(f, "Invalid input: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for PrecompileError {}
+
+type PrecompileResult = Result<Vec<u8>, PrecompileError>;
+
+// a contract implemented natively, failures consume all the gas of the call
+#[derive(Debug, Clone, Copy)]
+pub struct Precompile {
+    pub name: &'static str,
+    pub gas: fn(&[u8], SpecId) -> u64,
+    pub run: fn(&[u8], SpecId) -> PrecompileResult,
+}
+
+const PRECOMPILES: [(u8, SpecId, Precompile); 10] = [
+    (
+        0x01,
+        SpecId::Frontier,
+        Precompile {
+            name: "ecrecover",
+            gas: |_, _| 3000,
+            run: ecrecover,
+        },
+    ),
+    (
+        0x02,
+        SpecId::Frontier,
+        Precompile {
+            name: "sha256",
+            gas: |input, _| 60 + 12 * to_words(input.len()),
+            run: |input, _| Ok(Sha256::digest(input).to_vec()),
+        },
+    ),
+    (
+        0x03,
+        SpecId::Frontier,
+        Precompile {
+            name: "ripemd160",
+            gas: |input, _| 600 + 120 * to_words(input.len()),
+            run: |input, _| Ok(left_pad(&Ripemd160::digest(input))),
+        },
+    ),
+    (
+        0x04,
+        SpecId::Frontier,
+        Precompile {
+            name: "identity",
+            gas: |input, _| 15 + 3 * to_words(input.len()),
+            run: |input, _| Ok(input.to_vec()),
+        },
+    ),
+    (
+        0x05,
+        SpecId::Byzantium,
+        Precompile {
+            name: "modexp",
+            gas: modexp_gas,
+            run: modexp,
+        },
+    ),
+    (
+        0x06,
+        SpecId::Byzantium,
+        Precompile {
+            name: "ecadd",
+            // EIP-1108 repriced the bn254 operations in Istanbul
+            gas: |_, spec| {
+                if spec.is_enabled_in(SpecId::Istanbul) {
+                    150
+                } else {
+                    500
+                }
+            },
+            run: bn_add,
+        },
+    ),
+    (
+        0x07,
+        SpecId::Byzantium,
+        Precompile {
+            name: "ecmul",
+            gas: |_, spec| {
+                if spec.is_enabled_in(SpecId::Istanbul) {
+                    6000
+                } else {
+                    40000
+                }
+            },
+            run: bn_mul,
+        },
+    ),
+    (
+        0x08,
+        SpecId::Byzantium,
+        Precompile {
+            name: "ecpairing",
+            gas: |input, spec| {
+                let pairs = (input.len() / 192) as u64;
+                if spec.is_enabled_in(SpecId::Istanbul) {
+                    45000 + 34000 * pairs
+                } else {
+                    100000 + 80000 * pairs
+                }
+            },
+            run: bn_pairing,
+        },
+    ),
+    (
+        0x09,
+        SpecId::Istanbul,
+        Precompile {
+            name: "blake2f",
+            gas: |input, _| match input.get(..4) {
+                Some(rounds) => u32::from_be_bytes(rounds.try_into().unwrap()) as u64,
+                None => 0,
+            },
+            run: blake2f,
+        },
+    ),
+    (
+        0x0a,
+        SpecId::Cancun,
+        Precompile {
+            name: "point_evaluation",
+            gas: |_, _| 50000,
+            run: point_evaluation,
+        },
+    ),
+];
+
+fn precompile_address(index: u8) -> Address {
+    let mut address = [0; 20];
+    address[19] = index;
+    address
+}
+
+// the precompile at `address` if the fork has it
+pub fn get(address: &Address, spec: SpecId) -> Option<Precompile> {
+    if address[..19].iter().any(|b| *b != 0) {
+        return None;
+    }
+    PRECOMPILES
+        .iter()
+        .find(|(index, since, _)| *index == address[19] && spec.is_enabled_in(*since))
+        .map(|(_, _, precompile)| *precompile)
+}
+
+// addresses of every precompile of the fork, warm from Berlin on
+pub fn addresses(spec: SpecId) -> Vec<Address> {
+    PRECOMPILES
+        .iter()
+        .filter(|(_, since, _)| spec.is_enabled_in(*since))
+        .map(|(index, _, _)| precompile_address(*index))
+        .collect()
+}
+
+// input zero padded or truncated to `len` bytes
+fn padded(input: &[u8], len: usize) -> Vec<u8> {
+    let mut data = input[..input.len().min(len)].to_vec();
+    data.resize(len, 0);
+    data
+}
+
+fn left_pad(bytes: &[u8]) -> Vec<u8> {
+    let mut word = vec![0; 32 - bytes.len()];
+    word.extend_from_slice(bytes);
+    word
+}
+
+// an invalid signature returns no output instead of failing
+fn ecrecover(input: &[u8], _: SpecId) -> PrecompileResult {
+    let input = padded(input, 128);
+    let v = &input[32..64];
+    if v[..31].iter().any(|b| *b != 0) || !matches!(v[31], 27 | 28) {
+        return Ok(vec![]);
+    }
+    let Ok(mut signature) = Signature::from_slice(&input[64..128]) else {
+        return Ok(vec![]);
+    };
+    let mut recovery_id = v[31] - 27;
+    // high s values are accepted, unlike in transactions
+    if let Some(normalized) = signature.normalize_s() {
+        signature = normalized;
+        recovery_id ^= 1;
+    }
+    let key = RecoveryId::from_byte(recovery_id)
+        .and_then(|id| VerifyingKey::recover_from_prehash(&input[..32], &signature, id).ok());
+    Ok(key.map_or(vec![], |key| {
+        let point = key.to_encoded_point(false);
+        left_pad(&keccak256(&point.as_bytes()[1..])[12..])
+    }))
+}
+
+// lengths are clamped, anything that large runs out of gas anyway
+fn modexp_lengths(input: &[u8]) -> (usize, usize, usize) {
+    let header = padded(input, 96);
+    let length = |word: &[u8]| {
+        let value = Uint256::from_bytes_be(word);
+        usize::try_from(value).unwrap_or(usize::MAX)
+    };
+    (
+        length(&header[..32]),
+        length(&header[32..64]),
+        length(&header[64..96]),
+    )
+}
+
+// bytes `offset..offset + len` of the input, zero padded past its end
+fn input_slice(input: &[u8], offset: usize, len: usize) -> Vec<u8> {
+    let start = offset.min(input.len());
+    padded(&input[start..], len)
+}
+
+fn modexp_gas(input: &[u8], spec: SpecId) -> u64 {
+    let (base_len, exp_len, mod_len) = modexp_lengths(input);
+    // bit length of the first 32 bytes of the exponent
+    let head = input_slice(input, 96_usize.saturating_add(base_len), exp_len.min(32));
+    let head_bits = Uint256::from_bytes_be(&head).bits();
+    let adjusted_exp_len = if exp_len <= 32 {
+        head_bits.saturating_sub(1)
+    } else {
+        8u64.saturating_mul(exp_len as u64 - 32)
+            .saturating_add(head_bits.saturating_sub(1))
+    }
+    .max(1) as u128;
+    let x = base_len.max(mod_len) as u128;
+
+    let gas = if spec.is_enabled_in(SpecId::Berlin) {
+        // EIP-2565
+        let words = x.div_ceil(8);
+        (words.saturating_mul(words).saturating_mul(adjusted_exp_len) / 3).max(200)
+    } else {
+        // EIP-198
+        let complexity = if x <= 64 {
+            x * x
+        } else if x <= 1024 {
+            x * x / 4 + 96 * x - 3072
+        } else {
+            (x.saturating_mul(x) / 16).saturating_add(480 * x) - 199680
+        };
+        complexity.saturating_mul(adjusted_exp_len) / 20
+    };
+    u64::try_from(gas).unwrap_or(u64::MAX)
+}
+
+fn modexp(input: &[u8], _: SpecId) -> PrecompileResult {
+    let (base_len, exp_len, mod_len) = modexp_lengths(input);
+    if mod_len == 0 {
+        return Ok(vec![]);
+    }
+    let base = Uint256::from_bytes_be(&input_slice(input, 96, base_len));
+    let exp = Uint256::from_bytes_be(&input_slice(input, 96 + base_len, exp_len));
+    let modulus = Uint256::from_bytes_be(&input_slice(input, 96 + base_len + exp_len, mod_len));
+    // modpow panics on a zero modulus
+    let result = if modulus.is_zero() || modulus.is_one() {
+        Uint256::zero()
+    } else {
+        base.modpow(&exp, &modulus)
+    };
+    let bytes = result.to_bytes_be();
+    let mut output = vec![0; mod_len];
+    if !result.is_zero() {
+        output[mod_len - bytes.len()..].copy_from_slice(&bytes);
+    }
+    Ok(output)
+}
+
+fn read_fq(bytes: &[u8]) -> Result<bn::Fq, PrecompileError> {
+    bn::Fq::from_slice(bytes).map_err(|_| PrecompileError::InvalidInput("field element"))
+}
+
+fn read_g1(bytes: &[u8]) -> Result<bn::G1, PrecompileError> {
+    use bn::Group;
+
+    let (x, y) = (read_fq(&bytes[..32])?, read_fq(&bytes[32..64])?);
+    if x.is_zero() && y.is_zero() {
+        return Ok(bn::G1::zero());
+    }
+    bn::AffineG1::new(x, y)
+        .map(Into::into)
+        .map_err(|_| PrecompileError::InvalidInput("G1 point"))
+}
+
+// imaginary part first
+fn read_g2(bytes: &[u8]) -> Result<bn::G2, PrecompileError> {
+    use bn::Group;
+
+    let x = bn::Fq2::new(read_fq(&bytes[32..64])?, read_fq(&bytes[..32])?);
+    let y = bn::Fq2::new(read_fq(&bytes[96..128])?, read_fq(&bytes[64..96])?);
+    if x.is_zero() && y.is_zero() {
+        return Ok(bn::G2::zero());
+    }
+    bn::AffineG2::new(x, y)
+        .map(Into::into)
+        .map_err(|_| PrecompileError::InvalidInput("G2 point"))
+}
+
+fn write_g1(point: bn::G1) -> Vec<u8> {
+    let mut output = vec![0; 64];
+    if let Some(point) = bn::AffineG1::from_jacobian(point) {
+        point.x().to_big_endian(&mut output[..32]).unwrap();
+        point.y().to_big_endian(&mut output[32..]).unwrap();
+    }
+    output
+}
+
+fn bn_add(input: &[u8], _: SpecId) -> PrecompileResult {
+    let input = padded(input, 128);
+    let (a, b) = (read_g1(&input[..64])?, read_g1(&input[64..])?);
+    Ok(write_g1(a + b))
+}
+
+fn bn_mul(input: &[u8], _: SpecId) -> PrecompileResult {
+    let input = padded(input, 96);
+    let point = read_g1(&input[..64])?;
+    let scalar =
+        bn::Fr::from_slice(&input[64..]).map_err(|_| PrecompileError::InvalidInput("scalar"))?;
+    Ok(write_g1(point * scalar))
+}
+
+fn bn_pairing(input: &[u8], _: SpecId) -> PrecompileResult {
+    if input.len() % 192 != 0 {
+        return Err(PrecompileError::InvalidInput("pairing input length"));
+    }
+    let pairs = input
+        .chunks(192)
+        .map(|chunk| Ok((read_g1(&chunk[..64])?, read_g2(&chunk[64..])?)))
+        .collect::<Result<Vec<_>, PrecompileError>>()?;
+    let success = bn::pairing_batch(&pairs) == bn::Gt::one();
+    Ok(left_pad(&[u8::from(success)]))
+}
+
+const BLAKE2B_IV: [u64; 8] = [
+    0x6a09e667f3bcc908,
+    0xbb67ae8584caa73b,
+    0x3c6ef372fe94f82b,
+    0xa54ff53a5f1d36f1,
+    0x510e527fade682d1,
+    0x9b05688c2b3e6c1f,
+    0x1f83d9abfb41bd6b,
+    0x5be0cd19137e2179,
+];
+
+const BLAKE2B_SIGMA: [[usize; 16]; 10] = [
+    [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15],
+    [14, 10, 4, 8, 9, 15, 13, 6, 1, 12, 0, 2, 11, 7, 5, 3],
+    [11, 8, 12, 0, 5, 2, 15, 13, 10, 14, 3, 6, 7, 1, 9, 4],
+    [7, 9, 3, 1, 13, 12, 11, 14, 2, 6, 5, 10, 4, 0, 15, 8],
+    [9, 0, 5, 7, 2, 4, 10, 15, 14, 1, 11, 12, 6, 8, 3, 13],
+    [2, 12, 6, 10, 0, 11, 8, 3, 4, 13, 7, 5, 15, 14, 1, 9],
+    [12, 5, 1, 15, 14, 13, 4, 10, 0, 7, 6, 3, 9, 2, 8, 11],
+    [13, 11, 7, 14, 12, 1, 3, 9, 5, 0, 15, 4, 8, 6, 2, 10],
+    [6, 15, 14, 9, 11, 3, 0, 8, 12, 2, 13, 7, 1, 4, 10, 5],
+    [10, 2, 8, 4, 7, 6, 1, 5, 15, 11, 9, 14, 3, 12, 13, 0],
+];
+
+fn blake2b_mix(v: &mut [u64; 16], [a, b, c, d]: [usize; 4], x: u64, y: u64) {
+    v[a] = v[a].wrapping_add(v[b]).wrapping_add(x);
+    v[d] = (v[d] ^ v[a]).rotate_right(32);
+    v[c] = v[c].wrapping_add(v[d]);
+    v[b] = (v[b] ^ v[c]).rotate_right(24);
+    v[a] = v[a].wrapping_add(v[b]).wrapping_add(y);
+    v[d] = (v[d] ^ v[a]).rotate_right(16);
+    v[c] = v[c].wrapping_add(v[d]);
+    v[b] = (v[b] ^ v[c]).rotate_right(63);
+}
+
+// the BLAKE2b compression function F with a configurable number of rounds (EIP-152)
+fn blake2b_compress(rounds: u32, h: &mut [u64; 8], m: &[u64; 16], t: [u64; 2], last: bool) {
+    let mut v = [0; 16];
+    v[..8].copy_from_slice(h);
+    v[8..].copy_from_slice(&BLAKE2B_IV);
+    v[12] ^= t[0];
+    v[13] ^= t[1];
+    if last {
+        v[14] = !v[14];
+    }
+    for round in 0..rounds as usize {
+        let s = &BLAKE2B_SIGMA[round % 10];
+        blake2b_mix(&mut v, [0, 4, 8, 12], m[s[0]], m[s[1]]);
+        blake2b_mix(&mut v, [1, 5, 9, 13], m[s[2]], m[s[3]]);
+        blake2b_mix(&mut v, [2, 6, 10, 14], m[s[4]], m[s[5]]);
+        blake2b_mix(&mut v, [3, 7, 11, 15], m[s[6]], m[s[7]]);
+        blake2b_mix(&mut v, [0, 5, 10, 15], m[s[8]], m[s[9]]);
+        blake2b_mix(&mut v, [1, 6, 11, 12], m[s[10]], m[s[11]]);
+        blake2b_mix(&mut v, [2, 7, 8, 13], m[s[12]], m[s[13]]);
+        blake2b_mix(&mut v, [3, 4, 9, 14], m[s[14]], m[s[15]]);
+    }
+    for i in 0..8 {
+        h[i] ^= v[i] ^ v[i + 8];
+    }
+}
+
+fn blake2f(input: &[u8], _: SpecId) -> PrecompileResult {
+    if input.len() != 213 {
+        return Err(PrecompileError::InvalidInput("blake2f input length"));
+    }
+    let last = match input[212] {
+        0 => false,
+        1 => true,
+        _ => return Err(PrecompileError::InvalidInput("blake2f final flag")),
+    };
+    let rounds = u32::from_be_bytes(input[..4].try_into().unwrap());
+    let words: Vec<u64> = input[4..212]
+        .chunks(8)
+        .map(|word| u64::from_le_bytes(word.try_into().unwrap()))
+        .collect();
+    let mut h: [u64; 8] = words[..8].try_into().unwrap();
+    let m: [u64; 16] = words[8..24].try_into().unwrap();
+    blake2b_compress(rounds, &mut h, &m, [words[24], words[25]], last);
+    Ok(h.iter().flat_map(|word| word.to_le_bytes()).collect())
+}
+
+// BLS12-381 scalar field modulus
+const BLS_MODULUS: &str = "73eda753299d7d483339d80809a1d80553bda402fffe5bfeffffffff00000001";
+const FIELD_ELEMENTS_PER_BLOB: u32 = 4096;
+
+// EIP-4844, versioned hash, z, y, commitment, proof
+fn point_evaluation(input: &[u8], _: SpecId) -> PrecompileResult {
+    if input.len() != 192 {
+        return Err(PrecompileError::InvalidInput(
+            "point evaluation input length",
+        ));
+    }
+    let commitment: [u8; 48] = input[96..144].try_into().unwrap();
+    let mut versioned_hash = Sha256::digest(commitment).to_vec();
+    versioned_hash[0] = 0x01;
+    if input[..32] != versioned_hash[..] {
+        return Err(PrecompileError::InvalidInput("versioned hash mismatch"));
+    }
+    let z: [u8; 32] = input[32..64].try_into().unwrap();
+    let y: [u8; 32] = input[64..96].try_into().unwrap();
+    let proof: [u8; 48] = input[144..192].try_into().unwrap();
+    let settings = c_kzg::ethereum_kzg_settings(0);
+    let valid = settings
+        .verify_kzg_proof(&commitment.into(), &z.into(), &y.into(), &proof.into())
+        .unwrap_or(false);
+    if !valid {
+        return Err(PrecompileError::InvalidInput("invalid KZG proof"));
+    }
+    let mut output = left_pad(&FIELD_ELEMENTS_PER_BLOB.to_be_bytes());
+    output.extend(hex::decode(BLS_MODULUS).unwrap());
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{addresses, get, precompile_address};
+    use crate::spec::SpecId;
+
+    fn run(index: u8, input: &str, spec: SpecId) -> (u64, String) {
+        let precompile = get(&precompile_address(index), spec).unwrap();
+        let input = hex::decode(input).unwrap();
+        let output = (precompile.run)(&input, spec).unwrap();
+        ((precompile.gas)(&input, spec), hex::encode(output))
+    }
+
+    #[test]
+    fn test_registry() {
+        assert_eq!(addresses(SpecId::Frontier).len(), 4);
+        assert_eq!(addresses(SpecId::Byzantium).len(), 8);
+        assert_eq!(addresses(SpecId::Cancun).len(), 10);
+        assert!(get(&precompile_address(9), SpecId::Petersburg).is_none());
+        assert_eq!(
+            get(&precompile_address(2), SpecId::Frontier).unwrap().name,
+            "sha256"
+        );
+    }
+
+    #[test]
+    fn test_precompiles() {
+        let input = [
+            "456e9aea5e197a1f1af7a3e85a3212fa4049a3ba34c2289b4c860fc0b0c64ef3",
+            "000000000000000000000000000000000000000000000000000000000000001c",
+            "9242685bf161793cc25603c231bc2f568eb630ea16aa137d2664ac8038825608",
+            "4f8ae3bd7535248d0bd448298cc2e2071e56992d0774dc340c368ae950852ada",
+        ]
+        .concat();
+        assert_eq!(
+            run(1, &input, SpecId::Cancun),
+            (
+                3000,
+                "0000000000000000000000007156526fbd7a3c72969b54f64e42c10fbb768c8a".into()
+            )
+        );
+        assert_eq!(
+            run(2, "", SpecId::Cancun).1,
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        // 3 ** 5 % 7
+        let input = [
+            "0000000000000000000000000000000000000000000000000000000000000001",
+            "0000000000000000000000000000000000000000000000000000000000000001",
+            "0000000000000000000000000000000000000000000000000000000000000001",
+            "030507",
+        ]
+        .concat();
+        assert_eq!(run(5, &input, SpecId::Cancun), (200, "05".into()));
+        // 1 * G = G
+        let input = [
+            "0000000000000000000000000000000000000000000000000000000000000001",
+            "0000000000000000000000000000000000000000000000000000000000000002",
+            "0000000000000000000000000000000000000000000000000000000000000001",
+        ]
+        .concat();
+        assert_eq!(run(7, &input, SpecId::Cancun).1, input[..128]);
+        // an empty pairing check succeeds
+        assert_eq!(run(8, "", SpecId::Cancun), (45000, format!("{:064x}", 1)));
+    }
+
+    #[test]
+    fn test_hashes_and_identity() {
+        assert_eq!(
+            run(3, "", SpecId::Cancun),
+            (
+                600,
+                format!("{:0>64}", "9c1185a5c5e9fc54612808977ee8f548b2258d31")
+            )
+        );
+        assert_eq!(run(4, "c0ffee", SpecId::Cancun), (18, "c0ffee".into()));
+        // G + G = 2G
+        let g = [
+            "0000000000000000000000000000000000000000000000000000000000000001",
+            "0000000000000000000000000000000000000000000000000000000000000002",
+        ]
+        .concat();
+        assert_eq!(
+            run(6, &g.repeat(2), SpecId::Cancun),
+            (
+                150,
+                [
+                    "030644e72e131a029b85045b68181585d97816a916871ca8d3c208c16d87cfd3",
+                    "15ed738c0e0a7c92e7845f96b2ae9c0a68a6a449e3538fc7ff3ebf7a5a18a2c4",
+                ]
+                .concat()
+            )
+        );
+    }
+
+    #[test]
+    fn test_point_evaluation() {
+        // the zero polynomial evaluates to 0 at 0, commitment and proof are the point at infinity
+        let infinity = format!("c0{}", "00".repeat(47));
+        let input = [
+            "010657f37554c781402a22917dee2f75def7ab966d7b770905398eba3c444014",
+            &"00".repeat(64),
+            &infinity,
+            &infinity,
+        ]
+        .concat();
+        assert_eq!(
+            run(0x0a, &input, SpecId::Cancun),
+            (
+                50000,
+                [
+                    "0000000000000000000000000000000000000000000000000000000000001000",
+                    "73eda753299d7d483339d80809a1d80553bda402fffe5bfeffffffff00000001",
+                ]
+                .concat()
+            )
+        );
+        // a different claimed value fails
+        let input = input.replacen(
+            &"00".repeat(64),
+            &format!("{}{:064x}", "00".repeat(32), 1),
+            1,
+        );
+        let precompile = get(&precompile_address(0x0a), SpecId::Cancun).unwrap();
+        assert!((precompile.run)(&hex::decode(input).unwrap(), SpecId::Cancun).is_err());
+    }
+
+    #[test]
+    fn test_blake2f() {
+        // EIP-152 test vector 5, "abc" with 12 rounds
+        let input = [
+            "0000000c",
+            "48c9bdf267e6096a3ba7ca8485ae67bb2bf894fe72f36e3cf1361d5f3af54fa5",
+            "d182e6ad7f520e511f6c3e2b8c68059b6bbd41fbabd9831f79217e1319cde05b",
+            "6162630000000000000000000000000000000000000000000000000000000000",
+            "0000000000000000000000000000000000000000000000000000000000000000",
+            "0000000000000000000000000000000000000000000000000000000000000000",
+            "0000000000000000000000000000000000000000000000000000000000000000",
+            "0300000000000000",
+            "0000000000000000",
+            "01",
+        ]
+        .concat();
+        assert_eq!(
+            run(9, &input, SpecId::Cancun),
+            (
+                12,
+                [
+                    "ba80a53f981c4d0d6a2797b69f12f6e94c212f14685ac4b74b12bb6fdbffa2d1",
+                    "7d87c5392aab792dc252d5de4533cc9518d38aa8dbf1925ab92386edd4009923",
+                ]
+                .concat()
+            )
+        );
+    }
+}
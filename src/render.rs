@@ -0,0 +1,268 @@
+use std::{collections::BTreeMap, fmt::Write as _};
+
+use crate::{
+    analysis::jumps::{block_index, jump_targets, reachable},
+    block::Block,
+    formatter::{mnemonic, stack_effect, FormatOptions},
+    opcode::OpCode,
+};
+
+// lanes of jump arrows drawn left of the code, further jumps are only named
+const MAX_LANES: usize = 8;
+
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+const DIM: &str = "\x1b[2m";
+const RED: &str = "\x1b[31m";
+const GREEN: &str = "\x1b[32m";
+const YELLOW: &str = "\x1b[33m";
+const BLUE: &str = "\x1b[34m";
+const MAGENTA: &str = "\x1b[35m";
+const CYAN: &str = "\x1b[36m";
+const GRAY: &str = "\x1b[90m";
+
+// color of the instruction's class
+fn op_color(op: &OpCode) -> Option<&'static str> {
+    use OpCode::*;
+
+    match op {
+        STOP | JUMP | JUMPI | JUMPDEST | RETURN | REVERT | INVALID(_) | SELFDESTRUCT => {
+            Some(MAGENTA)
+        }
+        SLOAD | SSTORE | TLOAD | TSTORE => Some(YELLOW),
+        CALL | CALLCODE | DELEGATECALL | STATICCALL | CREATE | CREATE2 => Some(RED),
+        ADD | MUL | SUB | DIV | SDIV | MOD | SMOD | ADDMOD | MULMOD | EXP | SIGNEXTEND | LT
+        | GT | SLT | SGT | EQ | ISZERO | AND | OR | XOR | NOT | BYTE | SHL | SHR | SAR => {
+            Some(CYAN)
+        }
+        LOGN(_) => Some(BLUE),
+        _ => None,
+    }
+}
+
+// a jump with a known destination, as rows of the listing
+#[derive(Debug, Clone, Copy)]
+struct Arrow {
+    from: usize,
+    to: usize,
+}
+
+impl Arrow {
+    fn top(&self) -> usize {
+        self.from.min(self.to)
+    }
+
+    fn bottom(&self) -> usize {
+        self.from.max(self.to)
+    }
+}
+
+// shorter jumps get the lanes closest to the code
+fn assign_lanes(mut arrows: Vec<Arrow>) -> Vec<Vec<Arrow>> {
+    arrows.sort_by_key(|a| a.bottom() - a.top());
+    let mut lanes: Vec<Vec<Arrow>> = vec![];
+    for arrow in arrows {
+        let overlaps = |lane: &Vec<Arrow>| {
+            lane.iter()
+                .any(|a| a.top() <= arrow.bottom() && arrow.top() <= a.bottom())
+        };
+        if let Some(lane) = lanes.iter_mut().find(|lane| !overlaps(lane)) {
+            lane.push(arrow);
+        } else if lanes.len() < MAX_LANES {
+            lanes.push(vec![arrow]);
+        }
+    }
+    lanes
+}
+
+struct Renderer<'a> {
+    blocks: &'a [Block],
+    options: &'a FormatOptions,
+    // outermost lane first
+    lanes: Vec<Vec<Arrow>>,
+}
+
+impl Renderer<'_> {
+    fn paint(&self, text: &str, color: Option<&str>) -> String {
+        match color {
+            Some(color) if self.options.color && !text.is_empty() => {
+                format!("{}{}{}", color, text, RESET)
+            }
+            _ => text.to_string(),
+        }
+    }
+
+    // arrows column of the instruction at `row`
+    fn gutter(&self, row: usize) -> String {
+        let mut result = String::new();
+        let mut horizontal = false;
+        for lane in &self.lanes {
+            let arrow = lane.iter().find(|a| a.top() <= row && row <= a.bottom());
+            let c = match arrow {
+                Some(a) if a.top() == row => '╭',
+                Some(a) if a.bottom() == row => '╰',
+                Some(_) if horizontal => '┼',
+                Some(_) => '│',
+                None if horizontal => '─',
+                None => ' ',
+            };
+            horizontal |= arrow.is_some_and(|a| a.from == row || a.to == row);
+            result.push(c);
+        }
+        let is_target = self.lanes.iter().flatten().any(|a| a.to == row);
+        result.push(match (is_target, horizontal) {
+            (true, _) => '▶',
+            (false, true) => '─',
+            (false, false) => ' ',
+        });
+        result
+    }
+
+    // arrows column of a line printed before the instruction at `row`
+    fn gutter_before(&self, row: usize) -> String {
+        let mut result: String = self
+            .lanes
+            .iter()
+            .map(|lane| {
+                if lane.iter().any(|a| a.top() < row && row <= a.bottom()) {
+                    '│'
+                } else {
+                    ' '
+                }
+            })
+            .collect();
+        result.push(' ');
+        result
+    }
+}
+
+// colored listing for terminals: named jump destinations, jump arrows and unreachable code
+pub fn render(
+    blocks: &[Block],
+    labels: &BTreeMap<usize, String>,
+    names: &[Option<&str>],
+    comments: &BTreeMap<usize, String>,
+    options: &FormatOptions,
+) -> String {
+    let targets = jump_targets(blocks);
+    let reached = reachable(blocks);
+    let is_jumpdest = |position: &usize| {
+        block_index(blocks, *position).is_some_and(|i| blocks[i].opcode == OpCode::JUMPDEST)
+    };
+    let arrows = targets
+        .iter()
+        .filter(|(_, target)| is_jumpdest(target))
+        .map(|(from, target)| Arrow {
+            from: *from,
+            to: block_index(blocks, *target).unwrap(),
+        })
+        .collect();
+    let mut lanes = assign_lanes(arrows);
+    lanes.reverse();
+    let renderer = Renderer {
+        blocks,
+        options,
+        lanes,
+    };
+    let label = |position: usize| match labels.get(&position) {
+        Some(label) => label.clone(),
+        None => format!("loc_{:x}", position),
+    };
+
+    let mut result = String::new();
+    for (i, b) in renderer.blocks.iter().enumerate() {
+        if b.opcode == OpCode::JUMPDEST || labels.contains_key(&b.position) {
+            let _ = writeln!(
+                result,
+                "{}{}",
+                renderer.gutter_before(i),
+                renderer.paint(&format!("{}:", label(b.position)), Some(BOLD))
+            );
+        }
+        if let Some(comment) = comments.get(&b.position) {
+            let _ = writeln!(
+                result,
+                "{}{}",
+                renderer.gutter_before(i),
+                renderer.paint(&format!("// {}", comment), Some(GRAY))
+            );
+        }
+
+        let mut notes = vec![];
+        if options.stack_effect {
+            notes.push(stack_effect(&b.opcode));
+        }
+        notes.extend(names[i].map(String::from));
+        match targets.get(&i) {
+            Some(target) if is_jumpdest(target) => notes.push(format!("-> {}", label(*target))),
+            Some(target) => notes.push(format!("-> 0x{:x} (not a JUMPDEST)", target)),
+            None => {}
+        }
+        let unreachable = !reached[i];
+        if unreachable && (i == 0 || reached[i - 1]) {
+            notes.push("unreachable".into());
+        }
+
+        let mut line = format!("{:08x}  ", b.position);
+        if options.bytes {
+            let _ = write!(line, "{:<12}  ", hex::encode(b.opcode.to_bytes()));
+        }
+        let line = renderer.paint(&line, Some(GRAY));
+        let name = format!("{:<14}", mnemonic(&b.opcode));
+        let operand = match &b.opcode {
+            OpCode::PUSHN(_, v) => format!("0x{:x}", v),
+            OpCode::TRUNCATED_PUSHN(_, bytes) => format!("0x{} (truncated)", hex::encode(bytes)),
+            _ => String::new(),
+        };
+        let mut text = if unreachable {
+            renderer.paint(&format!("{}{}", name, operand), Some(DIM))
+        } else {
+            renderer.paint(&name, op_color(&b.opcode)) + &renderer.paint(&operand, Some(GREEN))
+        };
+        if !notes.is_empty() {
+            let padding = " ".repeat(24usize.saturating_sub(name.len() + operand.len()));
+            text += &padding;
+            text += &renderer.paint(&format!("// {}", notes.join(", ")), Some(GRAY));
+        }
+        let _ = writeln!(result, "{}{}{}", renderer.gutter(i), line, text.trim_end());
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::render;
+    use crate::{formatter::FormatOptions, parser::parse};
+
+    #[test]
+    fn test_render() {
+        // PUSH1 6 JUMP, PUSH1 0 STOP, JUMPDEST CALLER SLOAD STOP
+        let blocks = parse("6006566000005b335400").unwrap();
+        let labels = BTreeMap::from([(6, "transfer(address,uint256)".to_string())]);
+        let names = vec![None; blocks.len()];
+        let options = FormatOptions::default();
+        let text = render(&blocks, &labels, &names, &BTreeMap::new(), &options);
+        let lines: Vec<_> = text.lines().collect();
+        assert_eq!(lines[0], "  00000000  PUSH1         0x6");
+        assert_eq!(
+            lines[1],
+            "╭─00000002  JUMP                    // -> transfer(address,uint256)"
+        );
+        assert_eq!(
+            lines[2],
+            "│ 00000003  PUSH1         0x0       // unreachable"
+        );
+        assert_eq!(lines[4], "│ transfer(address,uint256):");
+        assert_eq!(lines[5], "╰▶00000006  JUMPDEST");
+        assert!(!text.contains('\x1b'));
+
+        let options = FormatOptions {
+            color: true,
+            ..Default::default()
+        };
+        let text = render(&blocks, &labels, &names, &BTreeMap::new(), &options);
+        assert!(text.contains("\x1b[33mSLOAD"));
+    }
+}
@@ -0,0 +1,60 @@
+use crate::Uint256;
+
+// length prefix of a string (0x80) or list (0xc0) payload
+fn prefix(len: usize, offset: u8) -> Vec<u8> {
+    if len < 56 {
+        return vec![offset + len as u8];
+    }
+    let len_bytes = trim_zeros(&len.to_be_bytes());
+    let mut prefix = vec![offset + 55 + len_bytes.len() as u8];
+    prefix.extend_from_slice(&len_bytes);
+    prefix
+}
+
+fn trim_zeros(bytes: &[u8]) -> Vec<u8> {
+    bytes.iter().copied().skip_while(|b| *b == 0).collect()
+}
+
+pub fn encode_bytes(bytes: &[u8]) -> Vec<u8> {
+    match bytes {
+        [b] if *b < 0x80 => vec![*b],
+        _ => [prefix(bytes.len(), 0x80), bytes.to_vec()].concat(),
+    }
+}
+
+// integers are big endian without leading zeros, zero is the empty string
+pub fn encode_uint(value: &Uint256) -> Vec<u8> {
+    encode_bytes(&trim_zeros(&value.to_bytes_be()))
+}
+
+pub fn encode_u64(value: u64) -> Vec<u8> {
+    encode_bytes(&trim_zeros(&value.to_be_bytes()))
+}
+
+// `items` are already encoded
+pub fn encode_list(items: &[Vec<u8>]) -> Vec<u8> {
+    let payload = items.concat();
+    [prefix(payload.len(), 0xc0), payload].concat()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{encode_bytes, encode_list, encode_u64, encode_uint};
+
+    #[test]
+    fn test_rlp() {
+        assert_eq!(encode_bytes(b"dog"), b"\x83dog");
+        assert_eq!(encode_bytes(&[]), [0x80]);
+        assert_eq!(encode_bytes(&[0x0f]), [0x0f]);
+        assert_eq!(encode_u64(0), [0x80]);
+        assert_eq!(encode_u64(1024), [0x82, 0x04, 0x00]);
+        assert_eq!(encode_uint(&0x7fu32.into()), [0x7f]);
+        assert_eq!(
+            encode_list(&[encode_bytes(b"cat"), encode_bytes(b"dog")]),
+            b"\xc8\x83cat\x83dog"
+        );
+        assert_eq!(encode_list(&[]), [0xc0]);
+        let long = encode_bytes(&[b'a'; 56]);
+        assert_eq!(long[..2], [0xb8, 56]);
+    }
+}
@@ -0,0 +1,164 @@
+use std::{collections::HashMap, fs, path::Path};
+
+use anyhow::Result;
+
+use crate::{
+    abi::json::Abi,
+    util::{keccak256, to_bytes32},
+    Uint256,
+};
+
+const BUNDLED: &str = include_str!("signatures.txt");
+
+pub fn selector(signature: &str) -> u32 {
+    let hash = keccak256(signature.as_bytes());
+    u32::from_be_bytes([hash[0], hash[1], hash[2], hash[3]])
+}
+
+pub fn topic(signature: &str) -> [u8; 32] {
+    keccak256(signature.as_bytes())
+}
+
+// text signatures of functions, errors and events keyed by selector and topic0
+#[derive(Debug, Clone, Default)]
+pub struct SignatureDb {
+    // several signatures can share a selector
+    functions: HashMap<u32, Vec<String>>,
+    events: HashMap<[u8; 32], String>,
+}
+
+impl SignatureDb {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // signatures shipped with the crate
+    pub fn bundled() -> Self {
+        let mut db = Self::new();
+        db.load_text(BUNDLED);
+        db
+    }
+
+    pub fn insert_function(&mut self, signature: &str) {
+        let names = self.functions.entry(selector(signature)).or_default();
+        if !names.iter().any(|n| n == signature) {
+            names.push(signature.to_string());
+        }
+    }
+
+    pub fn insert_event(&mut self, signature: &str) {
+        self.events.insert(topic(signature), signature.to_string());
+    }
+
+    // one signature per line, `#` starts a comment
+    pub fn load_text(&mut self, text: &str) {
+        for line in text.lines() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            // a bare signature may name either a function or an event
+            self.insert_function(line);
+            self.insert_event(line);
+        }
+    }
+
+    pub fn insert_abi(&mut self, abi: &Abi) {
+        for f in &abi.functions {
+            self.insert_function(&f.signature());
+        }
+        for e in &abi.errors {
+            self.insert_function(&e.signature());
+        }
+        for e in &abi.events {
+            self.insert_event(&e.signature());
+        }
+    }
+
+    pub fn load_abi_json(&mut self, json: &str) -> Result<()> {
+        self.insert_abi(&Abi::from_json(json)?);
+        Ok(())
+    }
+
+    // `.json` files are read as ABIs, anything else as text signatures
+    pub fn load_file(&mut self, path: &Path) -> Result<()> {
+        let content = fs::read_to_string(path)?;
+        if path.extension().is_some_and(|e| e == "json") {
+            self.load_abi_json(&content)
+        } else {
+            self.load_text(&content);
+            Ok(())
+        }
+    }
+
+    pub fn function(&self, selector: u32) -> Option<&str> {
+        self.functions
+            .get(&selector)
+            .and_then(|names| names.first())
+            .map(|n| n.as_str())
+    }
+
+    pub fn functions(&self, selector: u32) -> &[String] {
+        self.functions.get(&selector).map_or(&[], |n| n.as_slice())
+    }
+
+    pub fn event(&self, topic: &Uint256) -> Option<&str> {
+        self.events.get(&to_bytes32(topic)).map(|n| n.as_str())
+    }
+
+    // name of the function called by `calldata`
+    pub fn function_of_calldata(&self, calldata: &[u8]) -> Option<&str> {
+        let selector = u32::from_be_bytes(calldata.get(..4)?.try_into().unwrap());
+        self.function(selector)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{selector, topic, SignatureDb};
+    use crate::Uint256;
+
+    #[test]
+    fn test_bundled_signatures() {
+        let db = SignatureDb::bundled();
+        assert_eq!(selector("transfer(address,uint256)"), 0xa9059cbb);
+        assert_eq!(db.function(0xa9059cbb), Some("transfer(address,uint256)"));
+        let transfer = topic("Transfer(address,address,uint256)");
+        assert_eq!(
+            hex::encode(transfer),
+            "ddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef"
+        );
+        assert_eq!(
+            db.event(&Uint256::from_bytes_be(&transfer)),
+            Some("Transfer(address,address,uint256)")
+        );
+    }
+
+    #[test]
+    fn test_load_abi_json() {
+        let mut db = SignatureDb::new();
+        db.load_abi_json(
+            r#"[
+                {"type": "function", "name": "add", "inputs": [
+                    {"name": "a", "type": "uint256"}, {"name": "b", "type": "uint256"}
+                ]},
+                {"type": "function", "name": "submit", "inputs": [
+                    {"name": "orders", "type": "tuple[]", "components": [
+                        {"name": "to", "type": "address"}, {"name": "data", "type": "bytes"}
+                    ]}
+                ]},
+                {"type": "event", "name": "Added", "inputs": [
+                    {"name": "sum", "type": "uint256", "indexed": false}
+                ]}
+            ]"#,
+        )
+        .unwrap();
+        assert_eq!(db.function(0x771602f7), Some("add(uint256,uint256)"));
+        assert_eq!(
+            db.function(selector("submit((address,bytes)[])")),
+            Some("submit((address,bytes)[])")
+        );
+        let added = Uint256::from_bytes_be(&topic("Added(uint256)"));
+        assert_eq!(db.event(&added), Some("Added(uint256)"));
+    }
+}
@@ -0,0 +1,763 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    io::{BufRead, BufReader, Write},
+    process::{Child, ChildStdin, ChildStdout, Command, Stdio},
+    rc::Rc,
+    str::FromStr,
+};
+
+use anyhow::{anyhow, Context, Result};
+use num_traits::{One, Zero};
+
+use crate::{
+    emulator::{Halt, VmError},
+    formatter::mnemonic,
+    opcode::OpCode,
+    spec::SpecId,
+    symbolic::{
+        explore_with, Constraint, End, Expr, Input, Limits, Path, Storage, Word, CALLDATASIZE,
+    },
+    Uint256,
+};
+
+pub const DEFAULT_SOLVER: &str = "z3 -in";
+
+// calldata of a model is at most this long
+pub const MAX_CALLDATA: usize = 4096;
+
+// selector of Solidity's Panic(uint256)
+const PANIC_SELECTOR: [u8; 4] = [0x4e, 0x48, 0x7b, 0x71];
+
+const WORD: &str = "(_ BitVec 256)";
+
+fn hex256(value: &Uint256) -> String {
+    format!("#x{:064x}", value)
+}
+
+fn quote(name: &str) -> String {
+    format!("|{}|", name)
+}
+
+// a 0 or 1 word
+fn word_of(condition: String) -> String {
+    format!(
+        "(ite {} {} {})",
+        condition,
+        hex256(&Uint256::one()),
+        hex256(&Uint256::zero())
+    )
+}
+
+// SMT-LIB2 declarations and assertions over bitvectors, each shared subterm defined once
+#[derive(Debug)]
+pub struct Script {
+    lines: Vec<String>,
+    // names of the words and storages already defined, by address, holding on to them so
+    // the address is not reused by another expression
+    terms: HashMap<*const Expr, (Word, String)>,
+    storages: HashMap<*const Storage, (Rc<Storage>, String)>,
+    declared: HashSet<String>,
+    // inputs named like `caller`, calldatasize included
+    pub vars: Vec<String>,
+}
+
+impl Default for Script {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Script {
+    pub fn new() -> Self {
+        let byte = |i: usize| {
+            let index = format!("(bvadd i {})", hex256(&i.into()));
+            format!("(calldatabyte {})", index)
+        };
+        let lines = vec![
+            "(set-option :produce-models true)".to_string(),
+            "(set-logic QF_AUFBV)".to_string(),
+            "(declare-const calldata (Array (_ BitVec 256) (_ BitVec 8)))".to_string(),
+            format!("(declare-const {} {})", quote(CALLDATASIZE), WORD),
+            format!(
+                "(assert (bvule {} {}))",
+                quote(CALLDATASIZE),
+                hex256(&MAX_CALLDATA.into())
+            ),
+            format!(
+                "(define-fun calldatabyte ((i {})) (_ BitVec 8) (ite (bvult i {}) (select calldata i) #x00))",
+                WORD,
+                quote(CALLDATASIZE)
+            ),
+            format!(
+                "(define-fun calldataload ((i {})) {} (concat {}))",
+                WORD,
+                WORD,
+                (0..32).map(byte).collect::<Vec<_>>().join(" ")
+            ),
+        ];
+        Self {
+            lines,
+            terms: HashMap::new(),
+            storages: HashMap::new(),
+            declared: HashSet::new(),
+            vars: vec![CALLDATASIZE.to_string()],
+        }
+    }
+
+    fn declare(&mut self, name: &str, declaration: String) {
+        if self.declared.insert(name.to_string()) {
+            self.lines.push(declaration);
+        }
+    }
+
+    // uninterpreted function named after `op`
+    fn function(&mut self, op: &OpCode, args: &[String]) -> String {
+        let name = quote(&mnemonic(op).to_lowercase());
+        let sorts = vec![WORD; args.len()].join(" ");
+        self.declare(
+            &name,
+            format!("(declare-fun {} ({}) {})", name, sorts, WORD),
+        );
+        format!("({} {})", name, args.join(" "))
+    }
+
+    fn operation(&mut self, op: &OpCode, words: &[Word]) -> String {
+        let args: Vec<_> = words.iter().map(|w| self.term(w)).collect();
+        let zero = hex256(&Uint256::zero());
+        let (a, b) = (&args[0], args.get(1).cloned().unwrap_or_default());
+        let unless_zero = |divisor: &str, result: String| {
+            format!("(ite (= {} {}) {} {})", divisor, zero, zero, result)
+        };
+        let binary = |name: &str| format!("({} {} {})", name, a, b);
+        match op {
+            OpCode::ADD => binary("bvadd"),
+            OpCode::MUL => binary("bvmul"),
+            OpCode::SUB => binary("bvsub"),
+            OpCode::DIV => unless_zero(&b, binary("bvudiv")),
+            OpCode::SDIV => unless_zero(&b, binary("bvsdiv")),
+            OpCode::MOD => unless_zero(&b, binary("bvurem")),
+            OpCode::SMOD => unless_zero(&b, binary("bvsrem")),
+            OpCode::ADDMOD | OpCode::MULMOD => {
+                // in 512 bits so nothing wraps
+                let n = &args[2];
+                let wide = |x: &str| format!("((_ zero_extend 256) {})", x);
+                let name = if *op == OpCode::ADDMOD { "bvadd" } else { "bvmul" };
+                let result = format!(
+                    "((_ extract 255 0) (bvurem ({} {} {}) {}))",
+                    name,
+                    wide(a),
+                    wide(&b),
+                    wide(n)
+                );
+                unless_zero(n, result)
+            }
+            OpCode::EXP => match words[1].constant().and_then(|e| u32::try_from(e).ok()) {
+                // small constant exponents are multiplied out
+                Some(exponent) if exponent <= 32 => {
+                    let mut result = hex256(&Uint256::one());
+                    for _ in 0..exponent {
+                        result = format!("(bvmul {} {})", result, a);
+                    }
+                    result
+                }
+                _ if words[0].constant() == Some(&2u32.into()) => {
+                    format!("(bvshl {} {})", hex256(&Uint256::one()), b)
+                }
+                _ => self.function(op, &args),
+            },
+            OpCode::SIGNEXTEND => match words[0].constant() {
+                Some(k) if *k < 31u32.into() => {
+                    let k = usize::try_from(k).unwrap();
+                    format!(
+                    "((_ sign_extend {}) ((_ extract {} 0) {}))",
+                    256 - 8 * (k + 1),
+                    8 * k + 7,
+                    b
+                )
+                }
+                Some(_) => b,
+                None => self.function(op, &args),
+            },
+            OpCode::LT => word_of(binary("bvult")),
+            OpCode::GT => word_of(binary("bvugt")),
+            OpCode::SLT => word_of(binary("bvslt")),
+            OpCode::SGT => word_of(binary("bvsgt")),
+            OpCode::EQ => word_of(binary("=")),
+            OpCode::ISZERO => word_of(format!("(= {} {})", a, zero)),
+            OpCode::AND => binary("bvand"),
+            OpCode::OR => binary("bvor"),
+            OpCode::XOR => binary("bvxor"),
+            OpCode::NOT => format!("(bvnot {})", a),
+            OpCode::BYTE => format!(
+                "(ite (bvult {a} {n32}) (bvand (bvlshr {b} (bvmul (bvsub {n31} {a}) {n8})) {ff}) {zero})",
+                a = a,
+                b = b,
+                n32 = hex256(&32u32.into()),
+                n31 = hex256(&31u32.into()),
+                n8 = hex256(&8u32.into()),
+                ff = hex256(&0xffu32.into()),
+                zero = zero,
+            ),
+            // shifts of 256 bits or more give zero or the sign like in the EVM
+            OpCode::SHL => format!("(bvshl {} {})", b, a),
+            OpCode::SHR => format!("(bvlshr {} {})", b, a),
+            OpCode::SAR => format!("(bvashr {} {})", b, a),
+            _ => self.function(op, &args),
+        }
+    }
+
+    fn storage(&mut self, storage: &Rc<Storage>) -> String {
+        let key = Rc::as_ptr(storage);
+        if let Some((_, name)) = self.storages.get(&key) {
+            return name.clone();
+        }
+        let array = format!("(Array {} {})", WORD, WORD);
+        let body = match &**storage {
+            Storage::Initial => {
+                self.declare("storage", format!("(declare-const storage {})", array));
+                return "storage".into();
+            }
+            Storage::Empty => {
+                return format!("((as const {}) {})", array, hex256(&Uint256::zero()));
+            }
+            Storage::Store(previous, k, v) => {
+                let previous = self.storage(previous);
+                format!("(store {} {} {})", previous, self.term(k), self.term(v))
+            }
+        };
+        let name = format!("s{}", self.storages.len());
+        self.lines
+            .push(format!("(define-fun {} () {} {})", name, array, body));
+        self.storages.insert(key, (storage.clone(), name.clone()));
+        name
+    }
+
+    // name or literal of `word`, defining it and what it uses first
+    pub fn term(&mut self, word: &Word) -> String {
+        let body = match &**word {
+            Expr::Const(value) => return hex256(value),
+            Expr::Leaf(Input::Var(name)) => {
+                let quoted = quote(name);
+                if name != CALLDATASIZE && !self.declared.contains(&quoted) {
+                    self.vars.push(name.clone());
+                }
+                self.declare(&quoted, format!("(declare-const {} {})", quoted, WORD));
+                return quoted;
+            }
+            _ if self.terms.contains_key(&Rc::as_ptr(word)) => {
+                return self.terms[&Rc::as_ptr(word)].1.clone();
+            }
+            Expr::Leaf(Input::Calldata(offset)) => format!("(calldataload {})", self.term(offset)),
+            Expr::Op(op, args) => self.operation(op, args),
+            Expr::Leaf(Input::Sload(storage, key)) => {
+                format!("(select {} {})", self.storage(storage), self.term(key))
+            }
+            // an uninterpreted function per input length keeps equal inputs hashing alike
+            Expr::Leaf(Input::Sha3(bytes)) => {
+                let name = format!("|keccak256_{}|", bytes.len());
+                self.declare(
+                    &name,
+                    format!(
+                        "(declare-fun {} ((_ BitVec {})) {})",
+                        name,
+                        bytes.len() * 8,
+                        WORD
+                    ),
+                );
+                let bytes: Vec<_> = bytes
+                    .iter()
+                    .map(|b| format!("((_ extract 7 0) {})", self.term(b)))
+                    .collect();
+                match bytes.as_slice() {
+                    [byte] => format!("({} {})", name, byte),
+                    bytes => format!("({} (concat {}))", name, bytes.join(" ")),
+                }
+            }
+        };
+        let name = format!("e{}", self.terms.len());
+        self.lines
+            .push(format!("(define-fun {} () {} {})", name, WORD, body));
+        self.terms
+            .insert(Rc::as_ptr(word), (word.clone(), name.clone()));
+        name
+    }
+
+    // assertion of `constraint`, defining its terms
+    fn assertion(&mut self, constraint: &Constraint) -> String {
+        let term = self.term(&constraint.condition);
+        let zero = hex256(&Uint256::zero());
+        if constraint.taken {
+            format!("(assert (not (= {} {})))", term, zero)
+        } else {
+            format!("(assert (= {} {}))", term, zero)
+        }
+    }
+
+    pub fn assert(&mut self, constraint: &Constraint) {
+        let line = self.assertion(constraint);
+        self.lines.push(line);
+    }
+
+    pub fn text(&self) -> String {
+        let mut text = self.lines.join("\n");
+        text.push('\n');
+        text
+    }
+}
+
+// SMT-LIB2 script checking whether `constraints` can all hold
+pub fn to_smtlib(constraints: &[Constraint]) -> String {
+    let mut script = Script::new();
+    for constraint in constraints {
+        script.assert(constraint);
+    }
+    script.text() + "(check-sat)\n"
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Sexp {
+    Atom(String),
+    List(Vec<Sexp>),
+}
+
+fn tokenize(text: &str) -> Vec<String> {
+    let mut tokens = vec![];
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '(' | ')' => tokens.push(c.to_string()),
+            c if c.is_whitespace() => {}
+            '|' | '"' => {
+                let mut token = c.to_string();
+                for next in chars.by_ref() {
+                    token.push(next);
+                    if next == c {
+                        break;
+                    }
+                }
+                tokens.push(token);
+            }
+            c => {
+                let mut token = c.to_string();
+                while let Some(next) = chars.peek() {
+                    if next.is_whitespace() || *next == '(' || *next == ')' {
+                        break;
+                    }
+                    token.push(chars.next().unwrap());
+                }
+                tokens.push(token);
+            }
+        }
+    }
+    tokens
+}
+
+fn parse_sexp(text: &str) -> Result<Sexp> {
+    let mut stack = vec![vec![]];
+    for token in tokenize(text) {
+        match token.as_str() {
+            "(" => stack.push(vec![]),
+            ")" => {
+                let list = stack.pop().filter(|_| !stack.is_empty());
+                let list = list.ok_or(anyhow!("Unbalanced solver output: {}", text))?;
+                stack.last_mut().unwrap().push(Sexp::List(list));
+            }
+            _ => stack.last_mut().unwrap().push(Sexp::Atom(token)),
+        }
+    }
+    match stack.pop() {
+        Some(mut items) if stack.is_empty() && items.len() == 1 => Ok(items.remove(0)),
+        _ => Err(anyhow!("Invalid solver output: {}", text)),
+    }
+}
+
+// one complete s-expression from the solver
+fn read_sexp(reader: &mut impl BufRead) -> Result<Sexp> {
+    let mut text = String::new();
+    loop {
+        if reader.read_line(&mut text)? == 0 {
+            return Err(anyhow!("Solver exited: {}", text.trim()));
+        }
+        let tokens = tokenize(&text);
+        let depth = tokens.iter().fold(0i64, |depth, t| match t.as_str() {
+            "(" => depth + 1,
+            ")" => depth - 1,
+            _ => depth,
+        });
+        if !tokens.is_empty() && depth <= 0 {
+            return parse_sexp(&text);
+        }
+    }
+}
+
+fn bitvec(sexp: &Sexp) -> Option<Uint256> {
+    match sexp {
+        Sexp::Atom(atom) => {
+            if let Some(digits) = atom.strip_prefix("#x") {
+                Uint256::parse_bytes(digits.as_bytes(), 16)
+            } else {
+                Uint256::parse_bytes(atom.strip_prefix("#b")?.as_bytes(), 2)
+            }
+        }
+        // (_ bvN 256)
+        Sexp::List(items) => match items.as_slice() {
+            [Sexp::Atom(underscore), Sexp::Atom(value), _] if underscore == "_" => {
+                Uint256::parse_bytes(value.strip_prefix("bv")?.as_bytes(), 10)
+            }
+            _ => None,
+        },
+    }
+}
+
+// values of a `get-value` response, in the order asked
+fn values(response: &Sexp) -> Result<Vec<Uint256>> {
+    let invalid = || anyhow!("Invalid get-value response: {:?}", response);
+    match response {
+        Sexp::List(pairs) => pairs
+            .iter()
+            .map(|pair| match pair {
+                Sexp::List(pair) if pair.len() == 2 => bitvec(&pair[1]).ok_or_else(invalid),
+                _ => Err(invalid()),
+            })
+            .collect(),
+        _ => Err(invalid()),
+    }
+}
+
+// inputs satisfying a set of constraints
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Model {
+    pub calldata: Vec<u8>,
+    // other inputs by name, like `caller`
+    pub values: BTreeMap<String, Uint256>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Sat {
+    Sat(Model),
+    Unsat,
+    Unknown,
+}
+
+// inputs named like `caller` that `word` reads, in the order found
+fn inputs(word: &Word, seen: &mut HashSet<*const Expr>, names: &mut Vec<String>) {
+    if !seen.insert(Rc::as_ptr(word)) {
+        return;
+    }
+    match &**word {
+        Expr::Const(_) => {}
+        Expr::Leaf(Input::Var(name)) => {
+            if !names.contains(name) {
+                names.push(name.clone());
+            }
+        }
+        Expr::Leaf(Input::Calldata(offset)) => inputs(offset, seen, names),
+        Expr::Op(_, args) | Expr::Leaf(Input::Sha3(args)) => {
+            for arg in args {
+                inputs(arg, seen, names);
+            }
+        }
+        Expr::Leaf(Input::Sload(storage, key)) => {
+            inputs(key, seen, names);
+            let mut storage = storage;
+            while let Storage::Store(previous, k, v) = &**storage {
+                inputs(k, seen, names);
+                inputs(v, seen, names);
+                storage = previous;
+            }
+        }
+    }
+}
+
+// a running solver, with the definitions sent so far kept at the outermost level and
+// each check's assertions between `(push)` and `(pop)`
+#[derive(Debug)]
+struct Session {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+    script: Script,
+    // lines of `script` already sent
+    sent: usize,
+}
+
+impl Session {
+    fn start(command: &[String]) -> Result<Session> {
+        let mut child = Command::new(&command[0])
+            .args(&command[1..])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .with_context(|| format!("Failed to run {}", command[0]))?;
+        let stdin = child.stdin.take().unwrap();
+        let stdout = BufReader::new(child.stdout.take().unwrap());
+        Ok(Session {
+            child,
+            stdin,
+            stdout,
+            script: Script::new(),
+            sent: 0,
+        })
+    }
+
+    fn send(&mut self, commands: &str) -> Result<()> {
+        writeln!(self.stdin, "{}", commands)?;
+        self.stdin.flush()?;
+        Ok(())
+    }
+
+    fn ask(&mut self, query: &str) -> Result<Sexp> {
+        self.send(query)?;
+        match read_sexp(&mut self.stdout)? {
+            Sexp::List(items) if items.first() == Some(&Sexp::Atom("error".into())) => {
+                Err(anyhow!("Solver error: {:?}", items.get(1)))
+            }
+            response => Ok(response),
+        }
+    }
+
+    fn check(&mut self, constraints: &[Constraint]) -> Result<Sat> {
+        let assertions: Vec<_> = constraints
+            .iter()
+            .map(|c| self.script.assertion(c))
+            .collect();
+        let definitions = self.script.lines[self.sent..].join("\n");
+        self.sent = self.script.lines.len();
+        if !definitions.is_empty() {
+            self.send(&definitions)?;
+        }
+        self.send("(push 1)")?;
+
+        let query = assertions.join("\n") + "\n(check-sat)";
+        let result = match self.ask(&query)? {
+            Sexp::Atom(answer) if answer == "sat" => {
+                let mut vars = vec![CALLDATASIZE.to_string()];
+                let mut seen = HashSet::new();
+                for constraint in constraints {
+                    inputs(&constraint.condition, &mut seen, &mut vars);
+                }
+                let names: Vec<_> = vars.iter().map(|v| quote(v)).collect();
+                let answers = values(&self.ask(&format!("(get-value ({}))", names.join(" ")))?)?;
+                let size = usize::try_from(&answers[0]).unwrap_or(MAX_CALLDATA);
+                let mut calldata = vec![];
+                if size > 0 {
+                    let bytes: Vec<_> = (0..size)
+                        .map(|i| format!("(select calldata {})", hex256(&i.into())))
+                        .collect();
+                    let response = self.ask(&format!("(get-value ({}))", bytes.join(" ")))?;
+                    calldata = values(&response)?
+                        .iter()
+                        .map(|b| u8::try_from(b).unwrap_or_default())
+                        .collect();
+                }
+                let values = vars.into_iter().zip(answers).skip(1).collect();
+                Sat::Sat(Model { calldata, values })
+            }
+            Sexp::Atom(answer) if answer == "unsat" => Sat::Unsat,
+            _ => Sat::Unknown,
+        };
+        self.send("(pop 1)")?;
+        Ok(result)
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        let _ = self.send("(exit)");
+        let _ = self.child.wait();
+    }
+}
+
+// a solver process speaking SMT-LIB2 over stdin and stdout, started on the first check and
+// reused by the later ones
+#[derive(Debug)]
+pub struct Solver {
+    command: Vec<String>,
+    session: Option<Session>,
+}
+
+impl Default for Solver {
+    fn default() -> Self {
+        Self::parse(DEFAULT_SOLVER).unwrap()
+    }
+}
+
+impl Solver {
+    pub fn parse(command: &str) -> Result<Solver> {
+        let command: Vec<String> = command.split_whitespace().map(String::from).collect();
+        if command.is_empty() {
+            return Err(anyhow!("Empty solver command"));
+        }
+        Ok(Solver {
+            command,
+            session: None,
+        })
+    }
+
+    pub fn check(&mut self, constraints: &[Constraint]) -> Result<Sat> {
+        let session = match &mut self.session {
+            Some(session) => session,
+            None => self.session.insert(Session::start(&self.command)?),
+        };
+        let result = session.check(constraints);
+        // the solver may be left inside a scope, start over next time
+        if result.is_err() {
+            self.session = None;
+        }
+        result
+    }
+
+    // false only when the constraints are known to contradict
+    pub fn is_feasible(&mut self, constraints: &[Constraint]) -> Result<bool> {
+        Ok(self.check(constraints)? != Sat::Unsat)
+    }
+}
+
+// what a model should make the code do
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Goal {
+    // execute the instruction at a position
+    Reach(usize),
+    Revert,
+    // fail an assert: a Panic(uint256) revert, or INVALID in code from before solc 0.8
+    Assert,
+}
+
+impl FromStr for Goal {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "revert" => Ok(Goal::Revert),
+            "assert" => Ok(Goal::Assert),
+            s => {
+                let position = match s.strip_prefix("0x") {
+                    Some(hex) => usize::from_str_radix(hex, 16),
+                    None => s.parse(),
+                };
+                position.map(Goal::Reach).map_err(|_| {
+                    anyhow!("Unknown goal: {}, expected revert, assert or a position", s)
+                })
+            }
+        }
+    }
+}
+
+impl Goal {
+    // constraints under which `path` meets the goal
+    pub fn constraints<'a>(&self, path: &'a Path) -> Option<&'a [Constraint]> {
+        let met = match self {
+            Goal::Reach(position) => {
+                let step = path.visited.iter().position(|p| p == position)?;
+                // forks after the first visit do not matter
+                let n = path
+                    .constraints
+                    .iter()
+                    .take_while(|c| c.step <= step)
+                    .count();
+                return Some(&path.constraints[..n]);
+            }
+            Goal::Revert => path.end == End::Halt(Halt::Revert),
+            Goal::Assert => match &path.end {
+                End::Halt(Halt::Error(VmError::InvalidOpcode(0xfe))) => true,
+                End::Halt(Halt::Revert) => {
+                    let selector: Option<Vec<u8>> = path
+                        .output
+                        .iter()
+                        .take(4)
+                        .map(|b| b.constant().and_then(|v| u8::try_from(v).ok()))
+                        .collect();
+                    path.output.len() == 36 && selector.as_deref() == Some(&PANIC_SELECTOR)
+                }
+                _ => false,
+            },
+        };
+        met.then_some(&path.constraints[..])
+    }
+}
+
+// first path of `code` meeting `goal` with inputs taking it there, infeasible forks pruned
+pub fn solve(
+    code: &[u8],
+    spec: SpecId,
+    limits: &Limits,
+    goal: Goal,
+    solver: &mut Solver,
+) -> Result<Option<(Path, Model)>> {
+    let mut error = None;
+    let paths = explore_with(code, spec, limits, &mut |constraints| {
+        // without a solver every fork is kept
+        solver.is_feasible(constraints).unwrap_or_else(|e| {
+            error.get_or_insert(e);
+            true
+        })
+    });
+    if let Some(e) = error {
+        return Err(e);
+    }
+    for path in paths {
+        let Some(constraints) = goal.constraints(&path) else {
+            continue;
+        };
+        if let Sat::Sat(model) = solver.check(constraints)? {
+            return Ok(Some((path, model)));
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::{parse_sexp, to_smtlib, values, Goal, Script};
+    use crate::{
+        opcode::OpCode,
+        parser::decode_hex,
+        spec::SpecId,
+        symbolic::{constant, explore, op, var, Limits},
+    };
+
+    #[test]
+    fn test_smtlib() {
+        // if calldataload(0) == 0x2a { sstore(0, caller) } else { revert(0, 0) }
+        let code = decode_hex("600035602a14600e5760006000fd5b3360005500").unwrap();
+        let paths = explore(&code, SpecId::default(), &Limits::default());
+        let script = to_smtlib(&paths[0].constraints);
+        let zero = format!("#x{}", "0".repeat(64));
+        assert!(script.contains(&format!(
+            "(define-fun e0 () (_ BitVec 256) (calldataload {}))",
+            zero
+        )));
+        assert!(script.contains("(define-fun e1 () (_ BitVec 256) (ite (= #x000"));
+        assert!(script.ends_with(&format!("(assert (= e1 {}))\n(check-sat)\n", zero)));
+
+        assert_eq!(Goal::Revert.constraints(&paths[0]).unwrap().len(), 1);
+        assert!(Goal::Revert.constraints(&paths[1]).is_none());
+        let reach: Goal = "0xf".parse().unwrap();
+        assert_eq!(reach.constraints(&paths[1]).unwrap().len(), 1);
+        assert_eq!(Goal::Reach(0x8).constraints(&paths[1]).unwrap().len(), 0);
+    }
+
+    #[test]
+    fn test_terms_of_dropped_words() {
+        // each word is dropped before the next is built, possibly at the same address
+        let mut script = Script::new();
+        let names: HashSet<_> = (0..16u32)
+            .map(|i| script.term(&op(OpCode::ADD, vec![var("x"), constant(i)])))
+            .collect();
+        assert_eq!(names.len(), 16);
+    }
+
+    #[test]
+    fn test_values() {
+        let response =
+            parse_sexp("((|calldatasize| #x04) ((select calldata #x00) #b101) (x (_ bv7 256)))")
+                .unwrap();
+        assert_eq!(
+            values(&response).unwrap(),
+            [4u32.into(), 5u32.into(), 7u32.into()]
+        );
+        assert!(parse_sexp("((a #x1)").is_err());
+    }
+}
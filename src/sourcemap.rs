@@ -0,0 +1,237 @@
+use std::{collections::BTreeMap, fs, path::Path};
+
+use anyhow::{anyhow, Result};
+
+use crate::{block::Block, opcode::OpCode};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum JumpType {
+    // a jump into a function
+    In,
+    // a return from a function
+    Out,
+    #[default]
+    Regular,
+}
+
+// source range of one instruction, `s:l:f:j:m` in solc terms
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct SourceRange {
+    pub offset: usize,
+    pub length: usize,
+    // None for compiler generated code
+    pub file: Option<usize>,
+    pub jump: JumpType,
+    pub modifier_depth: usize,
+}
+
+// one range per instruction, indexed like the parsed blocks
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SourceMap {
+    pub ranges: Vec<SourceRange>,
+}
+
+fn parse_field<T: std::str::FromStr>(field: Option<&str>, previous: T) -> Result<T> {
+    match field {
+        None | Some("") => Ok(previous),
+        Some(s) => s
+            .parse()
+            .map_err(|_| anyhow!("Invalid source map field {}", s)),
+    }
+}
+
+impl SourceMap {
+    // decompress, empty or missing fields repeat the previous entry
+    pub fn parse(map: &str) -> Result<Self> {
+        let mut ranges = vec![];
+        let mut previous = SourceRange::default();
+        for entry in map.trim().split(';') {
+            let mut fields = entry.split(':');
+            let mut range = previous.clone();
+            range.offset = parse_field(fields.next(), previous.offset)?;
+            range.length = parse_field(fields.next(), previous.length)?;
+            let file: i64 = parse_field(fields.next(), previous.file.map_or(-1, |f| f as i64))?;
+            range.file = usize::try_from(file).ok();
+            range.jump = match fields.next() {
+                None | Some("") => previous.jump,
+                Some("i") => JumpType::In,
+                Some("o") => JumpType::Out,
+                Some("-") => JumpType::Regular,
+                Some(s) => return Err(anyhow!("Invalid jump type {}", s)),
+            };
+            range.modifier_depth = parse_field(fields.next(), previous.modifier_depth)?;
+            ranges.push(range.clone());
+            previous = range;
+        }
+        Ok(SourceMap { ranges })
+    }
+
+    // instruction `index` of the code the map was generated for
+    pub fn get(&self, index: usize) -> Option<&SourceRange> {
+        self.ranges.get(index)
+    }
+
+    // one comment per source line, for `formatter::format_with_options`
+    pub fn comments(&self, blocks: &[Block], files: &SourceFiles) -> BTreeMap<usize, String> {
+        let mut comments = BTreeMap::new();
+        let mut previous = None;
+        for (block, range) in blocks.iter().zip(&self.ranges) {
+            let Some(location) = files.location(range) else {
+                continue;
+            };
+            let key = (range.file, location.line);
+            if previous == Some(key) {
+                continue;
+            }
+            comments.insert(block.position, location.to_string());
+            previous = Some(key);
+        }
+        comments
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceFile {
+    pub path: String,
+    pub content: String,
+}
+
+// source files by source id
+#[derive(Debug, Clone, Default)]
+pub struct SourceFiles {
+    pub files: BTreeMap<usize, SourceFile>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceLocation<'a> {
+    pub path: &'a str,
+    // 1-based
+    pub line: usize,
+    // the whole line the range starts on
+    pub text: &'a str,
+}
+
+impl std::fmt::Display for SourceLocation<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}\t{}", self.path, self.line, self.text.trim())
+    }
+}
+
+impl SourceFiles {
+    pub fn insert(&mut self, id: usize, path: &str, content: String) {
+        self.files.insert(
+            id,
+            SourceFile {
+                path: path.to_string(),
+                content,
+            },
+        );
+    }
+
+    pub fn load(&mut self, id: usize, path: &Path) -> Result<()> {
+        let content = fs::read_to_string(path)?;
+        self.insert(id, &path.display().to_string(), content);
+        Ok(())
+    }
+
+    pub fn location(&self, range: &SourceRange) -> Option<SourceLocation<'_>> {
+        let file = self.files.get(&range.file?)?;
+        let content = &file.content;
+        let before = content.get(..range.offset)?;
+        let start = before.rfind('\n').map_or(0, |i| i + 1);
+        let end = content[range.offset..]
+            .find('\n')
+            .map_or(content.len(), |i| range.offset + i);
+        Some(SourceLocation {
+            path: &file.path,
+            line: before.matches('\n').count() + 1,
+            text: &content[start..end],
+        })
+    }
+
+    // the source text the range covers
+    pub fn snippet(&self, range: &SourceRange) -> Option<&str> {
+        let file = self.files.get(&range.file?)?;
+        file.content
+            .get(range.offset..range.offset.checked_add(range.length)?)
+    }
+}
+
+// internal function calls, reconstructed from the jump types of executed instructions
+#[derive(Debug, Clone, Default)]
+pub struct CallStack {
+    // range of each jump into a function, outermost first
+    pub frames: Vec<SourceRange>,
+}
+
+impl CallStack {
+    // record an executed instruction, returns whether the stack changed
+    pub fn step(&mut self, op: &OpCode, range: &SourceRange) -> bool {
+        // the jump type is inherited by following entries, only jumps act on it
+        if *op != OpCode::JUMP {
+            return false;
+        }
+        match range.jump {
+            JumpType::In => {
+                self.frames.push(range.clone());
+                true
+            }
+            JumpType::Out => self.frames.pop().is_some(),
+            JumpType::Regular => false,
+        }
+    }
+
+    pub fn depth(&self) -> usize {
+        self.frames.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CallStack, JumpType, SourceFiles, SourceMap, SourceRange};
+    use crate::opcode::OpCode;
+
+    #[test]
+    fn test_parse_source_map() {
+        let map = SourceMap::parse("10:20:0:-:0;;:5;30:4:1:i;:::o;50:1:-1").unwrap();
+        assert_eq!(map.ranges.len(), 6);
+        assert_eq!(map.ranges[1], map.ranges[0]);
+        assert_eq!(
+            map.ranges[2],
+            SourceRange {
+                offset: 10,
+                length: 5,
+                file: Some(0),
+                jump: JumpType::Regular,
+                modifier_depth: 0
+            }
+        );
+        assert_eq!(map.ranges[3].jump, JumpType::In);
+        assert_eq!(map.ranges[4].offset, 30);
+        assert_eq!(map.ranges[4].jump, JumpType::Out);
+        assert_eq!(map.ranges[5].file, None);
+
+        let mut stack = CallStack::default();
+        assert!(!stack.step(&OpCode::ADD, &map.ranges[3]));
+        assert!(stack.step(&OpCode::JUMP, &map.ranges[3]));
+        assert_eq!(stack.depth(), 1);
+        assert!(stack.step(&OpCode::JUMP, &map.ranges[4]));
+        assert_eq!(stack.depth(), 0);
+    }
+
+    #[test]
+    fn test_source_location() {
+        let mut files = SourceFiles::default();
+        files.insert(0, "A.sol", "contract A {\n    uint x;\n}\n".into());
+        let range = SourceRange {
+            offset: 17,
+            length: 6,
+            file: Some(0),
+            ..Default::default()
+        };
+        let location = files.location(&range).unwrap();
+        assert_eq!(location.line, 2);
+        assert_eq!(location.to_string(), "A.sol:2\tuint x;");
+        assert_eq!(files.snippet(&range), Some("uint x"));
+    }
+}
@@ -0,0 +1,105 @@
+use std::{fmt, str::FromStr};
+
+use anyhow::{anyhow, Error};
+
+/// Ethereum hard forks, in activation order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum SpecId {
+    Frontier,
+    Homestead,
+    Tangerine,
+    SpuriousDragon,
+    Byzantium,
+    Constantinople,
+    Petersburg,
+    Istanbul,
+    Berlin,
+    London,
+    Merge,
+    Shanghai,
+    #[default]
+    Cancun,
+    Prague,
+}
+
+impl SpecId {
+    pub const ALL: [SpecId; 14] = [
+        SpecId::Frontier,
+        SpecId::Homestead,
+        SpecId::Tangerine,
+        SpecId::SpuriousDragon,
+        SpecId::Byzantium,
+        SpecId::Constantinople,
+        SpecId::Petersburg,
+        SpecId::Istanbul,
+        SpecId::Berlin,
+        SpecId::London,
+        SpecId::Merge,
+        SpecId::Shanghai,
+        SpecId::Cancun,
+        SpecId::Prague,
+    ];
+
+    pub const LATEST: SpecId = SpecId::Prague;
+
+    // true if `fork` is active under this spec
+    pub fn is_enabled_in(self, fork: SpecId) -> bool {
+        self >= fork
+    }
+
+    pub fn name(self) -> &'static str {
+        use SpecId::*;
+
+        match self {
+            Frontier => "Frontier",
+            Homestead => "Homestead",
+            Tangerine => "Tangerine",
+            SpuriousDragon => "SpuriousDragon",
+            Byzantium => "Byzantium",
+            Constantinople => "Constantinople",
+            Petersburg => "Petersburg",
+            Istanbul => "Istanbul",
+            Berlin => "Berlin",
+            London => "London",
+            Merge => "Merge",
+            Shanghai => "Shanghai",
+            Cancun => "Cancun",
+            Prague => "Prague",
+        }
+    }
+}
+
+impl fmt::Display for SpecId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for SpecId {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use SpecId::*;
+
+        // accept the names used by ethereum/tests and common aliases
+        let spec = match s.to_ascii_lowercase().as_str() {
+            "frontier" => Frontier,
+            "homestead" => Homestead,
+            "tangerine" | "tangerinewhistle" | "eip150" => Tangerine,
+            "spuriousdragon" | "eip158" => SpuriousDragon,
+            "byzantium" => Byzantium,
+            "constantinople" => Constantinople,
+            "petersburg" | "constantinoplefix" => Petersburg,
+            "istanbul" => Istanbul,
+            "berlin" => Berlin,
+            "london" => London,
+            "merge" | "paris" => Merge,
+            "shanghai" => Shanghai,
+            "cancun" => Cancun,
+            "prague" => Prague,
+            "latest" => SpecId::LATEST,
+            _ => return Err(anyhow!("Unknown hard fork: {}", s)),
+        };
+        Ok(spec)
+    }
+}
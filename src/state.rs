@@ -0,0 +1,607 @@
+use std::collections::{hash_map::Entry, HashMap, HashSet};
+
+use num_traits::Zero;
+
+use crate::{
+    cheatcodes::{Cheatcodes, CHEATCODE_ADDRESS},
+    rlp,
+    trace::TraceStep,
+    trie::Trie,
+    util::{keccak256, to_bytes32, uint_to_address},
+    Address, Uint256,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Log {
+    pub address: Address,
+    pub topics: Vec<Uint256>,
+    pub data: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Account {
+    pub nonce: u64,
+    pub balance: Uint256,
+    pub code: Vec<u8>,
+    pub storage: HashMap<Uint256, Uint256>,
+}
+
+impl Log {
+    pub fn rlp(&self) -> Vec<u8> {
+        let topics: Vec<Vec<u8>> = self
+            .topics
+            .iter()
+            .map(|t| rlp::encode_bytes(&to_bytes32(t)))
+            .collect();
+        rlp::encode_list(&[
+            rlp::encode_bytes(&self.address),
+            rlp::encode_list(&topics),
+            rlp::encode_bytes(&self.data),
+        ])
+    }
+}
+
+// keccak256 of the RLP list of logs, the `logs` hash of state tests
+pub fn logs_hash(logs: &[Log]) -> [u8; 32] {
+    let logs: Vec<Vec<u8>> = logs.iter().map(Log::rlp).collect();
+    keccak256(&rlp::encode_list(&logs))
+}
+
+// 2048-bit bloom filter over the addresses and topics of the logs
+pub fn logs_bloom(logs: &[Log]) -> [u8; 256] {
+    let mut bloom = [0; 256];
+    let values = logs.iter().flat_map(|log| {
+        std::iter::once(log.address.to_vec())
+            .chain(log.topics.iter().map(|t| to_bytes32(t).to_vec()))
+    });
+    for value in values {
+        let hash = keccak256(&value);
+        for i in 0..3 {
+            let bit = (usize::from(hash[2 * i]) << 8 | usize::from(hash[2 * i + 1])) & 2047;
+            bloom[255 - bit / 8] |= 1 << (bit % 8);
+        }
+    }
+    bloom
+}
+
+impl Account {
+    // EIP-161
+    pub fn is_empty(&self) -> bool {
+        self.nonce == 0 && self.balance.is_zero() && self.code.is_empty()
+    }
+
+    // trie of keccak256(slot) to the RLP of the value
+    pub fn storage_root(&self) -> [u8; 32] {
+        self.storage
+            .iter()
+            .map(|(key, value)| {
+                (
+                    keccak256(&to_bytes32(key)).to_vec(),
+                    rlp::encode_uint(value),
+                )
+            })
+            .collect::<Trie>()
+            .root()
+    }
+
+    // the account as stored in the state trie
+    pub fn rlp(&self) -> Vec<u8> {
+        rlp::encode_list(&[
+            rlp::encode_u64(self.nonce),
+            rlp::encode_uint(&self.balance),
+            rlp::encode_bytes(&self.storage_root()),
+            rlp::encode_bytes(&keccak256(&self.code)),
+        ])
+    }
+}
+
+// undo record of one state change
+#[derive(Debug, Clone)]
+enum JournalEntry {
+    AccountCreated(Address),
+    AccountRemoved(Address, Account),
+    NonceChanged(Address, u64),
+    BalanceChanged(Address, Uint256),
+    CodeChanged(Address, Vec<u8>),
+    StorageChanged(Address, Uint256, Uint256),
+    OriginalStorageRecorded(Address, Uint256),
+    TransientStorageChanged(Address, Uint256, Uint256),
+    LogAdded,
+    AddressAccessed(Address),
+    StorageAccessed(Address, Uint256),
+    RefundChanged(i64),
+    Selfdestructed(Address),
+    Created(Address),
+    Touched(Address),
+    // only recorded while a snapshot taken outside a transaction is open
+    TransactionBegun(Box<TransactionSubstate>),
+}
+
+// everything `begin_transaction` resets
+#[derive(Debug, Clone, Default)]
+struct TransactionSubstate {
+    transient_storage: HashMap<(Address, Uint256), Uint256>,
+    logs: Vec<Log>,
+    original_storage: HashMap<(Address, Uint256), Uint256>,
+    accessed_addresses: HashSet<Address>,
+    accessed_storage: HashSet<(Address, Uint256)>,
+    refund: i64,
+    selfdestructs: HashSet<Address>,
+    created: HashSet<Address>,
+    touched: HashSet<Address>,
+}
+
+// world state shared by every frame of a transaction
+#[derive(Debug, Clone, Default)]
+pub struct State {
+    pub accounts: HashMap<Address, Account>,
+    // EIP-1153, discarded at the end of every transaction
+    pub transient_storage: HashMap<(Address, Uint256), Uint256>,
+    // logs emitted so far, in order
+    pub logs: Vec<Log>,
+    // storage values at the start of the transaction, recorded on first write (EIP-2200)
+    pub original_storage: HashMap<(Address, Uint256), Uint256>,
+    // EIP-2929 access lists
+    pub accessed_addresses: HashSet<Address>,
+    pub accessed_storage: HashSet<(Address, Uint256)>,
+    // gas refund counter, may go negative within a frame
+    pub refund: i64,
+    // accounts to delete at the end of the transaction
+    pub selfdestructs: HashSet<Address>,
+    // accounts created in this transaction (EIP-6780)
+    pub created: HashSet<Address>,
+    // accounts to delete at the end of the transaction if empty (EIP-161)
+    pub touched: HashSet<Address>,
+    // handles calls to `CHEATCODE_ADDRESS` when enabled
+    pub cheatcodes: Option<Box<Cheatcodes>>,
+    // instructions executed by every frame, recorded when set (EIP-3155)
+    pub trace: Option<Vec<TraceStep>>,
+    // changes made through the methods below, writes to the fields are not recorded
+    journal: Vec<JournalEntry>,
+    in_transaction: bool,
+    // a snapshot was taken outside a transaction, the journal is kept until `commit`
+    pinned: bool,
+}
+
+fn set_storage_value(storage: &mut HashMap<Uint256, Uint256>, key: Uint256, value: Uint256) {
+    if value.is_zero() {
+        storage.remove(&key);
+    } else {
+        storage.insert(key, value);
+    }
+}
+
+impl State {
+    pub fn account(&self, address: &Address) -> Option<&Account> {
+        self.accounts.get(address)
+    }
+
+    // the account to change a single field of, creating it if needed
+    fn load_account(&mut self, address: Address) -> &mut Account {
+        if !self.accounts.contains_key(&address) {
+            self.journal.push(JournalEntry::AccountCreated(address));
+        }
+        self.accounts.entry(address).or_default()
+    }
+
+    pub fn exists(&self, address: &Address) -> bool {
+        self.accounts.contains_key(address)
+    }
+
+    // missing accounts count as empty
+    pub fn is_empty(&self, address: &Address) -> bool {
+        self.account(address).is_none_or(|a| a.is_empty())
+    }
+
+    pub fn balance(&self, address: &Address) -> Uint256 {
+        self.account(address)
+            .map(|a| a.balance.clone())
+            .unwrap_or_default()
+    }
+
+    pub fn set_balance(&mut self, address: Address, balance: Uint256) {
+        let account = self.load_account(address);
+        let previous = std::mem::replace(&mut account.balance, balance);
+        self.journal
+            .push(JournalEntry::BalanceChanged(address, previous));
+    }
+
+    pub fn add_balance(&mut self, address: Address, value: &Uint256) {
+        self.set_balance(address, self.balance(&address) + value);
+    }
+
+    pub fn nonce(&self, address: &Address) -> u64 {
+        self.account(address).map_or(0, |a| a.nonce)
+    }
+
+    pub fn set_nonce(&mut self, address: Address, nonce: u64) {
+        let account = self.load_account(address);
+        let previous = std::mem::replace(&mut account.nonce, nonce);
+        self.journal
+            .push(JournalEntry::NonceChanged(address, previous));
+    }
+
+    pub fn code(&self, address: &Address) -> &[u8] {
+        self.account(address).map_or(&[], |a| &a.code)
+    }
+
+    pub fn set_code(&mut self, address: Address, code: Vec<u8>) {
+        let account = self.load_account(address);
+        let previous = std::mem::replace(&mut account.code, code);
+        self.journal
+            .push(JournalEntry::CodeChanged(address, previous));
+    }
+
+    // EXTCODEHASH, zero for accounts that do not exist or are empty
+    pub fn code_hash(&self, address: &Address) -> Uint256 {
+        match self.account(address) {
+            Some(a) if !a.is_empty() => Uint256::from_bytes_be(&keccak256(&a.code)),
+            _ => Uint256::zero(),
+        }
+    }
+
+    pub fn sload(&self, address: &Address, key: &Uint256) -> Uint256 {
+        self.account(address)
+            .and_then(|a| a.storage.get(key))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    pub fn sstore(&mut self, address: Address, key: Uint256, value: Uint256) {
+        let current = self.sload(&address, &key);
+        if let Entry::Vacant(entry) = self.original_storage.entry((address, key.clone())) {
+            entry.insert(current.clone());
+            self.journal
+                .push(JournalEntry::OriginalStorageRecorded(address, key.clone()));
+        }
+        let account = self.load_account(address);
+        set_storage_value(&mut account.storage, key.clone(), value);
+        self.journal
+            .push(JournalEntry::StorageChanged(address, key, current));
+    }
+
+    pub fn original_storage(&self, address: &Address, key: &Uint256) -> Uint256 {
+        match self.original_storage.get(&(*address, key.clone())) {
+            Some(value) => value.clone(),
+            None => self.sload(address, key),
+        }
+    }
+
+    // returns whether the address was already warm
+    pub fn access_address(&mut self, address: Address) -> bool {
+        if !self.accessed_addresses.insert(address) {
+            return true;
+        }
+        self.journal.push(JournalEntry::AddressAccessed(address));
+        false
+    }
+
+    // returns whether the slot was already warm
+    pub fn access_storage(&mut self, address: Address, key: Uint256) -> bool {
+        if !self.accessed_storage.insert((address, key.clone())) {
+            return true;
+        }
+        self.journal
+            .push(JournalEntry::StorageAccessed(address, key));
+        false
+    }
+
+    pub fn add_refund(&mut self, refund: i64) {
+        self.journal.push(JournalEntry::RefundChanged(self.refund));
+        self.refund += refund;
+    }
+
+    pub fn selfdestruct(&mut self, address: Address) {
+        if self.selfdestructs.insert(address) {
+            self.journal.push(JournalEntry::Selfdestructed(address));
+        }
+    }
+
+    pub fn mark_created(&mut self, address: Address) {
+        if self.created.insert(address) {
+            self.journal.push(JournalEntry::Created(address));
+        }
+    }
+
+    pub fn touch(&mut self, address: Address) {
+        if self.touched.insert(address) {
+            self.journal.push(JournalEntry::Touched(address));
+        }
+    }
+
+    // false if `from` cannot afford it
+    pub fn transfer(&mut self, from: Address, to: Address, value: &Uint256) -> bool {
+        let balance = self.balance(&from);
+        if balance < *value {
+            return false;
+        }
+        self.touch(to);
+        if value.is_zero() || from == to {
+            return true;
+        }
+        self.set_balance(from, balance - value);
+        self.add_balance(to, value);
+        true
+    }
+
+    pub fn tload(&self, address: Address, key: &Uint256) -> Uint256 {
+        self.transient_storage
+            .get(&(address, key.clone()))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    pub fn tstore(&mut self, address: Address, key: Uint256, value: Uint256) {
+        let previous = self
+            .transient_storage
+            .insert((address, key.clone()), value)
+            .unwrap_or_default();
+        self.journal.push(JournalEntry::TransientStorageChanged(
+            address, key, previous,
+        ));
+    }
+
+    pub fn log(&mut self, log: Log) {
+        self.logs.push(log);
+        self.journal.push(JournalEntry::LogAdded);
+    }
+
+    // reset everything scoped to a single transaction
+    pub fn begin_transaction(&mut self) {
+        self.in_transaction = true;
+        if !self.pinned {
+            // nothing can revert past this point, the previous transaction is final
+            self.journal.clear();
+        }
+        let substate = TransactionSubstate {
+            transient_storage: std::mem::take(&mut self.transient_storage),
+            logs: std::mem::take(&mut self.logs),
+            original_storage: std::mem::take(&mut self.original_storage),
+            accessed_addresses: std::mem::take(&mut self.accessed_addresses),
+            accessed_storage: std::mem::take(&mut self.accessed_storage),
+            refund: std::mem::take(&mut self.refund),
+            selfdestructs: std::mem::take(&mut self.selfdestructs),
+            created: std::mem::take(&mut self.created),
+            touched: std::mem::take(&mut self.touched),
+        };
+        if self.pinned {
+            self.journal
+                .push(JournalEntry::TransactionBegun(Box::new(substate)));
+        }
+    }
+
+    fn remove_account(&mut self, address: &Address) {
+        if let Some(account) = self.accounts.remove(address) {
+            self.journal
+                .push(JournalEntry::AccountRemoved(*address, account));
+        }
+    }
+
+    // apply self-destructs and, with `clear_empty`, remove touched empty accounts
+    pub fn end_transaction(&mut self, clear_empty: bool) {
+        let selfdestructs: Vec<Address> = self.selfdestructs.iter().copied().collect();
+        for address in &selfdestructs {
+            self.remove_account(address);
+        }
+        if clear_empty {
+            let touched: Vec<Address> = self.touched.iter().copied().collect();
+            for address in &touched {
+                if self.accounts.get(address).is_some_and(|a| a.is_empty()) {
+                    self.remove_account(address);
+                }
+            }
+        }
+        self.in_transaction = false;
+        if !self.pinned {
+            self.journal.clear();
+        }
+    }
+
+    // Solidity refuses to call an address without code, so the cheatcode address gets some
+    pub fn enable_cheatcodes(&mut self) {
+        self.cheatcodes = Some(Box::default());
+        if self.code(&CHEATCODE_ADDRESS).is_empty() {
+            self.set_code(CHEATCODE_ADDRESS, vec![0x01]);
+        }
+    }
+
+    // trie of keccak256(address) to the RLP of the account
+    pub fn state_root(&self) -> [u8; 32] {
+        self.accounts
+            .iter()
+            .map(|(address, account)| (keccak256(address).to_vec(), account.rlp()))
+            .collect::<Trie>()
+            .root()
+    }
+
+    // id to pass to `revert_to`, one taken outside a transaction holds the journal until `commit`
+    pub fn snapshot(&mut self) -> usize {
+        if !self.in_transaction {
+            self.pinned = true;
+        }
+        self.journal.len()
+    }
+
+    // undo every change made since `snapshot` returned `id`, later ids become invalid
+    pub fn revert_to(&mut self, id: usize) {
+        while self.journal.len() > id {
+            let Some(entry) = self.journal.pop() else {
+                break;
+            };
+            self.undo(entry);
+        }
+    }
+
+    // forget the journal, invalidating every snapshot
+    pub fn commit(&mut self) {
+        self.journal.clear();
+        self.pinned = false;
+    }
+
+    fn undo(&mut self, entry: JournalEntry) {
+        use JournalEntry::*;
+
+        match entry {
+            AccountCreated(address) => {
+                self.accounts.remove(&address);
+            }
+            AccountRemoved(address, account) => {
+                self.accounts.insert(address, account);
+            }
+            NonceChanged(address, nonce) => {
+                self.accounts.entry(address).or_default().nonce = nonce;
+            }
+            BalanceChanged(address, balance) => {
+                self.accounts.entry(address).or_default().balance = balance;
+            }
+            CodeChanged(address, code) => {
+                self.accounts.entry(address).or_default().code = code;
+            }
+            StorageChanged(address, key, value) => {
+                let account = self.accounts.entry(address).or_default();
+                set_storage_value(&mut account.storage, key, value);
+            }
+            OriginalStorageRecorded(address, key) => {
+                self.original_storage.remove(&(address, key));
+            }
+            TransientStorageChanged(address, key, value) => {
+                if value.is_zero() {
+                    self.transient_storage.remove(&(address, key));
+                } else {
+                    self.transient_storage.insert((address, key), value);
+                }
+            }
+            LogAdded => {
+                self.logs.pop();
+            }
+            AddressAccessed(address) => {
+                self.accessed_addresses.remove(&address);
+            }
+            StorageAccessed(address, key) => {
+                self.accessed_storage.remove(&(address, key));
+            }
+            RefundChanged(refund) => self.refund = refund,
+            Selfdestructed(address) => {
+                self.selfdestructs.remove(&address);
+            }
+            Created(address) => {
+                self.created.remove(&address);
+            }
+            Touched(address) => {
+                self.touched.remove(&address);
+            }
+            TransactionBegun(substate) => {
+                let substate = *substate;
+                self.transient_storage = substate.transient_storage;
+                self.logs = substate.logs;
+                self.original_storage = substate.original_storage;
+                self.accessed_addresses = substate.accessed_addresses;
+                self.accessed_storage = substate.accessed_storage;
+                self.refund = substate.refund;
+                self.selfdestructs = substate.selfdestructs;
+                self.created = substate.created;
+                self.touched = substate.touched;
+            }
+        }
+    }
+}
+
+pub fn create_address(sender: &Address, nonce: u64) -> Address {
+    let hash = keccak256(&rlp::encode_list(&[
+        rlp::encode_bytes(sender),
+        rlp::encode_u64(nonce),
+    ]));
+    uint_to_address(&Uint256::from_bytes_be(&hash[12..]))
+}
+
+// EIP-1014
+pub fn create2_address(sender: &Address, salt: &Uint256, init_code: &[u8]) -> Address {
+    let mut data = vec![0xff];
+    data.extend_from_slice(sender);
+    data.extend_from_slice(&to_bytes32(salt));
+    data.extend_from_slice(&keccak256(init_code));
+    let hash = keccak256(&data);
+    uint_to_address(&Uint256::from_bytes_be(&hash[12..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{create2_address, create_address, logs_bloom, logs_hash, Log, State};
+
+    #[test]
+    fn test_create_address() {
+        let sender: [u8; 20] = hex::decode("6ac7ea33f8831ea9dcc53393aaa88b25a785dbf0")
+            .unwrap()
+            .try_into()
+            .unwrap();
+        assert_eq!(
+            hex::encode(create_address(&sender, 0)),
+            "cd234a471b72ba2f1ccf0a70fcaba648a5eecd8d"
+        );
+        assert_eq!(
+            hex::encode(create_address(&sender, 1)),
+            "343c43a37d37dff08ae8c4a11544c718abb4fcf8"
+        );
+        // first example of EIP-1014
+        assert_eq!(
+            hex::encode(create2_address(&[0; 20], &0u32.into(), &[0x00])),
+            "4d1a2e2bb4f88f0250f26ffff098b0b30b26bf38"
+        );
+    }
+
+    #[test]
+    fn test_revert_to_snapshot() {
+        let (a, b) = ([1; 20], [2; 20]);
+        let mut state = State::default();
+        state.set_balance(a, 100u32.into());
+        state.sstore(a, 1u32.into(), 5u32.into());
+        let id = state.snapshot();
+
+        state.begin_transaction();
+        assert!(state.transfer(a, b, &40u32.into()));
+        state.sstore(a, 1u32.into(), 0u32.into());
+        state.set_code(b, vec![0x00]);
+        state.access_address(b);
+        state.add_refund(100);
+        assert_eq!(state.balance(&b), 40u32.into());
+
+        state.revert_to(id);
+        assert_eq!(state.balance(&a), 100u32.into());
+        assert!(!state.exists(&b));
+        assert_eq!(state.sload(&a, &1u32.into()), 5u32.into());
+        assert!(state.accessed_addresses.is_empty());
+        assert_eq!(state.refund, 0);
+        // the original value recorded before the snapshot survives
+        assert_eq!(state.original_storage(&a, &1u32.into()), 0u32.into());
+
+        state.commit();
+        state.begin_transaction();
+        state.sstore(a, 1u32.into(), 0u32.into());
+        state.end_transaction(true);
+        assert!(state.journal.is_empty());
+        // a transaction begun with nothing pinned keeps no copy of the previous one
+        state.begin_transaction();
+        assert!(state.journal.is_empty());
+    }
+
+    #[test]
+    fn test_logs_hash_and_bloom() {
+        assert_eq!(
+            hex::encode(logs_hash(&[])),
+            "1dcc4de8dec75d7aab85b567b6ccd41ad312451b948a7413f0a142fd40d49347"
+        );
+        let log = Log {
+            address: [1; 20],
+            topics: vec![7u32.into()],
+            data: vec![],
+        };
+        let bloom = logs_bloom(&[log]);
+        // three bits for the address and three for the topic, some may coincide
+        let bits: u32 = bloom.iter().map(|b| b.count_ones()).sum();
+        assert!((1..=6).contains(&bits));
+        assert_eq!(
+            State::default().state_root(),
+            crate::trie::Trie::default().root()
+        );
+    }
+}
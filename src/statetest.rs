@@ -0,0 +1,500 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Context, Result};
+use k256::ecdsa::SigningKey;
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::{
+    env::{BlockEnv, Env},
+    spec::SpecId,
+    state::{logs_hash, Account, State},
+    transaction::Transaction,
+    util::{keccak256, uint_to_address},
+    Address, Uint256,
+};
+
+// hex quantity, "0x" prefixed
+pub(crate) fn parse_uint(s: &str) -> Result<Uint256> {
+    let digits = s.strip_prefix("0x").unwrap_or(s);
+    if digits.is_empty() {
+        return Ok(Uint256::default());
+    }
+    Uint256::parse_bytes(digits.as_bytes(), 16).ok_or(anyhow!("Invalid quantity {}", s))
+}
+
+pub(crate) fn parse_u64(s: &str) -> Result<u64> {
+    u64::try_from(parse_uint(s)?).map_err(|_| anyhow!("Quantity too large: {}", s))
+}
+
+pub(crate) fn parse_bytes(s: &str) -> Result<Vec<u8>> {
+    Ok(hex::decode(s.strip_prefix("0x").unwrap_or(s))?)
+}
+
+fn parse_address(s: &str) -> Result<Address> {
+    parse_bytes(s)?
+        .try_into()
+        .map_err(|_| anyhow!("Invalid address {}", s))
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct JsonEnv {
+    current_coinbase: String,
+    current_gas_limit: String,
+    current_number: String,
+    current_timestamp: String,
+    current_difficulty: Option<String>,
+    current_random: Option<String>,
+    current_base_fee: Option<String>,
+    current_excess_blob_gas: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct JsonAccount {
+    balance: String,
+    code: String,
+    nonce: String,
+    storage: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct JsonAccessListItem {
+    address: String,
+    storage_keys: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct JsonTransaction {
+    data: Vec<String>,
+    gas_limit: Vec<String>,
+    value: Vec<String>,
+    gas_price: Option<String>,
+    max_fee_per_gas: Option<String>,
+    max_priority_fee_per_gas: Option<String>,
+    nonce: String,
+    sender: Option<String>,
+    secret_key: Option<String>,
+    to: String,
+    access_lists: Option<Vec<Option<Vec<JsonAccessListItem>>>>,
+    #[serde(default)]
+    blob_versioned_hashes: Vec<String>,
+    max_fee_per_blob_gas: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct JsonIndexes {
+    data: usize,
+    gas: usize,
+    value: usize,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct JsonPost {
+    hash: String,
+    logs: String,
+    indexes: JsonIndexes,
+    expect_exception: Option<String>,
+    // full post-state alloc, only in newer fixtures
+    state: Option<BTreeMap<String, JsonAccount>>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct JsonStateTest {
+    env: JsonEnv,
+    pre: BTreeMap<String, JsonAccount>,
+    transaction: JsonTransaction,
+    post: BTreeMap<String, Vec<JsonPost>>,
+    config: Option<JsonConfig>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct JsonConfig {
+    chainid: Option<String>,
+}
+
+fn parse_alloc(alloc: &BTreeMap<String, JsonAccount>) -> Result<HashMap<Address, Account>> {
+    alloc
+        .iter()
+        .map(|(address, account)| {
+            let storage = account
+                .storage
+                .iter()
+                .map(|(k, v)| Ok((parse_uint(k)?, parse_uint(v)?)))
+                .collect::<Result<HashMap<_, _>>>()?
+                .into_iter()
+                .filter(|(_, v)| *v != Uint256::default())
+                .collect();
+            let account = Account {
+                nonce: parse_u64(&account.nonce)?,
+                balance: parse_uint(&account.balance)?,
+                code: parse_bytes(&account.code)?,
+                storage,
+            };
+            Ok((parse_address(address)?, account))
+        })
+        .collect()
+}
+
+// address of a private key
+fn secret_key_address(key: &[u8]) -> Result<Address> {
+    let key = SigningKey::from_slice(key)?;
+    let point = key.verifying_key().to_encoded_point(false);
+    let hash = keccak256(&point.as_bytes()[1..]);
+    Ok(uint_to_address(&Uint256::from_bytes_be(&hash[12..])))
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CaseStatus {
+    Passed,
+    Failed(String),
+    Skipped(String),
+}
+
+// one post entry of one fork
+#[derive(Debug, Clone)]
+pub struct CaseResult {
+    pub name: String,
+    pub fork: String,
+    // data, gas and value index into the transaction matrix
+    pub indexes: (usize, usize, usize),
+    pub status: CaseStatus,
+}
+
+impl fmt::Display for CaseResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (d, g, v) = self.indexes;
+        let case = format!("{}[{}][d={},g={},v={}]", self.name, self.fork, d, g, v);
+        match &self.status {
+            CaseStatus::Passed => write!(f, "PASS {}", case),
+            CaseStatus::Failed(reason) => write!(f, "FAIL {}: {}", case, reason),
+            CaseStatus::Skipped(reason) => write!(f, "SKIP {}: {}", case, reason),
+        }
+    }
+}
+
+// BlockchainTests fixtures hold blocks to import instead of a transaction matrix, and are
+// not supported
+pub fn is_blockchain_test(json: &str) -> bool {
+    serde_json::from_str::<BTreeMap<String, Value>>(json)
+        .is_ok_and(|tests| tests.values().any(|t| t.get("blocks").is_some()))
+}
+
+// a GeneralStateTests fixture
+#[derive(Debug, Clone)]
+pub struct StateTest {
+    pub name: String,
+    json: JsonStateTest,
+}
+
+impl StateTest {
+    // every test of a fixture file, which usually holds a single one
+    pub fn from_json(json: &str) -> Result<Vec<StateTest>> {
+        if is_blockchain_test(json) {
+            return Err(anyhow!("Blockchain tests not supported"));
+        }
+        let tests: BTreeMap<String, JsonStateTest> = serde_json::from_str(json)?;
+        Ok(tests
+            .into_iter()
+            .map(|(name, json)| StateTest { name, json })
+            .collect())
+    }
+
+    pub fn load(path: &Path) -> Result<Vec<StateTest>> {
+        let json = fs::read_to_string(path)?;
+        StateTest::from_json(&json).with_context(|| format!("Invalid fixture {}", path.display()))
+    }
+
+    fn env(&self, spec: SpecId) -> Result<Env> {
+        let env = &self.json.env;
+        let number = parse_u64(&env.current_number)?;
+        let optional =
+            |value: &Option<String>| value.as_deref().map_or(Ok(Uint256::default()), parse_uint);
+        let mut block = BlockEnv {
+            number,
+            coinbase: parse_address(&env.current_coinbase)?,
+            timestamp: parse_u64(&env.current_timestamp)?,
+            gas_limit: parse_u64(&env.current_gas_limit)?,
+            basefee: optional(&env.current_base_fee)?,
+            difficulty: optional(&env.current_difficulty)?,
+            prevrandao: optional(&env.current_random)?,
+            excess_blob_gas: env
+                .current_excess_blob_gas
+                .as_deref()
+                .map_or(Ok(0), parse_u64)?,
+            ..Default::default()
+        };
+        if !spec.is_enabled_in(SpecId::London) {
+            block.basefee = Uint256::default();
+        }
+        // the hash the reference implementations use for BLOCKHASH in state tests
+        for n in number.saturating_sub(256)..number {
+            let hash = keccak256(n.to_string().as_bytes());
+            block.block_hashes.insert(n, Uint256::from_bytes_be(&hash));
+        }
+        let chain_id = match self.json.config.as_ref().and_then(|c| c.chainid.as_deref()) {
+            Some(id) => parse_u64(id)?,
+            None => 1,
+        };
+        Ok(Env {
+            chain_id,
+            block,
+            ..Default::default()
+        })
+    }
+
+    fn transaction(&self, indexes: &JsonIndexes) -> Result<Transaction> {
+        let tx = &self.json.transaction;
+        let caller = match (&tx.sender, &tx.secret_key) {
+            (Some(sender), _) => parse_address(sender)?,
+            (None, Some(key)) => secret_key_address(&parse_bytes(key)?)?,
+            (None, None) => return Err(anyhow!("Transaction without sender")),
+        };
+        let index = |values: &[String], i: usize| {
+            values
+                .get(i)
+                .cloned()
+                .ok_or(anyhow!("Index {} out of range", i))
+        };
+        let to = match tx.to.as_str() {
+            "" => None,
+            to => Some(parse_address(to)?),
+        };
+        let access_list = match tx.access_lists.as_ref().and_then(|l| l.get(indexes.data)) {
+            Some(Some(items)) => items
+                .iter()
+                .map(|item| {
+                    let keys = item
+                        .storage_keys
+                        .iter()
+                        .map(|k| parse_uint(k))
+                        .collect::<Result<_>>()?;
+                    Ok((parse_address(&item.address)?, keys))
+                })
+                .collect::<Result<_>>()?,
+            _ => vec![],
+        };
+        let gas_price = tx
+            .gas_price
+            .as_ref()
+            .or(tx.max_fee_per_gas.as_ref())
+            .ok_or(anyhow!("Transaction without gas price"))?;
+        Ok(Transaction {
+            caller,
+            to,
+            value: parse_uint(&index(&tx.value, indexes.value)?)?,
+            data: parse_bytes(&index(&tx.data, indexes.data)?)?,
+            gas_limit: parse_u64(&index(&tx.gas_limit, indexes.gas)?)?,
+            gas_price: parse_uint(gas_price)?,
+            max_priority_fee_per_gas: tx
+                .max_priority_fee_per_gas
+                .as_deref()
+                .map(parse_uint)
+                .transpose()?,
+            nonce: Some(parse_u64(&tx.nonce)?),
+            access_list,
+            blob_hashes: tx
+                .blob_versioned_hashes
+                .iter()
+                .map(|h| parse_uint(h))
+                .collect::<Result<_>>()?,
+            max_fee_per_blob_gas: tx
+                .max_fee_per_blob_gas
+                .as_deref()
+                .map(parse_uint)
+                .transpose()?,
+        })
+    }
+
+    fn run_case(&self, spec: SpecId, post: &JsonPost) -> Result<CaseStatus> {
+        let mut state = State::default();
+        state.accounts = parse_alloc(&self.json.pre)?;
+        let env = self.env(spec)?;
+        let tx = self.transaction(&post.indexes)?;
+        let logs = match (tx.execute(&mut state, &env, spec), &post.expect_exception) {
+            (Ok(_), Some(exception)) => {
+                return Ok(CaseStatus::Failed(format!(
+                    "Expected exception {}",
+                    exception
+                )))
+            }
+            (Err(e), None) => return Ok(CaseStatus::Failed(format!("Invalid transaction: {}", e))),
+            (Err(_), Some(_)) => vec![],
+            (Ok(result), None) => result.logs,
+        };
+
+        let logs = format!("0x{}", hex::encode(logs_hash(&logs)));
+        if logs != post.logs.to_lowercase() {
+            return Ok(CaseStatus::Failed(format!(
+                "Logs hash mismatch: expected {}, got {}",
+                post.logs, logs
+            )));
+        }
+        let root = format!("0x{}", hex::encode(state.state_root()));
+        if root == post.hash.to_lowercase() {
+            return Ok(CaseStatus::Passed);
+        }
+        // newer fixtures carry the post state, which tells which account differs
+        if let Some(expected) = &post.state {
+            let expected = parse_alloc(expected)?;
+            let mut addresses: Vec<&Address> =
+                expected.keys().chain(state.accounts.keys()).collect();
+            addresses.sort();
+            addresses.dedup();
+            for address in addresses {
+                let (actual, expected) = (state.accounts.get(address), expected.get(address));
+                if actual != expected {
+                    return Ok(CaseStatus::Failed(format!(
+                        "Account 0x{} mismatch: expected {:?}, got {:?}",
+                        hex::encode(address),
+                        expected,
+                        actual
+                    )));
+                }
+            }
+        }
+        Ok(CaseStatus::Failed(format!(
+            "State root mismatch: expected {}, got {}",
+            post.hash, root
+        )))
+    }
+
+    // run every post entry, only those of `fork` if given
+    pub fn run(&self, fork: Option<SpecId>) -> Vec<CaseResult> {
+        let mut results = vec![];
+        for (fork_name, posts) in &self.json.post {
+            let spec = fork_name.parse::<SpecId>();
+            if fork.is_some_and(|f| spec.as_ref().ok() != Some(&f)) {
+                continue;
+            }
+            for post in posts {
+                let status = match &spec {
+                    Ok(spec) => self
+                        .run_case(*spec, post)
+                        .unwrap_or_else(|e| CaseStatus::Failed(e.to_string())),
+                    Err(_) => CaseStatus::Skipped("Unknown fork".into()),
+                };
+                results.push(CaseResult {
+                    name: self.name.clone(),
+                    fork: fork_name.clone(),
+                    indexes: (post.indexes.data, post.indexes.gas, post.indexes.value),
+                    status,
+                });
+            }
+        }
+        results
+    }
+}
+
+// fixture files under `path`, which may be a single file
+pub fn find_fixtures(path: &Path) -> Result<Vec<PathBuf>> {
+    if path.is_file() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+    let mut files = vec![];
+    for entry in fs::read_dir(path)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            files.extend(find_fixtures(&path)?);
+        } else if path.extension().is_some_and(|e| e == "json") {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_blockchain_test, CaseStatus, StateTest};
+
+    // sstore(0, 1) called by the usual test key with a legacy transaction
+    const FIXTURE: &str = r#"{
+        "sstore": {
+            "env": {
+                "currentCoinbase": "0x2adc25665018aa1fe0e6bc666dac8fc2697ff9ba",
+                "currentGasLimit": "0x05f5e100",
+                "currentNumber": "0x01",
+                "currentTimestamp": "0x03e8",
+                "currentBaseFee": "0x07",
+                "currentRandom": "0x0000000000000000000000000000000000000000000000000000000000020000"
+            },
+            "pre": {
+                "0xa94f5374fce5edbc8e2a8697c15331677e6ebf0b": {
+                    "balance": "0x0de0b6b3a7640000", "code": "0x", "nonce": "0x00", "storage": {}
+                },
+                "0x0000000000000000000000000000000000001000": {
+                    "balance": "0x00", "code": "0x600160005500", "nonce": "0x01", "storage": {}
+                }
+            },
+            "transaction": {
+                "data": ["0x"],
+                "gasLimit": ["0x0186a0", "0x5207"],
+                "value": ["0x00"],
+                "gasPrice": "0x0a",
+                "nonce": "0x00",
+                "secretKey": "0x45a915e4d060149eb4365960e6a7a45f334393093061116b197e3240065ff2d8",
+                "to": "0x0000000000000000000000000000000000001000"
+            },
+            "post": {
+                "Cancun": [
+                    {
+                        "hash": "0x4536eefecba03d3e6087d6c34d7660350a0070da704f23c63d7ba40d503ec57a",
+                        "logs": "0x1dcc4de8dec75d7aab85b567b6ccd41ad312451b948a7413f0a142fd40d49347",
+                        "indexes": {"data": 0, "gas": 0, "value": 0},
+                        "state": {
+                            "0xa94f5374fce5edbc8e2a8697c15331677e6ebf0b": {
+                                "balance": "0x0de0b6b3a75d6c2c", "code": "0x", "nonce": "0x01", "storage": {}
+                            },
+                            "0x0000000000000000000000000000000000001000": {
+                                "balance": "0x00", "code": "0x600160005500", "nonce": "0x01",
+                                "storage": {"0x00": "0x01"}
+                            },
+                            "0x2adc25665018aa1fe0e6bc666dac8fc2697ff9ba": {
+                                "balance": "0x01f926", "code": "0x", "nonce": "0x00", "storage": {}
+                            }
+                        }
+                    },
+                    {
+                        "hash": "0x3a51338da28a8e9ef120088a2feef73c1dfa60354dd08c7a1d73c4aa1bfe6ee6",
+                        "logs": "0x1dcc4de8dec75d7aab85b567b6ccd41ad312451b948a7413f0a142fd40d49347",
+                        "indexes": {"data": 0, "gas": 1, "value": 0},
+                        "expectException": "TransactionException.INTRINSIC_GAS_TOO_LOW"
+                    }
+                ],
+                "Frontier": [
+                    {"hash": "0x00", "logs": "0x1dcc4de8dec75d7aab85b567b6ccd41ad312451b948a7413f0a142fd40d49347", "indexes": {"data": 0, "gas": 0, "value": 0}}
+                ]
+            }
+        }
+    }"#;
+
+    #[test]
+    fn test_state_test() {
+        let tests = StateTest::from_json(FIXTURE).unwrap();
+        let results = tests[0].run(None);
+        let statuses: Vec<_> = results.iter().map(|r| r.status.clone()).collect();
+        assert_eq!(statuses[..2], [CaseStatus::Passed, CaseStatus::Passed]);
+        // wrong root and no post state to find the account with
+        assert!(
+            matches!(&statuses[2], CaseStatus::Failed(e) if e.starts_with("State root mismatch"))
+        );
+        assert_eq!(tests[0].run(Some(crate::spec::SpecId::Frontier)).len(), 1);
+    }
+
+    #[test]
+    fn test_blockchain_test() {
+        let json = r#"{"block": {"genesisBlockHeader": {}, "blocks": [], "pre": {}}}"#;
+        assert!(is_blockchain_test(json));
+        assert!(!is_blockchain_test(FIXTURE));
+        let error = StateTest::from_json(json).unwrap_err();
+        assert_eq!(error.to_string(), "Blockchain tests not supported");
+    }
+}
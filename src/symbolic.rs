@@ -0,0 +1,842 @@
+use std::{collections::HashMap, fmt, rc::Rc};
+
+use num_traits::Zero;
+
+use crate::{
+    block::Block,
+    emulator::{check_instruction, CallKind, Halt, VmError},
+    expr,
+    formatter::mnemonic,
+    opcode::OpCode,
+    parser::parse_bytes_with_spec,
+    spec::SpecId,
+    util::keccak256,
+    Uint256,
+};
+
+// name of the calldata size, which bounds every calldata read
+pub const CALLDATASIZE: &str = "calldatasize";
+
+// memory past this is taken to run out of gas
+const MEMORY_LIMIT: usize = 1 << 20;
+
+pub use crate::expr::{constant, op};
+
+pub type Expr = expr::Expr<Input>;
+pub type Word = Rc<Expr>;
+
+// inputs of the transaction a value is built from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Input {
+    // named after the instruction reading it, like `caller` or `timestamp`
+    Var(String),
+    // 32 bytes of calldata at a byte offset, zero past `calldatasize`
+    Calldata(Word),
+    Sload(Rc<Storage>, Word),
+    // KECCAK256 of memory bytes
+    Sha3(Vec<Word>),
+}
+
+// storage of the executing account as a chain of writes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Storage {
+    // unknown storage from before the transaction
+    Initial,
+    // all zero, like transient storage when a transaction starts
+    Empty,
+    Store(Rc<Storage>, Word, Word),
+}
+
+pub fn var(name: impl Into<String>) -> Word {
+    Rc::new(Expr::Leaf(Input::Var(name.into())))
+}
+
+// word made of 32 memory bytes, big-endian
+fn word(bytes: &[Word]) -> Word {
+    // bytes of a single stored word
+    if let Expr::Op(OpCode::BYTE, args) = &*bytes[0] {
+        let source = &args[1];
+        let is_source = |(i, b): (usize, &Word)| match &**b {
+            Expr::Op(OpCode::BYTE, args) => {
+                args[0].constant() == Some(&i.into()) && Rc::ptr_eq(&args[1], source)
+            }
+            _ => false,
+        };
+        if bytes.iter().enumerate().all(is_source) {
+            return source.clone();
+        }
+    }
+    bytes
+        .iter()
+        .enumerate()
+        .fold(constant(0u32), |acc, (i, b)| {
+            let shifted = op(OpCode::SHL, vec![constant(8 * (31 - i)), b.clone()]);
+            op(OpCode::OR, vec![acc, shifted])
+        })
+}
+
+// bytes grouped into words where they fill one, for display
+fn pack(bytes: &[Word]) -> Vec<Word> {
+    let chunks = bytes.chunks_exact(32);
+    let rest = chunks.remainder();
+    chunks.map(word).chain(rest.iter().cloned()).collect()
+}
+
+fn sload(storage: &Rc<Storage>, key: &Word) -> Word {
+    match &**storage {
+        Storage::Empty => constant(0u32),
+        Storage::Store(_, k, value) if k == key => value.clone(),
+        // writes to another known slot do not matter
+        Storage::Store(previous, k, _) if k.constant().is_some() && key.constant().is_some() => {
+            sload(previous, key)
+        }
+        _ => Rc::new(Expr::Leaf(Input::Sload(storage.clone(), key.clone()))),
+    }
+}
+
+fn join(words: &[Word]) -> String {
+    words
+        .iter()
+        .map(|w| w.to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expr::Const(value) => write!(f, "0x{:x}", value),
+            Expr::Leaf(Input::Var(name)) => write!(f, "{}", name),
+            Expr::Leaf(Input::Calldata(offset)) => write!(f, "calldata[{}]", offset),
+            Expr::Op(op, args) => write!(f, "{}({})", mnemonic(op), join(args)),
+            Expr::Leaf(Input::Sload(storage, key)) => write!(f, "{}[{}]", storage, key),
+            Expr::Leaf(Input::Sha3(bytes)) => write!(f, "keccak256({})", join(&pack(bytes))),
+        }
+    }
+}
+
+impl fmt::Display for Storage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Storage::Initial => write!(f, "storage"),
+            Storage::Empty => write!(f, "empty"),
+            Storage::Store(previous, key, value) => {
+                write!(f, "store({}, {}, {})", previous, key, value)
+            }
+        }
+    }
+}
+
+// `condition` is nonzero exactly when `taken`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Constraint {
+    pub condition: Word,
+    pub taken: bool,
+    // instructions executed up to the JUMPI which made it, see `Path::visited`
+    pub step: usize,
+}
+
+impl fmt::Display for Constraint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let relation = if self.taken { "!=" } else { "==" };
+        write!(f, "{} {} 0", self.condition, relation)
+    }
+}
+
+// what a path does outside of its own frame, at the position of the instruction doing it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Effect {
+    Sstore {
+        position: usize,
+        key: Word,
+        value: Word,
+    },
+    Log {
+        position: usize,
+        topics: Vec<Word>,
+        data: Vec<Word>,
+    },
+    Call {
+        position: usize,
+        kind: CallKind,
+        gas: Word,
+        address: Word,
+        value: Option<Word>,
+        input: Vec<Word>,
+        // nonzero when the callee succeeded
+        success: Word,
+    },
+    Create {
+        position: usize,
+        value: Word,
+        code: Vec<Word>,
+        salt: Option<Word>,
+        address: Word,
+    },
+    Selfdestruct {
+        position: usize,
+        beneficiary: Word,
+    },
+}
+
+impl Effect {
+    pub fn position(&self) -> usize {
+        match self {
+            Effect::Sstore { position, .. }
+            | Effect::Log { position, .. }
+            | Effect::Call { position, .. }
+            | Effect::Create { position, .. }
+            | Effect::Selfdestruct { position, .. } => *position,
+        }
+    }
+}
+
+impl fmt::Display for Effect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Effect::Sstore { key, value, .. } => write!(f, "SSTORE({}, {})", key, value),
+            Effect::Log { topics, data, .. } => {
+                write!(f, "LOG{}(", topics.len())?;
+                for topic in topics {
+                    write!(f, "{}, ", topic)?;
+                }
+                write!(f, "data: [{}])", join(&pack(data)))
+            }
+            Effect::Call {
+                kind,
+                address,
+                value,
+                input,
+                success,
+                ..
+            } => {
+                write!(f, "{:?}({}", kind, address)?;
+                if let Some(value) = value {
+                    write!(f, ", value: {}", value)?;
+                }
+                write!(f, ", input: [{}]) -> {}", join(&pack(input)), success)
+            }
+            Effect::Create {
+                value,
+                code,
+                salt,
+                address,
+                ..
+            } => {
+                write!(f, "CREATE(value: {}", value)?;
+                if let Some(salt) = salt {
+                    write!(f, ", salt: {}", salt)?;
+                }
+                write!(f, ", code: [{}]) -> {}", join(&pack(code)), address)
+            }
+            Effect::Selfdestruct { beneficiary, .. } => write!(f, "SELFDESTRUCT({})", beneficiary),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum End {
+    Halt(Halt),
+    // exploration stopped before the path halted
+    Incomplete(String),
+}
+
+impl fmt::Display for End {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            End::Halt(Halt::Stop) => write!(f, "stop"),
+            End::Halt(Halt::Return) => write!(f, "return"),
+            End::Halt(Halt::Revert) => write!(f, "revert"),
+            End::Halt(Halt::Error(e)) => write!(f, "error: {}", e),
+            End::Incomplete(reason) => write!(f, "incomplete: {}", reason),
+        }
+    }
+}
+
+impl From<VmError> for End {
+    fn from(error: VmError) -> Self {
+        End::Halt(Halt::Error(error))
+    }
+}
+
+// one way through the code
+#[derive(Debug, Clone)]
+pub struct Path {
+    pub constraints: Vec<Constraint>,
+    pub effects: Vec<Effect>,
+    // positions of the executed instructions, in order
+    pub visited: Vec<usize>,
+    pub end: End,
+    // bytes of return or revert data
+    pub output: Vec<Word>,
+}
+
+impl fmt::Display for Path {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "end: {}", self.end)?;
+        if !self.output.is_empty() {
+            writeln!(f, "output: [{}]", join(&pack(&self.output)))?;
+        }
+        for constraint in &self.constraints {
+            writeln!(f, "constraint: {}", constraint)?;
+        }
+        for effect in &self.effects {
+            writeln!(f, "effect: {}", effect)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Limits {
+    pub max_paths: usize,
+    // instructions executed on a single path
+    pub max_steps: usize,
+    // forks at the same JUMPI on a single path
+    pub loop_bound: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_paths: 256,
+            max_steps: 10_000,
+            loop_bound: 4,
+        }
+    }
+}
+
+// value of a word that has to be known to go on
+fn concrete(word: &Word, what: &str) -> Result<usize, End> {
+    match word.constant() {
+        Some(value) => Ok(value.try_into().unwrap_or(usize::MAX)),
+        None => Err(End::Incomplete(format!("symbolic {}: {}", what, word))),
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Machine {
+    index: usize,
+    stack: Vec<Word>,
+    // one word per byte
+    memory: Vec<Word>,
+    storage: Rc<Storage>,
+    transient: Rc<Storage>,
+    constraints: Vec<Constraint>,
+    effects: Vec<Effect>,
+    visited: Vec<usize>,
+    forks: HashMap<usize, usize>,
+    // calls and creates made so far, naming their results
+    calls: usize,
+    output: Vec<Word>,
+    // set on a fork which ends as soon as it is taken
+    end: Option<End>,
+}
+
+impl Machine {
+    fn new() -> Self {
+        Self {
+            index: 0,
+            stack: vec![],
+            memory: vec![],
+            storage: Rc::new(Storage::Initial),
+            transient: Rc::new(Storage::Empty),
+            constraints: vec![],
+            effects: vec![],
+            visited: vec![],
+            forks: HashMap::new(),
+            calls: 0,
+            output: vec![],
+            end: None,
+        }
+    }
+
+    fn pop(&mut self) -> Word {
+        self.stack.pop().expect("stack checked before step")
+    }
+
+    fn expand_memory(&mut self, offset: usize, size: usize) -> Result<(), End> {
+        if size == 0 {
+            return Ok(());
+        }
+        let end = offset
+            .checked_add(size)
+            .filter(|end| *end <= MEMORY_LIMIT)
+            .ok_or(VmError::OutOfGas)?;
+        let end = end.div_ceil(32) * 32;
+        if end > self.memory.len() {
+            self.memory.resize(end, constant(0u32));
+        }
+        Ok(())
+    }
+
+    fn read_memory(&mut self, offset: &Word, size: &Word) -> Result<Vec<Word>, End> {
+        let size = concrete(size, "memory size")?;
+        if size == 0 {
+            return Ok(vec![]);
+        }
+        let offset = concrete(offset, "memory offset")?;
+        self.expand_memory(offset, size)?;
+        Ok(self.memory[offset..offset + size].to_vec())
+    }
+
+    fn pop_memory(&mut self) -> Result<Vec<Word>, End> {
+        let (offset, size) = (self.pop(), self.pop());
+        self.read_memory(&offset, &size)
+    }
+
+    // write `bytes(i)` for `size` bytes at a popped offset
+    fn copy_to_memory(
+        &mut self,
+        offset: Word,
+        size: Word,
+        bytes: impl Fn(usize) -> Word,
+    ) -> Result<(), End> {
+        let size = concrete(&size, "copy size")?;
+        if size == 0 {
+            return Ok(());
+        }
+        let offset = concrete(&offset, "memory offset")?;
+        self.expand_memory(offset, size)?;
+        for i in 0..size {
+            self.memory[offset + i] = bytes(i);
+        }
+        Ok(())
+    }
+
+    // byte `i` of `word`
+    fn byte(i: usize, word: &Word) -> Word {
+        op(OpCode::BYTE, vec![constant(i), word.clone()])
+    }
+
+    // fresh name for the result of the next call or create
+    fn next_call(&mut self) -> usize {
+        self.calls += 1;
+        self.calls
+    }
+
+    fn return_data_word(&self, offset: usize) -> Word {
+        var(format!("returndata{}[0x{:x}]", self.calls, offset))
+    }
+
+    fn jump(&mut self, blocks: &[Block], target: &Word) -> Result<(), End> {
+        let target = concrete(target, "jump target")?;
+        match blocks.binary_search_by_key(&target, |b| b.position) {
+            Ok(i) if blocks[i].opcode == OpCode::JUMPDEST => {
+                self.index = i;
+                Ok(())
+            }
+            _ => Err(VmError::InvalidJump(target).into()),
+        }
+    }
+
+    // run one instruction, returns the other side of a fork
+    fn step(
+        &mut self,
+        blocks: &[Block],
+        code: &[u8],
+        spec: SpecId,
+        limits: &Limits,
+        feasible: &mut dyn FnMut(&[Constraint]) -> bool,
+    ) -> Result<Option<Machine>, End> {
+        let block = &blocks[self.index];
+        self.visited.push(block.position);
+        check_instruction(&block.opcode, self.stack.len(), spec)?;
+        let (inputs, _) = block.opcode.stack_io();
+        let index = self.index;
+        let mut fork = None;
+
+        // every instruction is listed so new ones have to be handled here
+        match block.opcode.clone() {
+            OpCode::STOP => return Err(End::Halt(Halt::Stop)),
+            instruction @ (OpCode::ADD
+            | OpCode::MUL
+            | OpCode::SUB
+            | OpCode::DIV
+            | OpCode::SDIV
+            | OpCode::MOD
+            | OpCode::SMOD
+            | OpCode::ADDMOD
+            | OpCode::MULMOD
+            | OpCode::EXP
+            | OpCode::SIGNEXTEND
+            | OpCode::LT
+            | OpCode::GT
+            | OpCode::SLT
+            | OpCode::SGT
+            | OpCode::EQ
+            | OpCode::ISZERO
+            | OpCode::AND
+            | OpCode::OR
+            | OpCode::XOR
+            | OpCode::NOT
+            | OpCode::BYTE
+            | OpCode::SHL
+            | OpCode::SHR
+            | OpCode::SAR
+            // uninterpreted reads of the world
+            | OpCode::BALANCE
+            | OpCode::EXTCODESIZE
+            | OpCode::EXTCODEHASH
+            | OpCode::BLOCKHASH
+            | OpCode::BLOBHASH) => {
+                let args = (0..inputs).map(|_| self.pop()).collect();
+                self.stack.push(op(instruction, args));
+            }
+            instruction @ (OpCode::ADDRESS
+            | OpCode::ORIGIN
+            | OpCode::CALLER
+            | OpCode::CALLVALUE
+            | OpCode::CALLDATASIZE
+            | OpCode::GASPRICE
+            | OpCode::COINBASE
+            | OpCode::TIMESTAMP
+            | OpCode::NUMBER
+            | OpCode::DIFFICULTY
+            | OpCode::GASLIMIT
+            | OpCode::CHAINID
+            | OpCode::SELFBALANCE
+            | OpCode::BASEFEE
+            | OpCode::BLOBBASEFEE) => self.stack.push(var(mnemonic(&instruction).to_lowercase())),
+            OpCode::GAS => self.stack.push(var(format!("gas@{:x}", block.position))),
+            OpCode::SHA3 => {
+                let bytes = self.pop_memory()?;
+                let values: Option<Vec<u8>> = bytes
+                    .iter()
+                    .map(|b| b.constant().and_then(|v| u8::try_from(v).ok()))
+                    .collect();
+                self.stack.push(match values {
+                    Some(values) => constant(Uint256::from_bytes_be(&keccak256(&values))),
+                    None => Rc::new(Expr::Leaf(Input::Sha3(bytes))),
+                });
+            }
+            OpCode::CALLDATALOAD => {
+                let offset = self.pop();
+                self.stack.push(Rc::new(Expr::Leaf(Input::Calldata(offset))));
+            }
+            OpCode::CALLDATACOPY => {
+                let (dest, offset, size) = (self.pop(), self.pop(), self.pop());
+                self.copy_to_memory(dest, size, |i| {
+                    let at = op(OpCode::ADD, vec![offset.clone(), constant(i)]);
+                    Self::byte(0, &Rc::new(Expr::Leaf(Input::Calldata(at))))
+                })?;
+            }
+            OpCode::CODESIZE => self.stack.push(constant(code.len())),
+            OpCode::CODECOPY => {
+                let (dest, offset, size) = (self.pop(), self.pop(), self.pop());
+                let offset = concrete(&offset, "code offset")?;
+                self.copy_to_memory(dest, size, |i| {
+                    let at = offset.saturating_add(i);
+                    constant(code.get(at).copied().unwrap_or_default())
+                })?;
+            }
+            OpCode::EXTCODECOPY => {
+                let (address, dest, offset, size) =
+                    (self.pop(), self.pop(), self.pop(), self.pop());
+                self.copy_to_memory(dest, size, |i| {
+                    let at = op(OpCode::ADD, vec![offset.clone(), constant(i)]);
+                    let code = op(OpCode::EXTCODECOPY, vec![address.clone(), at]);
+                    Self::byte(0, &code)
+                })?;
+            }
+            OpCode::RETURNDATASIZE => self.stack.push(match self.calls {
+                0 => constant(0u32),
+                n => var(format!("returndatasize{}", n)),
+            }),
+            OpCode::RETURNDATACOPY => {
+                let (dest, offset, size) = (self.pop(), self.pop(), self.pop());
+                if self.calls == 0 && concrete(&size, "copy size")? > 0 {
+                    return Err(VmError::ReturnDataOutOfBounds.into());
+                }
+                let offset = concrete(&offset, "return data offset")?;
+                let words: Vec<_> = (0..concrete(&size, "copy size")?.div_ceil(32) + 1)
+                    .map(|i| self.return_data_word((offset / 32 + i) * 32))
+                    .collect();
+                self.copy_to_memory(dest, size, |i| {
+                    let at = offset + i;
+                    Self::byte(at % 32, &words[at / 32 - offset / 32])
+                })?;
+            }
+            OpCode::POP => {
+                self.pop();
+            }
+            OpCode::MLOAD => {
+                let offset = concrete(&self.pop(), "memory offset")?;
+                self.expand_memory(offset, 32)?;
+                let value = word(&self.memory[offset..offset + 32]);
+                self.stack.push(value);
+            }
+            OpCode::MSTORE => {
+                let (offset, value) = (self.pop(), self.pop());
+                self.copy_to_memory(offset, constant(32u32), |i| Self::byte(i, &value))?;
+            }
+            OpCode::MSTORE8 => {
+                let (offset, value) = (self.pop(), self.pop());
+                self.copy_to_memory(offset, constant(1u32), |_| Self::byte(31, &value))?;
+            }
+            OpCode::SLOAD => {
+                let key = self.pop();
+                self.stack.push(sload(&self.storage, &key));
+            }
+            OpCode::SSTORE => {
+                let (key, value) = (self.pop(), self.pop());
+                self.storage = Rc::new(Storage::Store(
+                    self.storage.clone(),
+                    key.clone(),
+                    value.clone(),
+                ));
+                self.effects.push(Effect::Sstore {
+                    position: block.position,
+                    key,
+                    value,
+                });
+            }
+            OpCode::TLOAD => {
+                let key = self.pop();
+                self.stack.push(sload(&self.transient, &key));
+            }
+            OpCode::TSTORE => {
+                let (key, value) = (self.pop(), self.pop());
+                self.transient = Rc::new(Storage::Store(self.transient.clone(), key, value));
+            }
+            OpCode::JUMP => {
+                let target = self.pop();
+                self.jump(blocks, &target)?;
+            }
+            OpCode::JUMPI => {
+                let (target, condition) = (self.pop(), self.pop());
+                // a condition decided earlier on the path goes the same way again
+                let decided = self
+                    .constraints
+                    .iter()
+                    .find(|c| c.condition == condition)
+                    .map(|c| c.taken);
+                match (condition.constant(), decided) {
+                    (Some(value), _) if value.is_zero() => {}
+                    (Some(_), _) | (None, Some(true)) => self.jump(blocks, &target)?,
+                    (None, Some(false)) => {}
+                    (None, None) => {
+                        let forks = self.forks.entry(index).or_default();
+                        *forks += 1;
+                        if *forks > limits.loop_bound {
+                            return Err(End::Incomplete("loop bound".into()));
+                        }
+                        let step = self.visited.len();
+                        let mut taken = self.clone();
+                        taken.constraints.push(Constraint {
+                            condition: condition.clone(),
+                            taken: true,
+                            step,
+                        });
+                        taken.end = taken.jump(blocks, &target).err();
+                        self.constraints.push(Constraint {
+                            condition,
+                            taken: false,
+                            step,
+                        });
+                        let taken = feasible(&taken.constraints).then_some(taken);
+                        if feasible(&self.constraints) {
+                            fork = taken;
+                        } else {
+                            *self = taken.ok_or(End::Incomplete("infeasible".into()))?;
+                        }
+                    }
+                }
+            }
+            OpCode::PC => self.stack.push(constant(block.position)),
+            OpCode::MSIZE => self.stack.push(constant(self.memory.len())),
+            OpCode::JUMPDEST => {}
+            OpCode::MCOPY => {
+                let (dest, source, size) = (self.pop(), self.pop(), self.pop());
+                let length = concrete(&size, "copy size")?;
+                if length > 0 {
+                    let source = concrete(&source, "memory offset")?;
+                    self.expand_memory(source, length)?;
+                    let bytes = self.memory[source..source + length].to_vec();
+                    self.copy_to_memory(dest, size, |i| bytes[i].clone())?;
+                }
+            }
+            instruction @ (OpCode::PUSH0 | OpCode::PUSHN(..) | OpCode::TRUNCATED_PUSHN(..)) => {
+                self.stack.push(constant(instruction.push_value().unwrap()));
+            }
+            OpCode::DUPN(n) => {
+                let value = self.stack[self.stack.len() - n as usize].clone();
+                self.stack.push(value);
+            }
+            OpCode::SWAPN(n) => {
+                let len = self.stack.len();
+                self.stack.swap(len - 1, len - 1 - n as usize);
+            }
+            OpCode::LOGN(n) => {
+                let (offset, size) = (self.pop(), self.pop());
+                let topics = (0..n).map(|_| self.pop()).collect();
+                let data = self.read_memory(&offset, &size)?;
+                self.effects.push(Effect::Log {
+                    position: block.position,
+                    topics,
+                    data,
+                });
+            }
+            instruction @ (OpCode::CREATE | OpCode::CREATE2) => {
+                let value = self.pop();
+                let code = self.pop_memory()?;
+                let salt = (instruction == OpCode::CREATE2).then(|| self.pop());
+                let address = var(format!("create{}", self.next_call()));
+                self.stack.push(address.clone());
+                self.effects.push(Effect::Create {
+                    position: block.position,
+                    value,
+                    code,
+                    salt,
+                    address,
+                });
+            }
+            instruction @ (OpCode::CALL | OpCode::CALLCODE | OpCode::DELEGATECALL | OpCode::STATICCALL) => {
+                let (kind, has_value) = match instruction {
+                    OpCode::CALL => (CallKind::Call, true),
+                    OpCode::CALLCODE => (CallKind::CallCode, true),
+                    OpCode::DELEGATECALL => (CallKind::DelegateCall, false),
+                    _ => (CallKind::StaticCall, false),
+                };
+                let gas = self.pop();
+                let address = self.pop();
+                let value = has_value.then(|| self.pop());
+                let input = self.pop_memory()?;
+                let (out_offset, out_size) = (self.pop(), self.pop());
+                let success = var(format!("call{}", self.next_call()));
+                // the callee's output is unknown
+                let size = concrete(&out_size, "return data size")?;
+                let words: Vec<_> = (0..size.div_ceil(32))
+                    .map(|i| self.return_data_word(i * 32))
+                    .collect();
+                self.copy_to_memory(out_offset, out_size, |i| Self::byte(i % 32, &words[i / 32]))?;
+                self.stack.push(success.clone());
+                self.effects.push(Effect::Call {
+                    position: block.position,
+                    gas,
+                    kind,
+                    address,
+                    value,
+                    input,
+                    success,
+                });
+            }
+            OpCode::RETURN => {
+                self.output = self.pop_memory()?;
+                return Err(End::Halt(Halt::Return));
+            }
+            OpCode::REVERT => {
+                self.output = self.pop_memory()?;
+                return Err(End::Halt(Halt::Revert));
+            }
+            OpCode::SELFDESTRUCT => {
+                let beneficiary = self.pop();
+                self.effects.push(Effect::Selfdestruct {
+                    position: block.position,
+                    beneficiary,
+                });
+                return Err(End::Halt(Halt::Stop));
+            }
+            OpCode::INVALID(op) => return Err(VmError::InvalidOpcode(op).into()),
+        }
+
+        // jumps move the index themselves
+        if self.index == index {
+            self.index += 1;
+        }
+        Ok(fork)
+    }
+
+    fn into_path(self, end: End) -> Path {
+        Path {
+            constraints: self.constraints,
+            effects: self.effects,
+            visited: self.visited,
+            end,
+            output: self.output,
+        }
+    }
+}
+
+// explore the paths of `code` from its start with every input symbolic
+pub fn explore(code: &[u8], spec: SpecId, limits: &Limits) -> Vec<Path> {
+    explore_with(code, spec, limits, &mut |_| true)
+}
+
+// like `explore`, dropping the side of a fork whose constraints are not `feasible`
+pub fn explore_with(
+    code: &[u8],
+    spec: SpecId,
+    limits: &Limits,
+    feasible: &mut dyn FnMut(&[Constraint]) -> bool,
+) -> Vec<Path> {
+    let blocks = parse_bytes_with_spec(code, spec);
+    let mut pending = vec![Machine::new()];
+    let mut paths = vec![];
+    while let Some(mut machine) = pending.pop() {
+        if paths.len() >= limits.max_paths {
+            break;
+        }
+        let end = loop {
+            if let Some(end) = machine.end.take() {
+                break end;
+            }
+            if machine.index >= blocks.len() {
+                break End::Halt(Halt::Stop);
+            }
+            if machine.visited.len() >= limits.max_steps {
+                break End::Incomplete("step limit".into());
+            }
+            match machine.step(&blocks, code, spec, limits, feasible) {
+                Ok(Some(fork)) => pending.push(fork),
+                Ok(None) => {}
+                Err(end) => break end,
+            }
+        };
+        paths.push(machine.into_path(end));
+    }
+    paths
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{constant, explore, Effect, End, Expr, Input, Limits};
+    use crate::{emulator::Halt, parser::decode_hex, spec::SpecId};
+
+    #[test]
+    fn test_explore() {
+        // if calldataload(0) == 0x2a { sstore(0, caller) } else { revert(0, 0) }
+        let code = decode_hex("600035602a14600e5760006000fd5b3360005500").unwrap();
+        let paths = explore(&code, SpecId::default(), &Limits::default());
+        assert_eq!(paths.len(), 2);
+        assert_eq!(paths[0].end, End::Halt(Halt::Revert));
+        assert_eq!(
+            paths[0].constraints[0].to_string(),
+            "EQ(0x2a, calldata[0x0]) == 0"
+        );
+        assert_eq!(paths[1].end, End::Halt(Halt::Stop));
+        assert!(paths[1].constraints[0].taken);
+        assert_eq!(paths[1].visited.last(), Some(&0x13));
+        assert_eq!(paths[1].effects[0].to_string(), "SSTORE(0x0, caller)");
+    }
+
+    #[test]
+    fn test_memory() {
+        // mstore(0, calldataload(4)), sstore(1, mload(0)), sstore(2, add(2, 3)), sstore(3, sload(1))
+        let code = decode_hex("600435600052600051600155600360020160025560015460035500").unwrap();
+        let paths = explore(&code, SpecId::default(), &Limits::default());
+        assert_eq!(paths.len(), 1);
+        let values: Vec<_> = paths[0]
+            .effects
+            .iter()
+            .map(|e| match e {
+                Effect::Sstore { value, .. } => value.clone(),
+                _ => unreachable!(),
+            })
+            .collect();
+        let calldata = Expr::Leaf(Input::Calldata(constant(4u32)));
+        assert_eq!(*values[0], calldata);
+        assert_eq!(values[1], constant(5u32));
+        assert_eq!(*values[2], calldata);
+    }
+}
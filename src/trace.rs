@@ -0,0 +1,253 @@
+use std::fmt::Write;
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    statetest::{parse_bytes, parse_u64, parse_uint},
+    Uint256,
+};
+
+// one executed instruction, as in EIP-3155
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceStep {
+    pub pc: usize,
+    pub op: u8,
+    // gas left before the instruction
+    pub gas: u64,
+    pub gas_cost: u64,
+    pub mem_size: usize,
+    pub stack: Vec<Uint256>,
+    // 1 for the outermost frame
+    pub depth: usize,
+    pub refund: i64,
+    pub op_name: String,
+    pub error: Option<String>,
+}
+
+// the line following the steps
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceSummary {
+    pub output: Vec<u8>,
+    pub gas_used: u64,
+    pub error: Option<String>,
+    // not every tool prints it
+    pub state_root: Option<[u8; 32]>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Trace {
+    pub steps: Vec<TraceStep>,
+    pub summary: Option<TraceSummary>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct JsonStep {
+    pc: usize,
+    op: u8,
+    gas: String,
+    gas_cost: String,
+    mem_size: usize,
+    stack: Vec<String>,
+    depth: usize,
+    #[serde(default)]
+    refund: i64,
+    #[serde(default)]
+    op_name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct JsonSummary {
+    output: String,
+    gas_used: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    state_root: Option<String>,
+}
+
+impl TraceStep {
+    pub(crate) fn to_json(&self) -> JsonStep {
+        JsonStep {
+            pc: self.pc,
+            op: self.op,
+            gas: format!("0x{:x}", self.gas),
+            gas_cost: format!("0x{:x}", self.gas_cost),
+            mem_size: self.mem_size,
+            stack: self.stack.iter().map(|v| format!("0x{:x}", v)).collect(),
+            depth: self.depth,
+            refund: self.refund,
+            op_name: self.op_name.clone(),
+            error: self.error.clone(),
+        }
+    }
+
+    pub(crate) fn from_json(json: &JsonStep) -> Result<Self> {
+        Ok(TraceStep {
+            pc: json.pc,
+            op: json.op,
+            gas: parse_u64(&json.gas)?,
+            gas_cost: parse_u64(&json.gas_cost)?,
+            mem_size: json.mem_size,
+            stack: json
+                .stack
+                .iter()
+                .map(|v| parse_uint(v))
+                .collect::<Result<_>>()?,
+            depth: json.depth,
+            refund: json.refund,
+            op_name: json.op_name.clone(),
+            error: json.error.clone(),
+        })
+    }
+}
+
+impl TraceSummary {
+    pub(crate) fn to_json(&self) -> JsonSummary {
+        JsonSummary {
+            output: format!("0x{}", hex::encode(&self.output)),
+            gas_used: format!("0x{:x}", self.gas_used),
+            error: self.error.clone(),
+            state_root: self.state_root.map(|r| format!("0x{}", hex::encode(r))),
+        }
+    }
+
+    pub(crate) fn from_json(json: &JsonSummary) -> Result<Self> {
+        let state_root = match &json.state_root {
+            Some(root) => Some(
+                parse_bytes(root)?
+                    .try_into()
+                    .map_err(|_| anyhow!("Invalid state root {}", root))?,
+            ),
+            None => None,
+        };
+        Ok(TraceSummary {
+            output: parse_bytes(&json.output)?,
+            gas_used: parse_u64(&json.gas_used)?,
+            // geth prints an empty error on success
+            error: json.error.clone().filter(|e| !e.is_empty()),
+            state_root,
+        })
+    }
+}
+
+impl Trace {
+    // JSON lines of a tool's output, other lines are ignored
+    pub fn parse(output: &str) -> Result<Trace> {
+        let mut trace = Trace::default();
+        for line in output.lines().map(str::trim) {
+            let Ok(value) = serde_json::from_str::<Value>(line) else {
+                continue;
+            };
+            if value.get("pc").is_some() {
+                let step: JsonStep = serde_json::from_value(value)?;
+                trace.steps.push(TraceStep::from_json(&step)?);
+            } else if value.get("gasUsed").is_some() {
+                let summary: JsonSummary = serde_json::from_value(value)?;
+                trace.summary = Some(TraceSummary::from_json(&summary)?);
+            }
+        }
+        Ok(trace)
+    }
+
+    pub fn to_jsonl(&self) -> String {
+        let mut result = String::new();
+        for step in &self.steps {
+            let _ = writeln!(result, "{}", serde_json::json!(step.to_json()));
+        }
+        if let Some(summary) = &self.summary {
+            let _ = writeln!(result, "{}", serde_json::json!(summary.to_json()));
+        }
+        result
+    }
+
+    // first difference with `expected`; gas costs and error messages vary between clients
+    // and are left out, a wrong cost shows up in the gas of the next step anyway
+    pub fn divergence(&self, expected: &Trace) -> Option<String> {
+        for (i, (ours, theirs)) in self.steps.iter().zip(&expected.steps).enumerate() {
+            let field = if ours.pc != theirs.pc {
+                format!("pc {} != {}", ours.pc, theirs.pc)
+            } else if ours.op != theirs.op {
+                format!("op 0x{:02x} != 0x{:02x}", ours.op, theirs.op)
+            } else if ours.depth != theirs.depth {
+                format!("depth {} != {}", ours.depth, theirs.depth)
+            } else if ours.gas != theirs.gas {
+                format!("gas {} != {}", ours.gas, theirs.gas)
+            } else if ours.stack != theirs.stack {
+                format!("stack {:x?} != {:x?}", ours.stack, theirs.stack)
+            } else if ours.mem_size != theirs.mem_size {
+                format!("memSize {} != {}", ours.mem_size, theirs.mem_size)
+            } else {
+                continue;
+            };
+            return Some(format!(
+                "step {} (pc {}, {}): {}",
+                i, ours.pc, ours.op_name, field
+            ));
+        }
+        if self.steps.len() != expected.steps.len() {
+            return Some(format!(
+                "{} steps != {}",
+                self.steps.len(),
+                expected.steps.len()
+            ));
+        }
+        let (Some(ours), Some(theirs)) = (&self.summary, &expected.summary) else {
+            return None;
+        };
+        if ours.output != theirs.output {
+            Some(format!(
+                "output 0x{} != 0x{}",
+                hex::encode(&ours.output),
+                hex::encode(&theirs.output)
+            ))
+        } else if ours.gas_used != theirs.gas_used {
+            Some(format!("gasUsed {} != {}", ours.gas_used, theirs.gas_used))
+        } else if ours.error.is_some() != theirs.error.is_some() {
+            Some(format!("error {:?} != {:?}", ours.error, theirs.error))
+        } else if let (Some(a), Some(b)) = (ours.state_root, theirs.state_root) {
+            (a != b).then(|| format!("stateRoot 0x{} != 0x{}", hex::encode(a), hex::encode(b)))
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Trace;
+    use crate::{emulator::Emulator, parser::parse_bytes};
+
+    #[test]
+    fn test_trace() {
+        // PUSH1 1, PUSH1 2, ADD, STOP
+        let code = vec![0x60, 0x01, 0x60, 0x02, 0x01, 0x00];
+        let mut emu = Emulator::new(code.clone(), parse_bytes(&code), &[]).with_gas_limit(100);
+        emu.state.trace = Some(vec![]);
+        emu.run_to_end();
+        let trace = Trace {
+            steps: emu.state.trace.take().unwrap(),
+            summary: None,
+        };
+        let pcs: Vec<_> = trace.steps.iter().map(|s| s.pc).collect();
+        assert_eq!(pcs, [0, 2, 4, 5]);
+        assert_eq!(trace.steps[2].gas, 94);
+        assert_eq!(trace.steps[2].gas_cost, 3);
+        assert_eq!(trace.steps[3].stack, [3u32.into()]);
+        assert_eq!(trace.steps[0].op_name, "PUSH1");
+
+        let parsed = Trace::parse(&format!("not json\n{}", trace.to_jsonl())).unwrap();
+        assert_eq!(parsed, trace);
+        let mut other = parsed.clone();
+        other.steps[3].gas = 0;
+        assert_eq!(
+            trace.divergence(&other).unwrap(),
+            "step 3 (pc 5, STOP): gas 91 != 0"
+        );
+    }
+}
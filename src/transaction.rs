@@ -0,0 +1,369 @@
+use std::fmt;
+
+use num_traits::Zero;
+
+use crate::{
+    emulator::{execute_call, execute_create, CallKind, FrameResult, Halt, Message},
+    env::{Env, TxEnv},
+    gas,
+    spec::SpecId,
+    state::{create_address, Log, State},
+    Address, Uint256,
+};
+
+#[derive(Debug, Clone, Default)]
+pub struct Transaction {
+    pub caller: Address,
+    // None deploys `data` as init code
+    pub to: Option<Address>,
+    pub value: Uint256,
+    pub data: Vec<u8>,
+    pub gas_limit: u64,
+    // max fee per gas from London on
+    pub gas_price: Uint256,
+    // EIP-1559, defaults to `gas_price`
+    pub max_priority_fee_per_gas: Option<Uint256>,
+    // checked against the sender's nonce when set
+    pub nonce: Option<u64>,
+    // EIP-2930
+    pub access_list: Vec<(Address, Vec<Uint256>)>,
+    // EIP-4844
+    pub blob_hashes: Vec<Uint256>,
+    pub max_fee_per_blob_gas: Option<Uint256>,
+}
+
+// reasons a transaction cannot be included, nothing is charged for these
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InvalidTransaction {
+    NonceMismatch { expected: u64, got: u64 },
+    NonceOverflow,
+    // EIP-3607
+    SenderNotEoa,
+    InsufficientFunds,
+    IntrinsicGasTooLow,
+    GasLimitExceedsBlock,
+    GasPriceBelowBaseFee,
+    PriorityFeeAboveMaxFee,
+    BlobGasPriceTooLow,
+    InitCodeSizeExceeded,
+}
+
+impl fmt::Display for InvalidTransaction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InvalidTransaction::NonceMismatch { expected, got } => {
+                write!(f, "Nonce mismatch: expected {}, got {}", expected, got)
+            }
+            InvalidTransaction::NonceOverflow => write!(f, "Nonce overflow"),
+            InvalidTransaction::SenderNotEoa => write!(f, "Sender has code"),
+            InvalidTransaction::InsufficientFunds => write!(f, "Insufficient funds"),
+            InvalidTransaction::IntrinsicGasTooLow => write!(f, "Intrinsic gas too low"),
+            InvalidTransaction::GasLimitExceedsBlock => {
+                write!(f, "Gas limit exceeds the block gas limit")
+            }
+            InvalidTransaction::GasPriceBelowBaseFee => write!(f, "Gas price below base fee"),
+            InvalidTransaction::PriorityFeeAboveMaxFee => {
+                write!(f, "Priority fee above max fee")
+            }
+            InvalidTransaction::BlobGasPriceTooLow => write!(f, "Blob gas price too low"),
+            InvalidTransaction::InitCodeSizeExceeded => write!(f, "Init code size exceeded"),
+        }
+    }
+}
+
+impl std::error::Error for InvalidTransaction {}
+
+// receipt of an included transaction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExecutionResult {
+    pub halt: Halt,
+    // after refunds
+    pub gas_used: u64,
+    pub gas_refunded: u64,
+    pub logs: Vec<Log>,
+    // return or revert data, empty for a successful create
+    pub output: Vec<u8>,
+    pub created_address: Option<Address>,
+}
+
+impl ExecutionResult {
+    pub fn is_success(&self) -> bool {
+        matches!(self.halt, Halt::Stop | Halt::Return)
+    }
+}
+
+impl Transaction {
+    fn access_list_keys(&self) -> usize {
+        self.access_list.iter().map(|(_, keys)| keys.len()).sum()
+    }
+
+    // price actually paid per gas and the part of it that goes to the coinbase
+    fn gas_prices(
+        &self,
+        env: &Env,
+        spec: SpecId,
+    ) -> Result<(Uint256, Uint256), InvalidTransaction> {
+        if !spec.is_enabled_in(SpecId::London) {
+            return Ok((self.gas_price.clone(), self.gas_price.clone()));
+        }
+        let basefee = &env.block.basefee;
+        if self.gas_price < *basefee {
+            return Err(InvalidTransaction::GasPriceBelowBaseFee);
+        }
+        let priority = self
+            .max_priority_fee_per_gas
+            .clone()
+            .unwrap_or_else(|| self.gas_price.clone());
+        if priority > self.gas_price {
+            return Err(InvalidTransaction::PriorityFeeAboveMaxFee);
+        }
+        let price = (basefee + priority).min(self.gas_price.clone());
+        let tip = &price - basefee;
+        Ok((price, tip))
+    }
+
+    // execute against `state`, which is only modified if the transaction is valid
+    pub fn execute(
+        &self,
+        state: &mut State,
+        env: &Env,
+        spec: SpecId,
+    ) -> Result<ExecutionResult, InvalidTransaction> {
+        let is_create = self.to.is_none();
+        let intrinsic = gas::intrinsic_gas(
+            &self.data,
+            is_create,
+            self.access_list.len(),
+            self.access_list_keys(),
+            spec,
+        );
+        let floor = gas::calldata_floor(&self.data, spec);
+        if self.gas_limit < intrinsic.max(floor) {
+            return Err(InvalidTransaction::IntrinsicGasTooLow);
+        }
+        // a zero block gas limit is treated as unset
+        if env.block.gas_limit != 0 && self.gas_limit > env.block.gas_limit {
+            return Err(InvalidTransaction::GasLimitExceedsBlock);
+        }
+        if is_create
+            && spec.is_enabled_in(SpecId::Shanghai)
+            && self.data.len() > gas::MAX_INITCODE_SIZE
+        {
+            return Err(InvalidTransaction::InitCodeSizeExceeded);
+        }
+
+        let nonce = state.nonce(&self.caller);
+        if let Some(got) = self.nonce.filter(|n| *n != nonce) {
+            return Err(InvalidTransaction::NonceMismatch {
+                expected: nonce,
+                got,
+            });
+        }
+        if nonce == u64::MAX {
+            return Err(InvalidTransaction::NonceOverflow);
+        }
+        if !state.code(&self.caller).is_empty() {
+            return Err(InvalidTransaction::SenderNotEoa);
+        }
+
+        let (price, tip) = self.gas_prices(env, spec)?;
+        let blob_gas = gas::GAS_PER_BLOB * self.blob_hashes.len() as u64;
+        let mut blob_fee = Uint256::zero();
+        let mut max_blob_fee = Uint256::zero();
+        if blob_gas != 0 {
+            let blob_price = env.block.blob_base_fee(spec);
+            let max_price = self.max_fee_per_blob_gas.clone().unwrap_or_default();
+            if max_price < blob_price {
+                return Err(InvalidTransaction::BlobGasPriceTooLow);
+            }
+            blob_fee = Uint256::from(blob_gas) * blob_price;
+            max_blob_fee = Uint256::from(blob_gas) * max_price;
+        }
+        // the sender must afford the worst case, not just the effective price
+        let max_cost = Uint256::from(self.gas_limit) * &self.gas_price + &self.value + max_blob_fee;
+        if state.balance(&self.caller) < max_cost {
+            return Err(InvalidTransaction::InsufficientFunds);
+        }
+
+        state.begin_transaction();
+        let upfront = Uint256::from(self.gas_limit) * &price + blob_fee;
+        state.set_balance(self.caller, state.balance(&self.caller) - upfront);
+        state.set_nonce(self.caller, nonce + 1);
+
+        let mut env = env.clone();
+        env.tx = TxEnv {
+            origin: self.caller,
+            gas_price: price.clone(),
+            blob_hashes: self.blob_hashes.clone(),
+        };
+        state.access_address(self.caller);
+        if spec.is_enabled_in(SpecId::Shanghai) {
+            // EIP-3651
+            state.access_address(env.block.coinbase);
+        }
+        for (address, keys) in &self.access_list {
+            state.access_address(*address);
+            for key in keys {
+                state.access_storage(*address, key.clone());
+            }
+        }
+
+        let gas = self.gas_limit - intrinsic;
+        let result = match self.to {
+            None => {
+                let address = create_address(&self.caller, nonce);
+                state.access_address(address);
+                execute_create(
+                    state,
+                    &mut env,
+                    spec,
+                    self.caller,
+                    address,
+                    self.value.clone(),
+                    self.data.clone(),
+                    gas,
+                    0,
+                )
+            }
+            Some(to) => {
+                state.access_address(to);
+                let message = Message {
+                    kind: CallKind::Call,
+                    caller: self.caller,
+                    address: to,
+                    code_address: to,
+                    value: self.value.clone(),
+                    input: self.data.clone(),
+                    gas,
+                    is_static: false,
+                    depth: 0,
+                };
+                execute_call(state, &mut env, spec, &message)
+            }
+        };
+        let FrameResult {
+            halt,
+            gas_left,
+            output,
+            created_address,
+        } = result;
+
+        // a failed frame restored the state, refund counter included
+        let mut gas_used = self.gas_limit - gas_left;
+        let gas_refunded = (state.refund.max(0) as u64).min(gas::max_refund(gas_used, spec));
+        gas_used = (gas_used - gas_refunded).max(floor);
+
+        let unused = Uint256::from(self.gas_limit - gas_used) * &price;
+        state.add_balance(self.caller, &unused);
+        state.add_balance(env.block.coinbase, &(Uint256::from(gas_used) * tip));
+        state.touch(env.block.coinbase);
+        state.end_transaction(spec.is_enabled_in(SpecId::SpuriousDragon));
+
+        Ok(ExecutionResult {
+            halt,
+            gas_used,
+            gas_refunded,
+            logs: state.logs.clone(),
+            output,
+            created_address,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{InvalidTransaction, Transaction};
+    use crate::{
+        env::Env,
+        spec::SpecId,
+        state::{create_address, State},
+        Uint256,
+    };
+
+    fn funded_state(caller: [u8; 20]) -> State {
+        let mut state = State::default();
+        state.set_balance(caller, Uint256::from(10u32).pow(18));
+        state
+    }
+
+    #[test]
+    fn test_transfer() {
+        let (caller, to) = ([1; 20], [2; 20]);
+        let mut state = funded_state(caller);
+        let mut env = Env::default();
+        env.block.coinbase = [3; 20];
+        env.block.basefee = 7u32.into();
+        let tx = Transaction {
+            caller,
+            to: Some(to),
+            value: 1000u32.into(),
+            gas_limit: 50000,
+            gas_price: 10u32.into(),
+            max_priority_fee_per_gas: Some(2u32.into()),
+            nonce: Some(0),
+            ..Default::default()
+        };
+        let result = tx.execute(&mut state, &env, SpecId::Cancun).unwrap();
+        assert!(result.is_success());
+        assert_eq!(result.gas_used, 21000);
+        assert_eq!(state.balance(&to), 1000u32.into());
+        assert_eq!(state.balance(&[3; 20]), (21000u32 * 2).into());
+        assert_eq!(
+            state.balance(&caller),
+            Uint256::from(10u32).pow(18) - 1000u32 - 21000u32 * 9
+        );
+        assert_eq!(state.nonce(&caller), 1);
+
+        let err = tx.execute(&mut state, &env, SpecId::Cancun).unwrap_err();
+        assert_eq!(
+            err,
+            InvalidTransaction::NonceMismatch {
+                expected: 1,
+                got: 0
+            }
+        );
+    }
+
+    #[test]
+    fn test_sstore_refund() {
+        let (caller, to) = ([1; 20], [2; 20]);
+        let mut state = funded_state(caller);
+        // sstore(0, 1), sstore(0, 0)
+        state.set_code(to, hex::decode("60016000556000600055").unwrap());
+        let tx = Transaction {
+            caller,
+            to: Some(to),
+            gas_limit: 100000,
+            ..Default::default()
+        };
+        let result = tx
+            .execute(&mut state, &Env::default(), SpecId::Cancun)
+            .unwrap();
+        assert!(result.is_success());
+        // 21000 + 4 pushes + cold set + warm reset, a fifth of it refunded
+        let used = 21000 + 12 + 22100 + 100;
+        assert_eq!(result.gas_refunded, used / 5);
+        assert_eq!(result.gas_used, used - used / 5);
+    }
+
+    #[test]
+    fn test_create() {
+        let caller = [1; 20];
+        let mut state = funded_state(caller);
+        // return a single zero byte as runtime code
+        let tx = Transaction {
+            caller,
+            data: hex::decode("60016000f3").unwrap(),
+            gas_limit: 100000,
+            ..Default::default()
+        };
+        let result = tx
+            .execute(&mut state, &Env::default(), SpecId::Cancun)
+            .unwrap();
+        let address = create_address(&caller, 0);
+        assert_eq!(result.created_address, Some(address));
+        assert_eq!(state.code(&address), &[0]);
+        assert_eq!(state.nonce(&address), 1);
+        assert_eq!(state.nonce(&caller), 1);
+    }
+}
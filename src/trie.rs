@@ -0,0 +1,150 @@
+use std::collections::BTreeMap;
+
+use crate::{rlp, util::keccak256};
+
+// Merkle-Patricia trie, nodes are built from the sorted entries when the root is asked for
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Trie {
+    entries: BTreeMap<Vec<u8>, Vec<u8>>,
+}
+
+fn to_nibbles(key: &[u8]) -> Vec<u8> {
+    key.iter().flat_map(|b| [b >> 4, b & 0x0f]).collect()
+}
+
+// compact encoding of a node path, with the leaf flag
+fn hex_prefix(nibbles: &[u8], leaf: bool) -> Vec<u8> {
+    let flag = if leaf { 2 } else { 0 };
+    let mut bytes = if nibbles.len() % 2 == 1 {
+        vec![((flag + 1) << 4) | nibbles[0]]
+    } else {
+        vec![flag << 4]
+    };
+    let rest = &nibbles[nibbles.len() % 2..];
+    bytes.extend(rest.chunks(2).map(|pair| (pair[0] << 4) | pair[1]));
+    bytes
+}
+
+// nodes shorter than a hash are embedded in their parent
+fn reference(node: Vec<u8>) -> Vec<u8> {
+    if node.len() < 32 {
+        node
+    } else {
+        rlp::encode_bytes(&keccak256(&node))
+    }
+}
+
+// RLP of the node holding `entries`, whose keys share the first `depth` nibbles
+fn encode_node(entries: &[(Vec<u8>, &[u8])], depth: usize) -> Vec<u8> {
+    match entries {
+        [] => rlp::encode_bytes(&[]),
+        [(key, value)] => rlp::encode_list(&[
+            rlp::encode_bytes(&hex_prefix(&key[depth..], true)),
+            rlp::encode_bytes(value),
+        ]),
+        [(first, _), .., (last, _)] => {
+            // entries are sorted, so the first and last keys bound the common prefix
+            let shared = first[depth..]
+                .iter()
+                .zip(&last[depth..])
+                .take_while(|(a, b)| a == b)
+                .count();
+            if shared > 0 {
+                let child = encode_node(entries, depth + shared);
+                return rlp::encode_list(&[
+                    rlp::encode_bytes(&hex_prefix(&first[depth..depth + shared], false)),
+                    reference(child),
+                ]);
+            }
+            let mut items = vec![];
+            let mut value = rlp::encode_bytes(&[]);
+            let mut rest = entries;
+            if let [(key, v), tail @ ..] = rest {
+                if key.len() == depth {
+                    value = rlp::encode_bytes(v);
+                    rest = tail;
+                }
+            }
+            for nibble in 0..16 {
+                let end = rest
+                    .iter()
+                    .position(|(key, _)| key[depth] != nibble)
+                    .unwrap_or(rest.len());
+                let (children, tail) = rest.split_at(end);
+                items.push(match children {
+                    [] => rlp::encode_bytes(&[]),
+                    _ => reference(encode_node(children, depth + 1)),
+                });
+                rest = tail;
+            }
+            items.push(value);
+            rlp::encode_list(&items)
+        }
+    }
+}
+
+impl Trie {
+    // empty values delete the key, like in the state trie
+    pub fn insert(&mut self, key: &[u8], value: Vec<u8>) {
+        if value.is_empty() {
+            self.entries.remove(key);
+        } else {
+            self.entries.insert(key.to_vec(), value);
+        }
+    }
+
+    pub fn get(&self, key: &[u8]) -> Option<&[u8]> {
+        self.entries.get(key).map(|v| v.as_slice())
+    }
+
+    pub fn remove(&mut self, key: &[u8]) -> Option<Vec<u8>> {
+        self.entries.remove(key)
+    }
+
+    pub fn root(&self) -> [u8; 32] {
+        let entries: Vec<(Vec<u8>, &[u8])> = self
+            .entries
+            .iter()
+            .map(|(key, value)| (to_nibbles(key), value.as_slice()))
+            .collect();
+        keccak256(&encode_node(&entries, 0))
+    }
+}
+
+impl FromIterator<(Vec<u8>, Vec<u8>)> for Trie {
+    fn from_iter<T: IntoIterator<Item = (Vec<u8>, Vec<u8>)>>(iter: T) -> Self {
+        let mut trie = Trie::default();
+        for (key, value) in iter {
+            trie.insert(&key, value);
+        }
+        trie
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Trie;
+
+    #[test]
+    fn test_trie_root() {
+        assert_eq!(
+            hex::encode(Trie::default().root()),
+            "56e81f171bcc55a6ff8345e692c0f86e5b48e01b996cadc001622fb5e363b421"
+        );
+        let mut trie: Trie = [
+            ("doe", "reindeer"),
+            ("dog", "puppy"),
+            ("dogglesworth", "cat"),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.as_bytes().to_vec(), v.as_bytes().to_vec()))
+        .collect();
+        assert_eq!(
+            hex::encode(trie.root()),
+            "8aad789dff2f538bca5d8ea56e8abe10f4c7ba3a5dea95fea4cd6e7c3a1168d3"
+        );
+        trie.insert(b"dogglesworth", vec![]);
+        assert_eq!(trie.get(b"dogglesworth"), None);
+        assert_eq!(trie.get(b"dog"), Some(&b"puppy"[..]));
+    }
+}
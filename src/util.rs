@@ -1,6 +1,7 @@
-use num_bigint::ToBigUint;
+use num_bigint::{BigInt, Sign, ToBigUint};
+use sha3::{Digest, Keccak256};
 
-use crate::Uint256;
+use crate::{Address, Uint256};
 
 pub trait Uint256Util
 where
@@ -13,11 +14,50 @@ where
 impl Uint256Util for Uint256 {
     fn fit(&self) -> Self {
         let a = self.to_biguint().unwrap().to_u32_digits();
-        let b = if a.len() >= 8 {
-            a[a.len() - 8..].to_vec()
-        } else {
-            a
-        };
+        // digits are little-endian, keep the low 256 bits
+        let b = if a.len() >= 8 { a[..8].to_vec() } else { a };
         Self::new(b)
     }
 }
+
+pub fn max_uint256() -> Uint256 {
+    (Uint256::from(1u32) << 256) - 1u32
+}
+
+// interpret as a two's complement signed integer
+pub fn to_signed(value: &Uint256) -> BigInt {
+    if value.bit(255) {
+        BigInt::from_biguint(Sign::Plus, value.clone()) - (BigInt::from(1) << 256)
+    } else {
+        BigInt::from_biguint(Sign::Plus, value.clone())
+    }
+}
+
+pub fn from_signed(value: &BigInt) -> Uint256 {
+    let modulus: BigInt = BigInt::from(1) << 256;
+    let v: BigInt = ((value % &modulus) + &modulus) % &modulus;
+    v.to_biguint().unwrap()
+}
+
+// big-endian, left padded to 32 bytes
+pub fn to_bytes32(value: &Uint256) -> [u8; 32] {
+    let bytes = value.fit().to_bytes_be();
+    let mut result = [0u8; 32];
+    result[32 - bytes.len()..].copy_from_slice(&bytes);
+    result
+}
+
+pub fn keccak256(data: &[u8]) -> [u8; 32] {
+    Keccak256::digest(data).into()
+}
+
+pub fn address_to_uint(address: &Address) -> Uint256 {
+    Uint256::from_bytes_be(address)
+}
+
+// lower 20 bytes of a stack word
+pub fn uint_to_address(value: &Uint256) -> Address {
+    let mut address = [0u8; 20];
+    address.copy_from_slice(&to_bytes32(value)[12..]);
+    address
+}
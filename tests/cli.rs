@@ -0,0 +1,30 @@
+use std::process::Command;
+
+fn run(args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_evm-utils"))
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn test_json_output() {
+    // deploy code: the constructor returns a runtime adding two words, and one argument follows
+    let code = [
+        "600f80600a5f395ff3fe",
+        "6000356020350160005260206000f3",
+        "00000000000000000000000000000000000000000000000000000000000000ff",
+    ]
+    .concat();
+    let stdout = run(&[&code, "--syntax", "json"]);
+    let document: serde_json::Value = serde_json::from_str(&stdout).unwrap();
+    assert_eq!(document["init"][0]["mnemonic"], "PUSH1");
+    assert_eq!(document["runtime"][1]["mnemonic"], "CALLDATALOAD");
+    assert_eq!(document["constructor_args"], format!("0x{:064x}", 0xff));
+
+    let stdout = run(&[&code, "--syntax", "json", "--part", "runtime"]);
+    let document: serde_json::Value = serde_json::from_str(&stdout).unwrap();
+    assert_eq!(document.as_array().unwrap().len(), 10);
+}